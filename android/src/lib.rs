@@ -0,0 +1,7 @@
+use bevy::prelude::bevy_main;
+
+/// Entry point for the Android activity, which loads this library instead of running the desktop binary.
+#[bevy_main]
+pub fn main() {
+    bevy_pong::main();
+}
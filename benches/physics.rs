@@ -0,0 +1,124 @@
+use std::f32::consts::PI;
+
+use bevy::math::Vec2;
+use bevy_pong::physics::{self, Aabb, BallBody, BallEvent, BallTuning, Body, Obstacle, ObstacleKind};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+const DT: f32 = 1f32/64f32;
+const HALF_ARENA: Vec2 = Vec2::new(320f32, 240f32);
+const BALL_HALF_SIZE: Vec2 = Vec2::new(4f32, 4f32);
+const PADDLE_HALF_SIZE: Vec2 = Vec2::new(4f32, 32f32);
+const MAX_STEP: f32 = 8f32;
+const EPSILON: f32 = 0.01f32;
+
+fn bench_contacts(c: &mut Criterion) {
+    let paddle = Aabb::new(Vec2::new(-300f32, 0f32), PADDLE_HALF_SIZE);
+    c.bench_function("sweep hit", |b| {
+        b.iter(|| physics::sweep(black_box(Vec2::new(-280f32, 10f32)), black_box(Vec2::new(-300f32, 12f32)), BALL_HALF_SIZE, paddle))
+    });
+    c.bench_function("sweep miss", |b| {
+        b.iter(|| physics::sweep(black_box(Vec2::new(0f32, 10f32)), black_box(Vec2::new(8f32, 12f32)), BALL_HALF_SIZE, paddle))
+    });
+    let a = Body { pos: Vec2::new(0f32, 0f32), vel: Vec2::new(100f32, 20f32) };
+    let other = Body { pos: Vec2::new(6f32, 1f32), vel: Vec2::new(-100f32, 0f32) };
+    c.bench_function("collide_boxes", |b| {
+        b.iter(|| physics::collide_boxes(black_box(a), black_box(other), BALL_HALF_SIZE))
+    });
+}
+
+fn bench_bounces(c: &mut Criterion) {
+    let vel = Vec2::new(-256f32, 64f32);
+    c.bench_function("paddle_bounce", |b| {
+        b.iter(|| physics::paddle_bounce(black_box(vel), 1f32, black_box(0.5f32), PI/4f32, 0.5f32))
+    });
+    c.bench_function("apply_surface", |b| {
+        b.iter(|| physics::apply_surface(black_box(vel), Vec2::Y, black_box(0.9f32), black_box(0.1f32)))
+    });
+    c.bench_function("magnus", |b| {
+        b.iter(|| physics::magnus(black_box(vel), black_box(3f32), 0.5f32, DT))
+    });
+}
+
+const TUNING: BallTuning = BallTuning {
+    half_size: BALL_HALF_SIZE,
+    acceleration: 8f32,
+    max_speed: 512f32,
+    lift: 0f32,
+    magnus_coefficient: 0.5f32,
+    spin_decay: 0.5f32,
+    spin_per_hit: 2f32,
+    max_step: MAX_STEP,
+    max_angle: PI/4f32,
+    min_horizontal_ratio: 0.5f32,
+    epsilon: EPSILON,
+};
+
+struct Court {
+    obstacles: Vec<Obstacle>,
+    balls: Vec<BallBody>,
+    bodies: Vec<Body>,
+    events: Vec<BallEvent>,
+}
+
+impl Court {
+    fn new(balls: usize) -> Self {
+        let wall_size = Vec2::new(HALF_ARENA.x, 16f32);
+        let wall = |aabb| Obstacle { aabb, kind: ObstacleKind::Wall, restitution: 0.98f32, friction: 0.02f32 };
+        let paddle = |aabb| Obstacle { aabb, kind: ObstacleKind::Paddle { dir: 0f32 }, restitution: 1.02f32, friction: 0f32 };
+        let obstacles = vec![
+            wall(Aabb::new(Vec2::new(0f32, HALF_ARENA.y + 16f32), wall_size)),
+            wall(Aabb::new(Vec2::new(0f32, -HALF_ARENA.y - 16f32), wall_size)),
+            paddle(Aabb::new(Vec2::new(-HALF_ARENA.x + 16f32, 0f32), Vec2::new(PADDLE_HALF_SIZE.x, HALF_ARENA.y))),
+            paddle(Aabb::new(Vec2::new(HALF_ARENA.x - 16f32, 0f32), Vec2::new(PADDLE_HALF_SIZE.x, HALF_ARENA.y))),
+        ];
+        // Spread out on a grid, each heading off at its own angle, so the same court is built every run.
+        let columns = (balls as f32).sqrt().ceil().max(1f32) as usize;
+        let spacing = (HALF_ARENA - 48f32) * 2f32 / columns as f32;
+        let balls = (0..balls)
+            .map(|i| BallBody {
+                pos: -HALF_ARENA + 48f32 + spacing * Vec2::new((i % columns) as f32 + 0.5f32, (i / columns) as f32 + 0.5f32),
+                vel: physics::from_angle(i as f32 * 2.39996f32) * 256f32,
+                speed: 256f32,
+                spin: 0f32,
+            })
+            .collect();
+        Court { obstacles, balls, bodies: Vec::new(), events: Vec::new() }
+    }
+
+    /// One fixed tick, done the way the game does it: the game's own ball step against the walls and paddles,
+    /// then the game's own pass over every pair of balls.
+    fn tick(&mut self) {
+        for ball in self.balls.iter_mut() {
+            self.events.clear();
+            physics::step_ball(ball, &self.obstacles, &TUNING, DT, &mut self.events);
+        }
+        self.bodies.clear();
+        self.bodies.extend(self.balls.iter().map(|ball| Body { pos: ball.pos, vel: ball.vel }));
+        physics::collide_all(&mut self.bodies, BALL_HALF_SIZE, |_, _| true);
+        for (ball, body) in self.balls.iter_mut().zip(&self.bodies) {
+            ball.pos = body.pos;
+            ball.vel = body.vel;
+        }
+    }
+}
+
+fn bench_stress(c: &mut Criterion) {
+    let mut group = c.benchmark_group("many balls, one second");
+    for balls in [1, 16, 128, 512] {
+        group.bench_with_input(BenchmarkId::from_parameter(balls), &balls, |b, &balls| {
+            b.iter_batched_ref(
+                || Court::new(balls),
+                |court| {
+                    for _ in 0..64 {
+                        court.tick();
+                    }
+                },
+                criterion::BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_contacts, bench_bounces, bench_stress);
+criterion_main!(benches);
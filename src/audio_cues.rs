@@ -0,0 +1,177 @@
+use std::{collections::VecDeque, time::Duration};
+
+use bevy::{
+    audio::{AudioSinkPlayback, Pitch, SpatialListener, Volume},
+    prelude::*,
+};
+
+use crate::{settings::Settings, spawn_toast, Arena, Ball, GameState, GoalScored, MatchOver, PauseState, Score, Side};
+
+/// World units to audio units, so a ball at either end of the court pans hard to that side.
+pub const SPATIAL_SCALE: f32 = 1f32/256f32;
+const BALL_TONE_HZ: f32 = 440f32;
+/// How many octaves the ball's tone climbs from the bottom of the court to the top.
+const PITCH_RANGE_OCTAVES: f32 = 1.5f32;
+const BALL_TONE_VOLUME: f32 = 0.25f32;
+const NOTE_SECONDS: f32 = 0.12f32;
+const NOTE_VOLUME: f32 = 0.5f32;
+const POINT_FOR: [f32; 2] = [523.25f32, 783.99f32];
+const POINT_AGAINST: [f32; 2] = [783.99f32, 523.25f32];
+const MATCH_WON: [f32; 3] = [523.25f32, 659.25f32, 1046.5f32];
+const MATCH_LOST: [f32; 3] = [392f32, 329.63f32, 261.63f32];
+/// The score is counted out after each point: a high tick per player point, then a low one per opponent point.
+const PLAYER_TICK: f32 = 1318.5f32;
+const ENEMY_TICK: f32 = 220f32;
+
+/// The ball's hum, which follows it around the court.
+#[derive(Component)]
+pub struct BallTone;
+
+/// Notes still to play, one after another.
+#[derive(Component)]
+pub struct Earcon {
+    notes: VecDeque<f32>,
+    timer: Timer,
+}
+
+impl Earcon {
+    fn new(notes: impl IntoIterator<Item = f32>) -> Self {
+        Earcon {
+            notes: notes.into_iter().collect(),
+            timer: Timer::from_seconds(NOTE_SECONDS, TimerMode::Repeating),
+        }
+    }
+}
+
+fn note(pitches: &mut Assets<Pitch>, hz: f32) -> PitchBundle {
+    PitchBundle {
+        source: pitches.add(Pitch::new(hz, Duration::from_secs_f32(NOTE_SECONDS * 0.8f32))),
+        settings: PlaybackSettings::DESPAWN.with_volume(Volume::new(NOTE_VOLUME)),
+    }
+}
+
+/// Ctrl+U switches audio cues on and off.
+pub fn toggle_audio_cues(
+    mut cmd: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    arena: Res<Arena>,
+    mut settings: ResMut<Settings>,
+) {
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if !ctrl || !keyboard_input.just_pressed(KeyCode::KeyU) {
+        return;
+    }
+    settings.accessibility.audio_cues = !settings.accessibility.audio_cues;
+    let state = if settings.accessibility.audio_cues { "on" } else { "off" };
+    spawn_toast(&mut cmd, &arena, format!("Audio cues {}", state));
+}
+
+/// The camera hears the court with ears as far apart as the court is wide.
+pub fn attach_listener(mut cmd: Commands, arena: Res<Arena>, cameras: Query<Entity, (With<Camera2d>, Without<SpatialListener>)>) {
+    for camera in cameras.iter() {
+        cmd.entity(camera).insert(SpatialListener::new(arena.half_size.x * 2f32));
+    }
+}
+
+/// Gives every ball its hum while audio cues are on, and silences them all when they're turned off.
+pub fn sync_ball_tones(
+    mut cmd: Commands,
+    settings: Res<Settings>,
+    mut pitches: ResMut<Assets<Pitch>>,
+    balls: Query<(Entity, Option<&Children>), With<Ball>>,
+    tones: Query<Entity, With<BallTone>>,
+) {
+    if !settings.accessibility.audio_cues {
+        for tone in tones.iter() {
+            cmd.entity(tone).despawn_recursive();
+        }
+        return;
+    }
+    for (entity, children) in balls.iter() {
+        if children.is_some_and(|children| children.iter().any(|child| tones.contains(*child))) {
+            continue;
+        }
+        let tone = cmd.spawn((
+            PitchBundle {
+                source: pitches.add(Pitch::new(BALL_TONE_HZ, Duration::from_secs(1))),
+                settings: PlaybackSettings::LOOP.with_volume(Volume::new(BALL_TONE_VOLUME)).with_spatial(true),
+            },
+            TransformBundle::default(),
+            BallTone,
+        )).id();
+        cmd.entity(entity).add_child(tone);
+    }
+}
+
+/// Pitch follows the ball's height, and the hum only plays while the ball is in play.
+pub fn update_ball_tones(
+    arena: Res<Arena>,
+    state: Res<State<GameState>>,
+    pause: Res<State<PauseState>>,
+    balls: Query<&Transform, With<Ball>>,
+    tones: Query<(&Parent, &SpatialAudioSink), With<BallTone>>,
+) {
+    let playing = *state.get() == GameState::Started && *pause.get() == PauseState::Running;
+    for (parent, sink) in tones.iter() {
+        let Ok(transform) = balls.get(parent.get()) else {
+            continue;
+        };
+        let height = (transform.translation.y / arena.half_size.y).clamp(-1f32, 1f32);
+        sink.set_speed(2f32.powf(height * PITCH_RANGE_OCTAVES / 2f32));
+        if playing && sink.is_paused() {
+            sink.play();
+        }
+        else if !playing && !sink.is_paused() {
+            sink.pause();
+        }
+    }
+}
+
+/// Says who scored, rising for the player and falling for the opponent, then counts out the score.
+pub fn announce_points(
+    mut cmd: Commands,
+    settings: Res<Settings>,
+    score: Res<Score>,
+    mut goals: EventReader<GoalScored>,
+    mut match_over: EventReader<MatchOver>,
+) {
+    let over = match_over.read().last().map(|over| over.winner);
+    let Some(scorer) = goals.read().last().map(|goal| goal.scorer) else {
+        return;
+    };
+    if !settings.accessibility.audio_cues {
+        return;
+    }
+    let mut notes: Vec<f32> = match (over, scorer) {
+        (Some(Side::Left), _) => MATCH_WON.to_vec(),
+        (Some(Side::Right), _) => MATCH_LOST.to_vec(),
+        (None, Side::Left) => POINT_FOR.to_vec(),
+        (None, Side::Right) => POINT_AGAINST.to_vec(),
+    };
+    notes.push(0f32);
+    notes.extend((0..score.player).map(|_| PLAYER_TICK));
+    notes.push(0f32);
+    notes.extend((0..score.enemy).map(|_| ENEMY_TICK));
+    cmd.spawn(Earcon::new(notes));
+}
+
+/// A note of zero hertz is a rest.
+pub fn play_earcons(
+    mut cmd: Commands,
+    time: Res<Time<Real>>,
+    mut pitches: ResMut<Assets<Pitch>>,
+    mut earcons: Query<(Entity, &mut Earcon)>,
+) {
+    for (entity, mut earcon) in earcons.iter_mut() {
+        if !earcon.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+        match earcon.notes.pop_front() {
+            Some(hz) if hz > 0f32 => {
+                cmd.spawn(note(&mut pitches, hz));
+            },
+            Some(_) => {},
+            None => cmd.entity(entity).despawn(),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{chat::NetworkController, Arena, Ball, GameState, Paddle, Score, ServeRequested, Side};
+
+/// What a bot sends, one JSON object per line: `{"dir": 1}` moves up, `-1` down, `0` stops;
+/// `"serve": true` serves.
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+struct BotCommand {
+    dir: i32,
+    serve: bool,
+}
+
+#[derive(Serialize)]
+struct BotBall {
+    pos: Vec2,
+    vel: Vec2,
+}
+
+/// What the bot receives every tick, one JSON object per line.
+#[derive(Serialize)]
+struct BotState<'a> {
+    state: &'a GameState,
+    score: &'a Score,
+    arena_half_size: Vec2,
+    paddle_y: f32,
+    enemy_y: f32,
+    balls: Vec<BotBall>,
+}
+
+/// A local TCP port an external bot connects to, to play the left paddle against the built-in AI.
+#[derive(Resource)]
+pub struct BotApi {
+    listener: TcpListener,
+    bot: Option<TcpStream>,
+    received: Vec<u8>,
+}
+
+impl BotApi {
+    /// Only listens on localhost; it's meant for bots on the same machine.
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], port)))?;
+        listener.set_nonblocking(true)?;
+        Ok(BotApi { listener, bot: None, received: Vec::new() })
+    }
+}
+
+/// Takes every complete line out of `received` and parses it, skipping lines that aren't commands.
+fn take_commands(received: &mut Vec<u8>) -> Vec<BotCommand> {
+    let Some(end) = received.iter().rposition(|byte| *byte == b'\n') else {
+        return Vec::new();
+    };
+    let lines: Vec<u8> = received.drain(..=end).collect();
+    lines
+        .split(|byte| *byte == b'\n')
+        .filter_map(|line| serde_json::from_slice(line).ok())
+        .collect()
+}
+
+pub fn attach_bot_controller(mut cmd: Commands, paddles: Query<(Entity, &Side), With<Paddle>>) {
+    for (paddle, side) in paddles.iter() {
+        if *side == Side::Left {
+            cmd.entity(paddle).insert(NetworkController::default());
+        }
+    }
+}
+
+pub fn receive_bot_commands(
+    mut api: ResMut<BotApi>,
+    mut serve: ResMut<ServeRequested>,
+    mut controllers: Query<(&mut NetworkController, &Side)>,
+) {
+    let api = &mut *api;
+    if let Ok((stream, addr)) = api.listener.accept() {
+        if stream.set_nonblocking(true).is_ok() {
+            info!("Bot connected from {}", addr);
+            api.bot = Some(stream);
+            api.received.clear();
+        }
+    }
+    let Some(bot) = api.bot.as_mut() else {
+        return;
+    };
+    let mut buf = [0u8; 1024];
+    loop {
+        match bot.read(&mut buf) {
+            Ok(0) => {
+                info!("Bot disconnected");
+                api.bot = None;
+                break;
+            },
+            Ok(len) => api.received.extend_from_slice(&buf[..len]),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+            Err(err) => {
+                warn!("Lost the bot: {}", err);
+                api.bot = None;
+                break;
+            },
+        }
+    }
+    for command in take_commands(&mut api.received) {
+        for (mut controller, _) in controllers.iter_mut().filter(|(_, side)| **side == Side::Left) {
+            controller.dir = command.dir.signum();
+        }
+        serve.0 |= command.serve;
+    }
+}
+
+pub fn send_bot_state(
+    mut api: ResMut<BotApi>,
+    arena: Res<Arena>,
+    state: Res<State<GameState>>,
+    score: Res<Score>,
+    paddles: Query<(&Transform, &Side), With<Paddle>>,
+    balls: Query<(&Transform, &Ball)>,
+) {
+    let paddle = |side| paddles.iter().find(|(_, paddle_side)| **paddle_side == side).map(|(transform, _)| transform);
+    let (Some(bot), Some(player), Some(enemy)) = (api.bot.as_mut(), paddle(Side::Left), paddle(Side::Right)) else {
+        return;
+    };
+    let bot_state = BotState {
+        state: state.get(),
+        score: &score,
+        arena_half_size: arena.half_size,
+        paddle_y: player.translation.y,
+        enemy_y: enemy.translation.y,
+        balls: balls
+            .iter()
+            .map(|(transform, ball)| BotBall { pos: transform.translation.truncate(), vel: ball.vel })
+            .collect(),
+    };
+    let mut line = serde_json::to_vec(&bot_state).expect("bot state always serializes");
+    line.push(b'\n');
+    // A bot too slow to keep up just misses ticks.
+    if let Err(err) = bot.write_all(&line) {
+        if err.kind() != io::ErrorKind::WouldBlock {
+            warn!("Lost the bot: {}", err);
+            api.bot = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_complete_command_lines() {
+        let mut received = b"{\"dir\": 1}\nnonsense\n{\"serve\": true}\n{\"dir\"".to_vec();
+        assert_eq!(take_commands(&mut received), vec![
+            BotCommand { dir: 1, serve: false },
+            BotCommand { dir: 0, serve: true },
+        ]);
+        assert_eq!(received, b"{\"dir\"");
+    }
+}
@@ -0,0 +1,15 @@
+use bevy::prelude::*;
+
+use crate::Paddle;
+
+/// Steers a paddle from outside the game instead of the AI.
+#[derive(Component, Debug, Default)]
+pub struct NetworkController {
+    pub dir: i32,
+}
+
+pub fn apply_network_control(mut paddles: Query<(&NetworkController, &mut Paddle)>) {
+    for (controller, mut paddle) in paddles.iter_mut() {
+        paddle.dir = controller.dir;
+    }
+}
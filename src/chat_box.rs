@@ -0,0 +1,166 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, window::ReceivedCharacter};
+
+use crate::{profiles::Profiles, settings::Settings, Arena};
+
+const HISTORY_SIZE: usize = 6;
+const MESSAGE_LIFETIME: f32 = 10f32;
+const MAX_MESSAGE_LEN: usize = 120;
+const FONT_SIZE: f32 = 14f32;
+const BLOCKED_WORDS: &[&str] = &["ass", "asshole", "bastard", "bitch", "crap", "damn", "dick", "fuck", "piss", "shit"];
+
+/// A chat line that arrived over the network.
+#[derive(Event)]
+pub struct ChatReceived {
+    pub name: String,
+    pub text: String,
+}
+
+/// A chat line the local player wrote, for the network to pass on.
+#[derive(Event)]
+pub struct ChatSent {
+    pub name: String,
+    pub text: String,
+}
+
+struct ChatLine {
+    name: String,
+    text: String,
+    received: f32,
+}
+
+/// The message history and, while the box is open, the line being typed.
+#[derive(Resource, Default)]
+pub struct ChatBox {
+    draft: Option<String>,
+    history: VecDeque<ChatLine>,
+}
+
+impl ChatBox {
+    pub fn is_open(&self) -> bool {
+        self.draft.is_some()
+    }
+}
+
+#[derive(Component)]
+pub struct ChatOverlay;
+
+/// Stars out blocked words, ignoring case and surrounding punctuation.
+fn filter_profanity(text: &str) -> String {
+    text.split(' ')
+        .map(|word| {
+            let bare = word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+            if BLOCKED_WORDS.contains(&bare.as_str()) { "*".repeat(word.chars().count()) } else { word.to_string() }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn spawn_chat_overlay(mut cmd: Commands, arena: Res<Arena>) {
+    cmd.spawn((
+        Text2dBundle {
+            text: Text::from_section("", TextStyle {
+                font_size: FONT_SIZE,
+                ..default()
+            }),
+            text_anchor: bevy::sprite::Anchor::BottomLeft,
+            transform: Transform::from_xyz(-arena.half_size.x + 8f32, -arena.half_size.y + 8f32, 3f32),
+            ..default()
+        },
+        ChatOverlay,
+    ));
+}
+
+/// Enter opens the box and sends the line, Escape throws it away.
+pub fn type_chat(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    time: Res<Time<Real>>,
+    profiles: Res<Profiles>,
+    mut chat: ResMut<ChatBox>,
+    mut sent: EventWriter<ChatSent>,
+) {
+    let Some(draft) = chat.draft.as_mut() else {
+        characters.clear();
+        // Alt+Enter is fullscreen.
+        let alt = keyboard_input.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
+        if keyboard_input.just_pressed(KeyCode::Enter) && !alt {
+            chat.draft = Some(String::new());
+        }
+        return;
+    };
+
+    for character in characters.read() {
+        for c in character.char.chars().filter(|c| !c.is_control()) {
+            if draft.chars().count() < MAX_MESSAGE_LEN {
+                draft.push(c);
+            }
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Backspace) {
+        draft.pop();
+    }
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        chat.draft = None;
+    }
+    else if keyboard_input.just_pressed(KeyCode::Enter) {
+        let text = chat.draft.take().unwrap_or_default().trim().to_string();
+        if !text.is_empty() {
+            let name = profiles.active().name.clone();
+            chat.history.push_back(ChatLine { name: name.clone(), text: text.clone(), received: time.elapsed_seconds() });
+            sent.send(ChatSent { name, text });
+        }
+    }
+}
+
+pub fn receive_chat(time: Res<Time<Real>>, mut received: EventReader<ChatReceived>, mut chat: ResMut<ChatBox>) {
+    for message in received.read() {
+        chat.history.push_back(ChatLine {
+            name: message.name.clone(),
+            text: message.text.chars().take(MAX_MESSAGE_LEN).collect(),
+            received: time.elapsed_seconds(),
+        });
+    }
+    while chat.history.len() > HISTORY_SIZE {
+        chat.history.pop_front();
+    }
+}
+
+/// Recent lines fade out after a while, but the whole history shows while typing.
+pub fn update_chat_overlay(
+    time: Res<Time<Real>>,
+    settings: Res<Settings>,
+    chat: Res<ChatBox>,
+    mut overlays: Query<&mut Text, With<ChatOverlay>>,
+) {
+    let now = time.elapsed_seconds();
+    let mut lines: Vec<String> = chat.history
+        .iter()
+        .filter(|line| chat.is_open() || now - line.received < MESSAGE_LIFETIME)
+        .map(|line| {
+            let text = if settings.net.chat_filter { filter_profanity(&line.text) } else { line.text.clone() };
+            format!("{}: {}", line.name, text)
+        })
+        .collect();
+    if let Some(draft) = &chat.draft {
+        lines.push(format!("> {}_", draft));
+    }
+    let value = lines.join("\n");
+    for mut text in overlays.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_blocked_words() {
+        assert_eq!(filter_profanity("well SHIT, nice shot"), "well ***** nice shot");
+        assert_eq!(filter_profanity("class assignment"), "class assignment");
+    }
+}
@@ -0,0 +1,181 @@
+use bevy::prelude::*;
+
+#[cfg(feature = "net")]
+use crate::{chat_box::ChatBox, lobby::Lobby};
+use crate::{
+    replay::Replay,
+    rules::{MatchRules, Mutators},
+    spawn_toast, Arena, GameConfig,
+};
+
+/// One step of a cheat code, from the arrow keys and A/B, or a gamepad's d-pad and face buttons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheatInput {
+    Up,
+    Down,
+    Left,
+    Right,
+    B,
+    A,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cheat {
+    GiantBall,
+    TinyPaddles,
+}
+
+use CheatInput::*;
+
+const CODES: &[(&[CheatInput], Cheat)] = &[
+    (&[Up, Up, Down, Down, Left, Right, Left, Right, B, A], Cheat::GiantBall),
+    (&[Down, Down, Up, Up, Right, Left, Right, Left, A, B], Cheat::TinyPaddles),
+];
+
+const KEYS: [(KeyCode, CheatInput); 6] = [
+    (KeyCode::ArrowUp, Up),
+    (KeyCode::ArrowDown, Down),
+    (KeyCode::ArrowLeft, Left),
+    (KeyCode::ArrowRight, Right),
+    (KeyCode::KeyB, B),
+    (KeyCode::KeyA, A),
+];
+
+const BUTTONS: [(GamepadButtonType, CheatInput); 6] = [
+    (GamepadButtonType::DPadUp, Up),
+    (GamepadButtonType::DPadDown, Down),
+    (GamepadButtonType::DPadLeft, Left),
+    (GamepadButtonType::DPadRight, Right),
+    (GamepadButtonType::East, B),
+    (GamepadButtonType::South, A),
+];
+
+impl Cheat {
+    pub fn name(self) -> &'static str {
+        match self {
+            Cheat::GiantBall => "Giant ball",
+            Cheat::TinyPaddles => "Tiny paddles",
+        }
+    }
+
+    /// Turns the cheat's mutator on, returning false if it already was.
+    pub fn unlock(self, mutators: &mut Mutators) -> bool {
+        let flag = match self {
+            Cheat::GiantBall => &mut mutators.giant_ball,
+            Cheat::TinyPaddles => &mut mutators.tiny_paddles,
+        };
+        !std::mem::replace(flag, true)
+    }
+}
+
+/// Matches the latest inputs against every code. A wrong step doesn't throw away a code that's
+/// still in progress, since the codes are matched against the tail of the inputs.
+#[derive(Resource, Default)]
+pub struct CheatMatcher {
+    recent: Vec<CheatInput>,
+}
+
+impl CheatMatcher {
+    pub fn push(&mut self, input: CheatInput) -> Option<Cheat> {
+        let longest = CODES.iter().map(|(code, _)| code.len()).max().unwrap_or(0);
+        if self.recent.len() >= longest {
+            self.recent.remove(0);
+        }
+        self.recent.push(input);
+        let cheat = CODES.iter().find(|(code, _)| self.recent.ends_with(code)).map(|(_, cheat)| *cheat);
+        if cheat.is_some() {
+            self.recent.clear();
+        }
+        cheat
+    }
+}
+
+fn read_cheats(
+    keyboard_input: &ButtonInput<KeyCode>,
+    gamepad_buttons: &ButtonInput<GamepadButton>,
+    matcher: &mut CheatMatcher,
+) -> Vec<Cheat> {
+    let keys = KEYS.iter().filter(|(key, _)| keyboard_input.just_pressed(*key)).map(|(_, input)| *input);
+    let buttons = gamepad_buttons.get_just_pressed().filter_map(|button| {
+        BUTTONS.iter().find(|(button_type, _)| *button_type == button.button_type).map(|(_, input)| *input)
+    });
+    keys.chain(buttons).filter_map(|input| matcher.push(input)).collect()
+}
+
+/// Codes typed on the pause menu of an offline match take effect from the next tick.
+pub fn enter_pause_cheats(
+    mut cmd: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    replay: Res<Replay>,
+    arena: Res<Arena>,
+    mut matcher: ResMut<CheatMatcher>,
+    mut rules: ResMut<MatchRules>,
+    mut config: ResMut<GameConfig>,
+) {
+    // A replay has to play out with the rules it was recorded with.
+    if replay.is_playing() {
+        return;
+    }
+    for cheat in read_cheats(&keyboard_input, &gamepad_buttons, &mut matcher) {
+        let mut unlocked = Mutators::default();
+        if cheat.unlock(&mut unlocked) && cheat.unlock(&mut rules.mutators) {
+            unlocked.apply(&mut config);
+            spawn_toast(&mut cmd, &arena, format!("Cheat unlocked: {}", cheat.name()));
+        }
+    }
+}
+
+/// Only the host enters codes in the lobby; they change the rules like any other mutator.
+#[cfg(feature = "net")]
+pub fn enter_lobby_cheats(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    chat: Res<ChatBox>,
+    mut matcher: ResMut<CheatMatcher>,
+    mut lobby: ResMut<Lobby>,
+) {
+    if chat.is_open() {
+        return;
+    }
+    for cheat in read_cheats(&keyboard_input, &gamepad_buttons, &mut matcher) {
+        if cheat.unlock(&mut lobby.rules.mutators) {
+            lobby.ready = [false, false];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enter(matcher: &mut CheatMatcher, inputs: &[CheatInput]) -> Option<Cheat> {
+        inputs.iter().filter_map(|input| matcher.push(*input)).last()
+    }
+
+    #[test]
+    fn konami_code_unlocks_giant_ball() {
+        let mut matcher = CheatMatcher::default();
+        assert_eq!(enter(&mut matcher, &[Up, Up, Down, Down, Left, Right, Left, Right, B, A]), Some(Cheat::GiantBall));
+    }
+
+    #[test]
+    fn stray_inputs_before_a_code_are_ignored() {
+        let mut matcher = CheatMatcher::default();
+        assert_eq!(enter(&mut matcher, &[A, Up, Up, Up, Down, Down, Left, Right, Left, Right, B, A]), Some(Cheat::GiantBall));
+    }
+
+    #[test]
+    fn a_wrong_step_needs_the_code_again() {
+        let mut matcher = CheatMatcher::default();
+        assert_eq!(enter(&mut matcher, &[Up, Up, Down, Down, Left, Left, Right, Left, Right, B, A]), None);
+    }
+
+    #[test]
+    fn unlocking_twice_changes_nothing() {
+        let mut mutators = Mutators::default();
+        assert!(Cheat::TinyPaddles.unlock(&mut mutators));
+        assert!(!Cheat::TinyPaddles.unlock(&mut mutators));
+        assert!(mutators.tiny_paddles);
+    }
+}
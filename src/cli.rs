@@ -0,0 +1,450 @@
+use std::{net::SocketAddr, path::PathBuf};
+
+use bevy::window::PresentMode;
+use serde::Serialize;
+
+use crate::settings::Difficulty;
+
+const USAGE: &str = "\
+Usage: kpong [OPTIONS]
+
+Options:
+  --size <WxH>              Window size in pixels
+  --fullscreen              Start in borderless fullscreen
+  --vsync <MODE>            on, off, adaptive, fifo, mailbox or immediate
+  --monitor <N>             Open centered on monitor N, counting from 0
+  --difficulty <easy|normal|hard>
+                            Enemy difficulty for this session
+  --seed <N>                Seed for the match RNG
+  --profile <NAME>          Play as NAME, creating the profile if needed
+  --replay <PATH>           Watch a recorded replay
+  --watch <CODE>            Watch the replay shared on the relay server as CODE
+  --layout <PATH>           Start matches with the paddles, balls and HUD described in PATH
+  --host <PORT>             Host a LAN match on PORT
+  --join <ADDR:PORT>        Join a LAN match hosted at ADDR:PORT
+  --spectate <ADDR:PORT>    Watch a LAN match hosted at ADDR:PORT
+  --rollback                Use rollback netcode for the LAN match
+  --input-delay <FRAMES>    Input delay for rollback netcode
+  --server <PORT>           Run a dedicated server on PORT for two players to --join
+  --tournament <ENTRANTS>   With --server, run tournaments between easy, normal, hard and bot entrants,
+                            separated by commas; bot plays over --bot-api
+  --room <CODE>             Play online against whoever joins room CODE on the relay server
+  --public                  With --room, list the room in the relay's lobby browser
+  --browse                  Pick a public lobby on the relay server to join, or host one
+  --net-latency <MS>        Delay every LAN packet by MS milliseconds each way, for testing
+  --net-jitter <MS>         Vary that delay by up to MS milliseconds either way
+  --net-loss <PERCENT>      Drop PERCENT of LAN packets each way
+  --twitch <CHANNEL>        Let CHANNEL's chat steer the enemy paddle
+  --bot-api <PORT>          Let a bot on localhost:PORT play the left paddle over line-delimited JSON
+  --headless-sim <N>        Simulate N matches without a window, then exit
+  --tutorial                Learn to play in a guided practice match
+  --speedrun                Beat easy, normal and hard AI back to back against the clock
+  --daily                   Play today's challenge, with mutators and serves picked from the date
+  --rules                   Set up the rules before the match, or load ones saved earlier
+  --help                    Print this message";
+
+/// Made-up network trouble for testing prediction and rollback on a good network. It applies both ways,
+/// to what this end sends and to what it receives.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct NetConditions {
+    pub latency_ms: u32,
+    /// Each packet's delay varies by up to this much either side of `latency_ms`, so some arrive out of order.
+    pub jitter_ms: u32,
+    /// The chance, from 0 to 1, that a packet is lost.
+    pub loss: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum Entrant {
+    Ai(Difficulty),
+    /// Whatever's connected to the bot API, which always plays the left paddle.
+    Bot,
+}
+
+impl Entrant {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "easy" => Ok(Entrant::Ai(Difficulty::Easy)),
+            "normal" => Ok(Entrant::Ai(Difficulty::Normal)),
+            "hard" => Ok(Entrant::Ai(Difficulty::Hard)),
+            "bot" => Ok(Entrant::Bot),
+            other => Err(format!("unknown tournament entrant '{}'", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cli {
+    pub size: Option<(f32, f32)>,
+    pub fullscreen: bool,
+    pub present_mode: Option<PresentMode>,
+    pub monitor: Option<usize>,
+    pub difficulty: Option<Difficulty>,
+    pub seed: Option<u64>,
+    pub profile: Option<String>,
+    pub replay: Option<PathBuf>,
+    pub watch: Option<String>,
+    pub layout: Option<PathBuf>,
+    pub host: Option<u16>,
+    pub join: Option<SocketAddr>,
+    pub spectate: Option<SocketAddr>,
+    pub rollback: bool,
+    pub input_delay: Option<usize>,
+    pub server: Option<u16>,
+    pub tournament: Option<Vec<Entrant>>,
+    pub room: Option<String>,
+    pub public: bool,
+    pub browse: bool,
+    pub net_conditions: NetConditions,
+    pub twitch: Option<String>,
+    pub bot_api: Option<u16>,
+    pub headless_sim: Option<u32>,
+    pub tutorial: bool,
+    pub speedrun: bool,
+    pub daily: bool,
+    pub rules: bool,
+}
+
+impl Default for Cli {
+    fn default() -> Self {
+        Cli {
+            size: None,
+            fullscreen: false,
+            present_mode: None,
+            monitor: None,
+            difficulty: None,
+            seed: None,
+            profile: None,
+            replay: None,
+            watch: None,
+            layout: None,
+            host: None,
+            join: None,
+            spectate: None,
+            rollback: false,
+            input_delay: None,
+            server: None,
+            tournament: None,
+            room: None,
+            public: false,
+            browse: false,
+            net_conditions: NetConditions::default(),
+            twitch: None,
+            bot_api: None,
+            headless_sim: None,
+            tutorial: false,
+            speedrun: false,
+            daily: false,
+            rules: false,
+        }
+    }
+}
+
+impl Cli {
+    /// Parses the process arguments, exiting with usage on `--help` or a bad argument.
+    pub fn parse() -> Self {
+        let args = args();
+        if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+            println!("{}", USAGE);
+            std::process::exit(0);
+        }
+        Cli::parse_from(args).unwrap_or_else(|err| {
+            eprintln!("{}\n\n{}", err, USAGE);
+            std::process::exit(2);
+        })
+    }
+
+    pub fn parse_from(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut cli = Cli::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--size" => cli.size = Some(parse_size(&value()?)?),
+                "--fullscreen" => cli.fullscreen = true,
+                "--vsync" => cli.present_mode = Some(match value()?.as_str() {
+                    "on" => PresentMode::AutoVsync,
+                    "off" => PresentMode::AutoNoVsync,
+                    "adaptive" => PresentMode::FifoRelaxed,
+                    "fifo" => PresentMode::Fifo,
+                    "mailbox" => PresentMode::Mailbox,
+                    "immediate" => PresentMode::Immediate,
+                    other => return Err(format!("unknown vsync mode '{}'", other)),
+                }),
+                "--monitor" => cli.monitor = Some(parse_number(&arg, &value()?)?),
+                "--difficulty" => cli.difficulty = Some(match value()?.as_str() {
+                    "easy" => Difficulty::Easy,
+                    "normal" => Difficulty::Normal,
+                    "hard" => Difficulty::Hard,
+                    other => return Err(format!("unknown difficulty '{}'", other)),
+                }),
+                "--seed" => cli.seed = Some(parse_number(&arg, &value()?)?),
+                "--profile" => cli.profile = Some(value()?),
+                "--replay" => cli.replay = Some(PathBuf::from(value()?)),
+                "--watch" => cli.watch = Some(value()?),
+                "--layout" => cli.layout = Some(PathBuf::from(value()?)),
+                "--host" => cli.host = Some(parse_number(&arg, &value()?)?),
+                "--join" => cli.join = Some(parse_addr(&arg, &value()?)?),
+                "--spectate" => cli.spectate = Some(parse_addr(&arg, &value()?)?),
+                "--rollback" => cli.rollback = true,
+                "--input-delay" => cli.input_delay = Some(parse_number(&arg, &value()?)?),
+                "--server" => cli.server = Some(parse_number(&arg, &value()?)?),
+                "--tournament" => cli.tournament = Some(parse_entrants(&value()?)?),
+                "--room" => cli.room = Some(value()?),
+                "--public" => cli.public = true,
+                "--browse" => cli.browse = true,
+                "--net-latency" => cli.net_conditions.latency_ms = parse_number(&arg, &value()?)?,
+                "--net-jitter" => cli.net_conditions.jitter_ms = parse_number(&arg, &value()?)?,
+                "--net-loss" => cli.net_conditions.loss = parse_percent(&arg, &value()?)?,
+                "--twitch" => cli.twitch = Some(value()?),
+                "--bot-api" => cli.bot_api = Some(parse_number(&arg, &value()?)?),
+                "--headless-sim" => cli.headless_sim = Some(parse_number(&arg, &value()?)?),
+                "--tutorial" => cli.tutorial = true,
+                "--speedrun" => cli.speedrun = true,
+                "--daily" => cli.daily = true,
+                "--rules" => cli.rules = true,
+                _ => return Err(format!("unknown argument '{}'", arg)),
+            }
+        }
+        if cli.tournament.is_some() && cli.server.is_none() {
+            return Err("--tournament needs --server".into());
+        }
+        if cli.public && cli.room.is_none() {
+            return Err("--public needs --room".into());
+        }
+        let other_match = cli.room.is_some() || cli.host.is_some() || cli.join.is_some() || cli.spectate.is_some();
+        if cli.browse && (other_match || cli.server.is_some()) {
+            return Err("--browse picks the match itself, so it can't be combined with another".into());
+        }
+        let offline_modes: Vec<&str> = [
+            (cli.tutorial, "--tutorial"),
+            (cli.speedrun, "--speedrun"),
+            (cli.daily, "--daily"),
+            (cli.rules, "--rules"),
+        ]
+            .into_iter()
+            .filter_map(|(on, mode)| on.then_some(mode))
+            .collect();
+        if let Some(mode) = offline_modes.first() {
+            if offline_modes.len() > 1
+                || other_match
+                || cli.browse
+                || cli.server.is_some()
+                || cli.replay.is_some()
+            {
+                return Err(format!("{} is an offline match of its own, so it can't be combined with another", mode));
+            }
+            if cli.twitch.is_some() {
+                return Err(format!("{} plays against its own AI, so it can't be combined with --twitch", mode));
+            }
+        }
+        if cli.daily && cli.seed.is_some() {
+            return Err("--daily picks its own seed".into());
+        }
+        if cli.tournament.iter().flatten().any(|entrant| *entrant == Entrant::Bot) && cli.bot_api.is_none() {
+            return Err("a bot entrant needs --bot-api".into());
+        }
+        Ok(cli)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn args() -> Vec<String> {
+    std::env::args().skip(1).collect()
+}
+
+/// The browser has no command line, so options come from the page's query string instead.
+#[cfg(target_arch = "wasm32")]
+fn args() -> Vec<String> {
+    let search = web_sys::window().and_then(|window| window.location().search().ok()).unwrap_or_default();
+    query_args(&search)
+}
+
+/// Reads `?room=ABCD&rollback` as `--room ABCD --rollback`.
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+fn query_args(search: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    for pair in search.trim_start_matches('?').split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        args.push(format!("--{}", percent_decode(key)));
+        if !value.is_empty() {
+            args.push(percent_decode(value));
+        }
+    }
+    args
+}
+
+fn percent_decode(value: &str) -> String {
+    let mut bytes = Vec::new();
+    let mut rest = value.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = (byte == b'%')
+            .then(|| tail.get(..2))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (byte, escaped) {
+            (_, Some(decoded)) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+                continue;
+            },
+            (b'+', None) => bytes.push(b' '),
+            _ => bytes.push(byte),
+        }
+        rest = tail;
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn parse_number<T: std::str::FromStr>(arg: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("{} expects a number, got '{}'", arg, value))
+}
+
+fn parse_entrants(value: &str) -> Result<Vec<Entrant>, String> {
+    let entrants = value.split(',').map(|entrant| Entrant::parse(entrant.trim())).collect::<Result<Vec<_>, _>>()?;
+    if entrants.len() < 2 {
+        return Err(format!("--tournament needs at least two entrants, got '{}'", value));
+    }
+    if entrants.iter().filter(|entrant| **entrant == Entrant::Bot).count() > 1 {
+        return Err("--tournament takes at most one bot".into());
+    }
+    Ok(entrants)
+}
+
+fn parse_percent(arg: &str, value: &str) -> Result<f32, String> {
+    let percent: f32 = parse_number(arg, value)?;
+    if !(0f32..=100f32).contains(&percent) {
+        return Err(format!("{} expects a percentage from 0 to 100, got '{}'", arg, value));
+    }
+    Ok(percent / 100f32)
+}
+
+fn parse_addr(arg: &str, value: &str) -> Result<SocketAddr, String> {
+    value.parse().map_err(|_| format!("{} expects ADDR:PORT, got '{}'", arg, value))
+}
+
+fn parse_size(value: &str) -> Result<(f32, f32), String> {
+    let (width, height) = value
+        .split_once('x')
+        .ok_or_else(|| format!("--size expects WIDTHxHEIGHT, got '{}'", value))?;
+    let width: f32 = parse_number("--size", width)?;
+    let height: f32 = parse_number("--size", height)?;
+    if width <= 0f32 || height <= 0f32 {
+        return Err(format!("--size must be positive, got '{}'", value));
+    }
+    Ok((width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, String> {
+        Cli::parse_from(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn no_arguments_is_default() {
+        assert_eq!(parse(&[]), Ok(Cli::default()));
+    }
+
+    #[test]
+    fn parses_every_option() {
+        let cli = parse(&[
+            "--size", "800x600",
+            "--fullscreen",
+            "--vsync", "on",
+            "--monitor", "1",
+            "--difficulty", "hard",
+            "--seed", "42",
+            "--profile", "ana",
+            "--replay", "match.ron",
+            "--watch", "K7QX",
+            "--layout", "doubles.layout.ron",
+            "--host", "7777",
+            "--join", "192.168.1.2:7777",
+            "--spectate", "192.168.1.3:7777",
+            "--rollback",
+            "--input-delay", "3",
+            "--server", "7778",
+            "--tournament", "easy,hard,bot",
+            "--room", "ABCD",
+            "--public",
+            "--net-latency", "80",
+            "--net-jitter", "20",
+            "--net-loss", "5",
+            "--twitch", "kpong",
+            "--bot-api", "7779",
+            "--headless-sim", "10",
+        ]).unwrap();
+        assert_eq!(cli, Cli {
+            size: Some((800f32, 600f32)),
+            fullscreen: true,
+            present_mode: Some(PresentMode::AutoVsync),
+            monitor: Some(1),
+            difficulty: Some(Difficulty::Hard),
+            seed: Some(42),
+            profile: Some("ana".into()),
+            replay: Some(PathBuf::from("match.ron")),
+            watch: Some("K7QX".into()),
+            layout: Some(PathBuf::from("doubles.layout.ron")),
+            host: Some(7777),
+            join: Some(SocketAddr::from(([192, 168, 1, 2], 7777))),
+            spectate: Some(SocketAddr::from(([192, 168, 1, 3], 7777))),
+            rollback: true,
+            input_delay: Some(3),
+            server: Some(7778),
+            tournament: Some(vec![Entrant::Ai(Difficulty::Easy), Entrant::Ai(Difficulty::Hard), Entrant::Bot]),
+            room: Some("ABCD".into()),
+            public: true,
+            browse: false,
+            net_conditions: NetConditions { latency_ms: 80, jitter_ms: 20, loss: 0.05f32 },
+            twitch: Some("kpong".into()),
+            bot_api: Some(7779),
+            headless_sim: Some(10),
+            tutorial: false,
+            speedrun: false,
+            daily: false,
+            rules: false,
+        });
+        // Browsing picks its own match, so it can't share the list above.
+        assert_eq!(parse(&["--browse"]), Ok(Cli { browse: true, ..Cli::default() }));
+        assert_eq!(parse(&["--tutorial"]), Ok(Cli { tutorial: true, ..Cli::default() }));
+        assert_eq!(parse(&["--speedrun"]), Ok(Cli { speedrun: true, ..Cli::default() }));
+        assert_eq!(parse(&["--daily"]), Ok(Cli { daily: true, ..Cli::default() }));
+        assert_eq!(parse(&["--rules"]), Ok(Cli { rules: true, ..Cli::default() }));
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert!(parse(&["--bogus"]).is_err());
+        assert!(parse(&["--seed"]).is_err());
+        assert!(parse(&["--seed", "abc"]).is_err());
+        assert!(parse(&["--size", "800"]).is_err());
+        assert!(parse(&["--size", "0x600"]).is_err());
+        assert!(parse(&["--vsync", "sometimes"]).is_err());
+        assert!(parse(&["--monitor", "-1"]).is_err());
+        assert!(parse(&["--join", "localhost"]).is_err());
+        assert!(parse(&["--server", "70000"]).is_err());
+        assert!(parse(&["--net-loss", "150"]).is_err());
+        assert!(parse(&["--tournament", "easy,hard"]).is_err());
+        assert!(parse(&["--server", "7778", "--tournament", "easy"]).is_err());
+        assert!(parse(&["--server", "7778", "--tournament", "easy,bot"]).is_err());
+        assert!(parse(&["--public"]).is_err());
+        assert!(parse(&["--browse", "--room", "ABCD"]).is_err());
+        assert!(parse(&["--tutorial", "--host", "7777"]).is_err());
+        assert!(parse(&["--tutorial", "--speedrun"]).is_err());
+        assert!(parse(&["--daily", "--speedrun"]).is_err());
+        assert!(parse(&["--daily", "--seed", "4"]).is_err());
+        assert!(parse(&["--rules", "--join", "192.168.1.3:7777"]).is_err());
+        assert!(parse(&["--tutorial", "--twitch", "kpong"]).is_err());
+        assert!(parse(&["--daily", "--twitch", "kpong"]).is_err());
+    }
+
+    #[test]
+    fn reads_query_strings_as_arguments() {
+        assert_eq!(query_args(""), Vec::<String>::new());
+        assert_eq!(query_args("?room=AB%20CD&rollback&profile=ana+b"), vec!["--room", "AB CD", "--rollback", "--profile", "ana b"]);
+        assert_eq!(Cli::parse_from(query_args("?room=ABCD")), Ok(Cli { room: Some("ABCD".into()), ..Cli::default() }));
+    }
+}
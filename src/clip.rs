@@ -0,0 +1,127 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    sync::{Arc, Mutex},
+};
+
+use bevy::{prelude::*, render::view::screenshot::ScreenshotManager, window::PrimaryWindow};
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    imageops::{self, FilterType},
+    Delay, Frame, RgbaImage,
+};
+
+use crate::{spawn_toast, storage, Arena, GameState};
+
+const CLIP_FPS: u32 = 12;
+const CLIP_MAX_FRAMES: usize = CLIP_FPS as usize * 8;
+const CLIP_SCALE: u32 = 2;
+
+type Frames = Arc<Mutex<VecDeque<RgbaImage>>>;
+
+/// Downscaled frames of the rally in progress and of the one before it, captured for GIF export.
+#[derive(Resource)]
+pub struct RallyClip {
+    live: Frames,
+    last: Vec<RgbaImage>,
+    timer: Timer,
+}
+
+impl Default for RallyClip {
+    fn default() -> Self {
+        RallyClip {
+            live: Frames::default(),
+            last: Vec::new(),
+            timer: Timer::from_seconds(1f32 / CLIP_FPS as f32, TimerMode::Repeating),
+        }
+    }
+}
+
+pub fn capture_rally_frames(
+    time: Res<Time>,
+    state: Res<State<GameState>>,
+    mut clip: ResMut<RallyClip>,
+    screenshots: Option<ResMut<ScreenshotManager>>,
+    windows: Query<Entity, With<PrimaryWindow>>,
+) {
+    // Encoding needs a background thread, which the browser build doesn't have.
+    if cfg!(target_arch = "wasm32") {
+        return;
+    }
+    let (Some(mut screenshots), Ok(window)) = (screenshots, windows.get_single()) else {
+        return;
+    };
+    if *state.get() == GameState::Serving || !clip.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let live = clip.live.clone();
+    // Only one screenshot can be pending per window, so this frame is skipped if F12 got there first.
+    let _ = screenshots.take_screenshot(window, move |image| {
+        let Ok(image) = image.try_into_dynamic() else {
+            return;
+        };
+        let image = image.to_rgba8();
+        let (width, height) = image.dimensions();
+        let frame = imageops::resize(&image, width / CLIP_SCALE, height / CLIP_SCALE, FilterType::Nearest);
+        let mut live = live.lock().unwrap();
+        if live.len() == CLIP_MAX_FRAMES {
+            live.pop_front();
+        }
+        live.push_back(frame);
+    });
+}
+
+pub fn start_rally_clip(mut clip: ResMut<RallyClip>) {
+    let frames = clip.live.lock().unwrap().drain(..).collect();
+    clip.last = frames;
+    clip.timer.reset();
+}
+
+pub fn export_rally_clip(
+    mut cmd: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    state: Res<State<GameState>>,
+    arena: Res<Arena>,
+    clip: Res<RallyClip>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F10) {
+        return;
+    }
+    // Mid-rally the finished rally is the previous one; otherwise it's what was just captured.
+    let frames: Vec<RgbaImage> = if *state.get() == GameState::Started {
+        clip.last.clone()
+    }
+    else {
+        clip.live.lock().unwrap().iter().cloned().collect()
+    };
+    if frames.is_empty() {
+        spawn_toast(&mut cmd, &arena, "No rally to export yet".into());
+        return;
+    }
+    let Some(dir) = storage::data_path("clips") else {
+        return;
+    };
+
+    let name = format!("rally-{}.gif", storage::timestamp());
+    spawn_toast(&mut cmd, &arena, format!("Exporting {}", name));
+    // Encoding takes a while, so keep it off the main thread.
+    std::thread::spawn(move || {
+        let path = dir.join(name);
+        let result = fs::create_dir_all(&dir)
+            .and_then(|_| File::create(&path))
+            .map_err(|err| err.to_string())
+            .and_then(|file| {
+                let mut encoder = GifEncoder::new(file);
+                encoder.set_repeat(Repeat::Infinite).map_err(|err| err.to_string())?;
+                let delay = Delay::from_numer_denom_ms(1000, CLIP_FPS);
+                encoder
+                    .encode_frames(frames.into_iter().map(|frame| Frame::from_parts(frame, 0, 0, delay)))
+                    .map_err(|err| err.to_string())
+            });
+        match result {
+            Ok(()) => info!("Exported rally clip to {}", path.display()),
+            Err(err) => warn!("Failed to export rally clip: {}", err),
+        }
+    });
+}
@@ -0,0 +1,235 @@
+use std::f32::consts::PI;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    sprite::Mesh2dHandle,
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    loading::Preload, rules::MatchRules, Arena, Ball, Collider, Interpolated, Paddle, PaddleLength, PaddleMotion, Surface,
+    Wall,
+};
+
+pub const GAME_CONFIG_PATH: &str = "game.config.ron";
+
+/// Gameplay tuning, loaded from `assets/game.config.ron` and reapplied whenever the file changes.
+/// Angles are in radians.
+#[derive(Asset, Resource, Reflect, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[reflect(Resource)]
+#[serde(default)]
+pub struct GameConfig {
+    pub ball_half_size: Vec2,
+    pub ball_start_speed: f32,
+    pub ball_max_speed: f32,
+    pub ball_acceleration: f32,
+    /// Pulls the ball down, in pixels per second squared.
+    pub ball_gravity: f32,
+    /// How hard the strongest gust pushes the ball up or down, in pixels per second squared.
+    pub ball_wind: f32,
+    pub serve_max_angle: f32,
+
+    pub paddle_half_size: Vec2,
+    pub paddle_speed: f32,
+    pub paddle_acceleration: f32,
+    pub paddle_stop_friction: f32,
+    pub paddle_instant: bool,
+
+    pub collision_max_angle: f32,
+    pub min_horizontal_speed_ratio: f32,
+
+    /// How much of the ball's speed off a surface is kept along the normal. Above 1 speeds it up.
+    pub wall_restitution: f32,
+    /// How much of the ball's speed along a surface is lost on a bounce.
+    pub wall_friction: f32,
+    pub paddle_restitution: f32,
+    pub paddle_friction: f32,
+
+    pub next_round_interval: f32,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            ball_half_size: Vec2::new(4f32, 4f32),
+            ball_start_speed: 256f32,
+            ball_max_speed: 512f32,
+            ball_acceleration: 8f32,
+            ball_gravity: 0f32,
+            ball_wind: 0f32,
+            serve_max_angle: PI/12f32,
+
+            paddle_half_size: Vec2::new(4f32, 32f32),
+            paddle_speed: 128f32,
+            paddle_acceleration: 1024f32,
+            paddle_stop_friction: 1024f32,
+            paddle_instant: false,
+
+            collision_max_angle: PI/4f32,
+            min_horizontal_speed_ratio: 0.5f32,
+
+            wall_restitution: 0.98f32,
+            wall_friction: 0.02f32,
+            paddle_restitution: 1.02f32,
+            paddle_friction: 0f32,
+
+            next_round_interval: 1f32,
+        }
+    }
+}
+
+impl GameConfig {
+    /// The ball never moves further than a paddle is thick in one substep, so it can't tunnel through.
+    pub fn max_ball_step(&self) -> f32 {
+        self.paddle_half_size.x * 2f32
+    }
+
+    pub(crate) fn paddle_motion(&self) -> PaddleMotion {
+        PaddleMotion {
+            accel: self.paddle_acceleration,
+            max_speed: self.paddle_speed,
+            stop_friction: self.paddle_stop_friction,
+            instant: self.paddle_instant,
+        }
+    }
+
+    pub(crate) fn wall_surface(&self) -> Surface {
+        Surface { restitution: self.wall_restitution, friction: self.wall_friction }
+    }
+
+    pub(crate) fn paddle_surface(&self) -> Surface {
+        Surface { restitution: self.paddle_restitution, friction: self.paddle_friction }
+    }
+}
+
+#[derive(Resource)]
+pub struct GameConfigHandle(pub Handle<GameConfig>);
+
+#[derive(Debug)]
+pub enum GameConfigError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+}
+
+impl std::fmt::Display for GameConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameConfigError::Io(err) => write!(f, "couldn't read game config: {}", err),
+            GameConfigError::Ron(err) => write!(f, "couldn't parse game config: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for GameConfigError {}
+
+#[derive(Default)]
+pub struct GameConfigLoader;
+
+impl AssetLoader for GameConfigLoader {
+    type Asset = GameConfig;
+    type Settings = ();
+    type Error = GameConfigError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<GameConfig, GameConfigError>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await.map_err(GameConfigError::Io)?;
+            ron::de::from_bytes(&bytes).map_err(GameConfigError::Ron)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["config.ron"]
+    }
+}
+
+pub fn load_game_config(mut cmd: Commands, asset_server: Res<AssetServer>, mut preload: ResMut<Preload>) {
+    let handle: Handle<GameConfig> = asset_server.load(GAME_CONFIG_PATH);
+    preload.add(handle.clone());
+    cmd.insert_resource(GameConfigHandle(handle));
+}
+
+pub fn apply_game_config(
+    mut events: EventReader<AssetEvent<GameConfig>>,
+    handle: Option<Res<GameConfigHandle>>,
+    assets: Res<Assets<GameConfig>>,
+    rules: Res<MatchRules>,
+    mut config: ResMut<GameConfig>,
+) {
+    let Some(handle) = handle else {
+        return;
+    };
+    let changed = events.read().any(|event| match event {
+        AssetEvent::Added { id } | AssetEvent::Modified { id } => *id == handle.0.id(),
+        _ => false,
+    });
+    let Some(loaded) = assets.get(&handle.0).filter(|_| changed) else {
+        return;
+    };
+    // The match's rules stay on through a reload.
+    let mut applied = loaded.clone();
+    rules.apply(&mut applied);
+    if applied == *config {
+        return;
+    }
+    info!("Applying game config");
+    *config = applied;
+}
+
+/// Fits the paddles and balls to the config whenever it changes, be it from the file or a mutator, and each
+/// paddle to its own length.
+pub fn resize_bodies(
+    config: Res<GameConfig>,
+    arena: Res<Arena>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut paddles: Query<
+        (Ref<PaddleLength>, &mut Collider, &mut PaddleMotion, &mut Mesh2dHandle, &mut Interpolated, &mut Transform),
+        With<Paddle>,
+    >,
+    mut balls: Query<&mut Mesh2dHandle, (With<Ball>, Without<Paddle>)>,
+) {
+    for (length, mut collider, mut motion, mut mesh, mut interp, mut transform) in paddles.iter_mut() {
+        if !config.is_changed() && !length.is_changed() {
+            continue;
+        }
+        let half_size = config.paddle_half_size * Vec2::new(1f32, length.0);
+        collider.half_size = half_size;
+        *motion = config.paddle_motion();
+        *mesh = Mesh2dHandle(meshes.add(Rectangle { half_size }));
+
+        // Keep the paddle flush with its edge of the arena.
+        let x = arena.paddle_x(interp.current.x.signum(), half_size);
+        interp.previous.x = x;
+        interp.current.x = x;
+        transform.translation.x = x;
+    }
+
+    if !config.is_changed() {
+        return;
+    }
+    let ball_mesh = Mesh2dHandle(meshes.add(Rectangle { half_size: config.ball_half_size }));
+    for mut mesh in balls.iter_mut() {
+        *mesh = ball_mesh.clone();
+    }
+}
+
+/// Gives the walls and paddles the config's bounciness and grip whenever it changes.
+pub fn apply_surfaces(
+    config: Res<GameConfig>,
+    mut walls: Query<&mut Surface, (With<Wall>, Without<Paddle>)>,
+    mut paddles: Query<&mut Surface, (With<Paddle>, Without<Wall>)>,
+) {
+    for mut surface in walls.iter_mut() {
+        *surface = config.wall_surface();
+    }
+    for mut surface in paddles.iter_mut() {
+        *surface = config.paddle_surface();
+    }
+}
@@ -0,0 +1,205 @@
+use std::fmt::Write as _;
+
+use bevy::{prelude::*, sprite::Anchor};
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{profiles::Profiles, rules::Mutators, spawn_toast, storage, Arena, MatchClock, MatchOver, Score, Side};
+
+const DAILY_FILE: &str = "daily.ron";
+const DAILY_VERSION: u32 = 1;
+const LEADERBOARD_SIZE: usize = 10;
+const SHOWN_ENTRIES: usize = 3;
+const SECONDS_PER_DAY: u64 = 86_400;
+const MIN_MUTATORS: usize = 2;
+const MAX_MUTATORS: usize = 3;
+const FONT_SIZE: f32 = 14f32;
+
+/// Days since the Unix epoch, in UTC, so everyone gets the same challenge on the same day.
+pub fn today() -> u64 {
+    storage::timestamp() / SECONDS_PER_DAY
+}
+
+/// The day's mutators: a few of them, picked the same way on every machine.
+pub fn mutators_for(day: u64) -> Mutators {
+    let mut rng = ChaCha8Rng::seed_from_u64(day);
+    let mut mutators = Mutators::default();
+    let mut flags = mutators.flags_mut();
+    flags.shuffle(&mut rng);
+    let count = rng.gen_range(MIN_MUTATORS..=MAX_MUTATORS);
+    for (_, on) in flags.iter_mut().take(count) {
+        **on = true;
+    }
+    mutators
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DailyEntry {
+    pub name: String,
+    pub points_for: i32,
+    pub points_against: i32,
+    pub duration_secs: f32,
+}
+
+/// Wins at one day's challenge, kept apart from the regular records. A new day starts an empty board.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DailyLeaderboard {
+    pub version: u32,
+    pub day: u64,
+    pub entries: Vec<DailyEntry>,
+}
+
+impl Default for DailyLeaderboard {
+    fn default() -> Self {
+        DailyLeaderboard { version: DAILY_VERSION, day: 0, entries: Vec::new() }
+    }
+}
+
+impl DailyLeaderboard {
+    pub fn load(day: u64) -> Self {
+        storage::data_path(DAILY_FILE)
+            .and_then(|path| storage::load_ron::<DailyLeaderboard>(&path))
+            .filter(|board| board.day == day)
+            .unwrap_or(DailyLeaderboard { day, ..default() })
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = storage::data_path(DAILY_FILE).ok_or("no data directory")?;
+        storage::save_ron(&path, self)
+    }
+
+    /// Ranks the win like the regular leaderboard does, returning its place if it made the board.
+    pub fn record(&mut self, entry: DailyEntry) -> Option<usize> {
+        self.entries.push(entry.clone());
+        self.entries.sort_by(|a, b| {
+            (b.points_for - b.points_against)
+                .cmp(&(a.points_for - a.points_against))
+                .then(a.duration_secs.total_cmp(&b.duration_secs))
+        });
+        self.entries.truncate(LEADERBOARD_SIZE);
+        self.entries.iter().position(|other| *other == entry).map(|i| i + 1)
+    }
+}
+
+/// Today's challenge: its mutators and a seed for the serves, both from the date.
+#[derive(Resource)]
+pub struct DailyChallenge {
+    pub day: u64,
+    pub mutators: Mutators,
+    leaderboard: DailyLeaderboard,
+}
+
+impl DailyChallenge {
+    pub fn new(day: u64) -> Self {
+        DailyChallenge { day, mutators: mutators_for(day), leaderboard: DailyLeaderboard::load(day) }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.day
+    }
+}
+
+#[derive(Component)]
+pub struct DailyText;
+
+pub fn spawn_daily_text(mut cmd: Commands, arena: Res<Arena>) {
+    cmd.spawn((
+        Text2dBundle {
+            text: Text::from_section("", TextStyle {
+                font_size: FONT_SIZE,
+                ..default()
+            })
+            .with_justify(JustifyText::Right),
+            text_anchor: Anchor::BottomRight,
+            transform: Transform::from_xyz(arena.half_size.x - 8f32, -arena.half_size.y + 8f32, 3f32),
+            ..default()
+        },
+        DailyText,
+    ));
+}
+
+pub fn track_daily(
+    mut cmd: Commands,
+    mut match_over: EventReader<MatchOver>,
+    score: Res<Score>,
+    clock: Res<MatchClock>,
+    arena: Res<Arena>,
+    profiles: Res<Profiles>,
+    mut daily: ResMut<DailyChallenge>,
+) {
+    for over in match_over.read() {
+        if over.winner != Side::Left {
+            continue;
+        }
+        let rank = daily.leaderboard.record(DailyEntry {
+            name: profiles.active().name.clone(),
+            points_for: score.player,
+            points_against: score.enemy,
+            duration_secs: clock.elapsed,
+        });
+        let Some(rank) = rank else {
+            continue;
+        };
+        if let Err(err) = daily.leaderboard.save() {
+            warn!("Failed to save the daily leaderboard: {}", err);
+        }
+        spawn_toast(&mut cmd, &arena, format!("Daily challenge: #{} today", rank));
+    }
+}
+
+pub fn update_daily_text(daily: Res<DailyChallenge>, mut texts: Query<&mut Text, With<DailyText>>) {
+    if !daily.is_changed() {
+        return;
+    }
+    let mut value = format!("Daily #{}\n{}", daily.day, daily.mutators.names().join(", "));
+    for (i, entry) in daily.leaderboard.entries.iter().take(SHOWN_ENTRIES).enumerate() {
+        let _ = write!(
+            value,
+            "\n{}. {}  {}-{}  {:.1}s",
+            i + 1,
+            entry.name,
+            entry.points_for,
+            entry.points_against,
+            entry.duration_secs,
+        );
+    }
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = value.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_day_same_mutators() {
+        for day in 19_000..19_100 {
+            let mutators = mutators_for(day);
+            assert!(mutators == mutators_for(day));
+            assert!((MIN_MUTATORS..=MAX_MUTATORS).contains(&mutators.names().len()));
+        }
+        assert!((19_000..19_100).any(|day| mutators_for(day) != mutators_for(19_000)));
+    }
+
+    #[test]
+    fn ranks_wins_by_margin_then_time() {
+        let entry = |name: &str, points_against, duration_secs| DailyEntry {
+            name: name.into(),
+            points_for: 5,
+            points_against,
+            duration_secs,
+        };
+        let mut board = DailyLeaderboard::default();
+        assert_eq!(board.record(entry("a", 2, 60f32)), Some(1));
+        assert_eq!(board.record(entry("b", 0, 90f32)), Some(1));
+        assert_eq!(board.record(entry("c", 2, 45f32)), Some(2));
+        for _ in 0..LEADERBOARD_SIZE {
+            board.record(entry("d", 1, 30f32));
+        }
+        assert_eq!(board.record(entry("e", 4, 10f32)), None);
+        assert_eq!(board.entries.len(), LEADERBOARD_SIZE);
+    }
+}
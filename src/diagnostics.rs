@@ -0,0 +1,198 @@
+use std::fmt::Write as _;
+
+use bevy::{
+    diagnostic::{
+        Diagnostic, DiagnosticId, Diagnostics, DiagnosticsStore, EntityCountDiagnosticsPlugin,
+        FrameTimeDiagnosticsPlugin, RegisterDiagnostic,
+    },
+    ecs::schedule::StateTransitionEvent,
+    prelude::*,
+    sprite::Anchor,
+};
+
+use crate::{Arena, Ball, BallHitPaddle, BallHitWall, GameState, GoalScored, Interpolated, Paddle, PauseState, Side};
+
+/// Paddle hits per second, over the last few seconds of play.
+pub const RALLY_RATE: DiagnosticId = DiagnosticId::from_u128(0x2b6f_41d3_8c0e_4f5a_9a21_7d3e_c5b8_1f04);
+/// Round trip to the other end of a networked match.
+pub const PING: DiagnosticId = DiagnosticId::from_u128(0x8e14_0a7c_52d9_46b1_b3f6_2c90_4e7d_a315);
+const RALLY_RATE_WINDOW: f32 = 1f32;
+const FONT_SIZE: f32 = 14f32;
+
+pub fn register(app: &mut App) {
+    if !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>() {
+        app.add_plugins(FrameTimeDiagnosticsPlugin);
+    }
+    if !app.is_plugin_added::<EntityCountDiagnosticsPlugin>() {
+        app.add_plugins(EntityCountDiagnosticsPlugin);
+    }
+    app
+        .register_diagnostic(Diagnostic::new(RALLY_RATE, "rally_rate", 5).with_suffix(" hits/s"))
+        .register_diagnostic(Diagnostic::new(PING, "ping", 20).with_suffix(" ms"));
+}
+
+/// Counts paddle hits towards `RALLY_RATE`.
+#[derive(Resource)]
+pub struct RallyRate {
+    hits: u32,
+    timer: Timer,
+}
+
+impl Default for RallyRate {
+    fn default() -> Self {
+        RallyRate {
+            hits: 0,
+            timer: Timer::from_seconds(RALLY_RATE_WINDOW, TimerMode::Repeating),
+        }
+    }
+}
+
+pub fn measure_rally_rate(
+    time: Res<Time>,
+    mut paddle_hits: EventReader<BallHitPaddle>,
+    mut rate: ResMut<RallyRate>,
+    mut diagnostics: Diagnostics,
+) {
+    rate.hits += paddle_hits.read().count() as u32;
+    if rate.timer.tick(time.delta()).just_finished() {
+        let hits = std::mem::take(&mut rate.hits);
+        diagnostics.add_measurement(RALLY_RATE, || hits as f64 / RALLY_RATE_WINDOW as f64);
+    }
+}
+
+/// Logs gameplay as structured events, so a log subscriber can filter or collect them by field.
+pub fn log_gameplay_events(
+    mut paddle_hits: EventReader<BallHitPaddle>,
+    mut wall_hits: EventReader<BallHitWall>,
+    mut goals: EventReader<GoalScored>,
+    balls: Query<&Ball>,
+) {
+    for hit in paddle_hits.read() {
+        debug!(target: "kpong::collision", ball = ?hit.ball, paddle = ?hit.paddle, "ball hit paddle");
+    }
+    for hit in wall_hits.read() {
+        debug!(target: "kpong::collision", ball = ?hit.ball, "ball hit wall");
+    }
+    for goal in goals.read() {
+        let last_hit = balls.get(goal.ball).ok().and_then(|ball| ball.last_hit);
+        info!(target: "kpong::score", ball = ?goal.ball, scorer = ?goal.scorer, ?last_hit, "goal");
+    }
+}
+
+pub fn log_state_transitions(
+    mut game: EventReader<StateTransitionEvent<GameState>>,
+    mut pause: EventReader<StateTransitionEvent<PauseState>>,
+) {
+    for transition in game.read() {
+        debug!(target: "kpong::state", from = ?transition.before, to = ?transition.after, "game state changed");
+    }
+    for transition in pause.read() {
+        info!(target: "kpong::state", from = ?transition.before, to = ?transition.after, "pause state changed");
+    }
+}
+
+/// F9 holds the simulation still, and then each press of Period runs a single fixed tick.
+#[derive(Resource, Default)]
+pub struct FrameStep {
+    pub enabled: bool,
+    steps: u32,
+    run_tick: bool,
+    tick: u64,
+    /// How far each ball and paddle moved in the last tick that ran.
+    deltas: Vec<(String, Vec2)>,
+}
+
+pub fn control_frame_step(keyboard_input: Res<ButtonInput<KeyCode>>, mut step: ResMut<FrameStep>) {
+    if keyboard_input.just_pressed(KeyCode::F9) {
+        step.enabled = !step.enabled;
+        step.steps = 0;
+        info!(target: "kpong::debug", enabled = step.enabled, "frame step");
+    }
+    if step.enabled && keyboard_input.just_pressed(KeyCode::Period) {
+        step.steps += 1;
+    }
+}
+
+/// Decides, at the start of each fixed tick, whether gameplay runs in it.
+pub fn begin_tick(mut step: ResMut<FrameStep>) {
+    step.run_tick = !step.enabled || step.steps > 0;
+    if step.run_tick {
+        step.steps = step.steps.saturating_sub(1);
+        step.tick += 1;
+    }
+}
+
+pub fn tick_allowed(step: Res<FrameStep>) -> bool {
+    step.run_tick
+}
+
+pub fn record_tick_deltas(
+    mut step: ResMut<FrameStep>,
+    balls: Query<&Interpolated, With<Ball>>,
+    paddles: Query<(&Interpolated, &Side), With<Paddle>>,
+) {
+    let balls = balls.iter().enumerate().map(|(i, interp)| (format!("Ball {}", i + 1), interp));
+    let paddles = paddles.iter().map(|(interp, side)| (format!("{:?} paddle", side), interp));
+    step.deltas = balls.chain(paddles).map(|(label, interp)| (label, interp.current - interp.previous)).collect();
+}
+
+#[derive(Component)]
+pub struct DebugOverlay;
+
+pub fn spawn_debug_overlay(mut cmd: Commands, arena: Res<Arena>) {
+    cmd.spawn((
+        Text2dBundle {
+            text: Text::from_section("", TextStyle {
+                font_size: FONT_SIZE,
+                ..default()
+            }),
+            text_anchor: Anchor::TopLeft,
+            transform: Transform::from_xyz(-arena.half_size.x + 8f32, arena.half_size.y - 8f32, 3f32),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        DebugOverlay,
+    ));
+}
+
+/// F4 shows frame rate, entity count, rally rate and ping in the corner, and, while frame stepping, what the last tick moved.
+pub fn update_debug_overlay(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    store: Res<DiagnosticsStore>,
+    step: Res<FrameStep>,
+    mut overlays: Query<(&mut Text, &mut Visibility), With<DebugOverlay>>,
+) {
+    for (mut text, mut visibility) in overlays.iter_mut() {
+        if keyboard_input.just_pressed(KeyCode::F4) {
+            *visibility = if *visibility == Visibility::Hidden { Visibility::Visible } else { Visibility::Hidden };
+        }
+        if keyboard_input.just_pressed(KeyCode::F9) && step.enabled {
+            *visibility = Visibility::Visible;
+        }
+        if *visibility == Visibility::Hidden {
+            continue;
+        }
+        let mut summary = String::new();
+        for (label, id) in [
+            ("FPS", FrameTimeDiagnosticsPlugin::FPS),
+            ("Entities", EntityCountDiagnosticsPlugin::ENTITY_COUNT),
+            ("Rally rate", RALLY_RATE),
+            ("Ping", PING),
+        ] {
+            let Some(diagnostic) = store.get(id) else {
+                continue;
+            };
+            let Some(value) = diagnostic.smoothed() else {
+                continue;
+            };
+            let _ = writeln!(summary, "{}: {:.1}{}", label, value, diagnostic.suffix);
+        }
+        if step.enabled {
+            let _ = writeln!(summary, "\nFrame step, tick {} (Period to advance)", step.tick);
+            for (label, delta) in &step.deltas {
+                let _ = writeln!(summary, "{}: {:+.2}, {:+.2}", label, delta.x, delta.y);
+            }
+        }
+        text.sections[0].value = summary;
+    }
+}
@@ -0,0 +1,67 @@
+use bevy::prelude::*;
+
+use crate::{settings::Settings, BallHitPaddle, GoalScored};
+
+/// Shake builds up as trauma, which wears off over time. The camera moves with its square, so small knocks
+/// barely show and big ones show a lot.
+const MAX_TRAUMA: f32 = 1.5f32;
+const TRAUMA_DECAY: f32 = 2f32;
+const PADDLE_HIT_TRAUMA: f32 = 0.25f32;
+const GOAL_TRAUMA: f32 = 0.6f32;
+/// How far the camera moves at a trauma of 1, in pixels.
+const MAX_SHAKE_OFFSET: f32 = 8f32;
+const SHAKE_HZ: f32 = 30f32;
+
+/// Screen shake left to play out.
+#[derive(Resource, Default)]
+pub struct Shake {
+    trauma: f32,
+    /// How far the camera has been moved by the shake so far.
+    applied: Vec2,
+}
+
+impl Shake {
+    fn add(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma).min(MAX_TRAUMA);
+    }
+
+    /// Stops the shake where it is, returning how far the camera has to move back to undo it.
+    pub fn settle(&mut self) -> Vec2 {
+        self.trauma = 0f32;
+        std::mem::take(&mut self.applied)
+    }
+}
+
+/// Knocks the screen on paddle hits and goals, as hard as the juice setting allows.
+pub fn add_shake(
+    settings: Res<Settings>,
+    mut shake: ResMut<Shake>,
+    mut paddle_hits: EventReader<BallHitPaddle>,
+    mut goals: EventReader<GoalScored>,
+) {
+    let scale = settings.effect_scale();
+    for _ in paddle_hits.read() {
+        shake.add(PADDLE_HIT_TRAUMA * scale);
+    }
+    for _ in goals.read() {
+        shake.add(GOAL_TRAUMA * scale);
+    }
+}
+
+/// Moves the camera by the shake, on top of wherever else it's been put.
+pub fn apply_shake(
+    time: Res<Time<Real>>,
+    mut shake: ResMut<Shake>,
+    mut cameras: Query<&mut Transform, With<Camera2d>>,
+) {
+    shake.trauma = (shake.trauma - TRAUMA_DECAY * time.delta_seconds()).max(0f32);
+    let t = time.elapsed_seconds() * SHAKE_HZ;
+    let offset = Vec2::new(t.sin(), (t * 1.3f32).cos()) * MAX_SHAKE_OFFSET * shake.trauma * shake.trauma;
+    if offset == shake.applied {
+        return;
+    }
+    for mut transform in cameras.iter_mut() {
+        transform.translation += (offset - shake.applied).extend(0f32);
+    }
+    shake.applied = offset;
+}
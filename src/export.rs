@@ -0,0 +1,113 @@
+use std::fmt::Write as _;
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::{
+    settings::Settings, storage, Ball, GameState, GoalScored, MatchClock, MatchOver, Rally, Score, Side,
+};
+
+const SPEED_SAMPLE_INTERVAL: f32 = 0.25f32;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PointEntry {
+    pub time_secs: f32,
+    pub scorer: Side,
+    pub player_score: i32,
+    pub enemy_score: i32,
+    pub rally_hits: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SpeedSample {
+    pub time_secs: f32,
+    pub speed: f32,
+}
+
+/// Everything that happened in the current match, kept so it can be exported once the match ends.
+#[derive(Resource, Debug, Default, Serialize)]
+pub struct MatchLog {
+    pub points: Vec<PointEntry>,
+    pub speed_samples: Vec<SpeedSample>,
+    #[serde(skip)]
+    next_sample: f32,
+}
+
+impl MatchLog {
+    pub fn points_csv(&self) -> String {
+        let mut csv = String::from("time_secs,scorer,player_score,enemy_score,rally_hits\n");
+        for point in &self.points {
+            let _ = writeln!(
+                csv,
+                "{},{:?},{},{},{}",
+                point.time_secs, point.scorer, point.player_score, point.enemy_score, point.rally_hits
+            );
+        }
+        csv
+    }
+
+    pub fn speed_csv(&self) -> String {
+        let mut csv = String::from("time_secs,speed\n");
+        for sample in &self.speed_samples {
+            let _ = writeln!(csv, "{},{}", sample.time_secs, sample.speed);
+        }
+        csv
+    }
+
+    fn write(&self, stem: &str) -> Result<(), String> {
+        let dir = storage::data_path("exports").ok_or("no data directory")?;
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        storage::write(&dir.join(format!("{}.json", stem)), &json)?;
+        storage::write(&dir.join(format!("{}-points.csv", stem)), &self.points_csv())?;
+        storage::write(&dir.join(format!("{}-speed.csv", stem)), &self.speed_csv())
+    }
+}
+
+pub fn record_match_log(
+    mut goals: EventReader<GoalScored>,
+    score: Res<Score>,
+    rally: Res<Rally>,
+    clock: Res<MatchClock>,
+    state: Res<State<GameState>>,
+    balls: Query<&Ball>,
+    mut log: ResMut<MatchLog>,
+) {
+    for goal in goals.read() {
+        log.points.push(PointEntry {
+            time_secs: clock.elapsed,
+            scorer: goal.scorer,
+            player_score: score.player,
+            enemy_score: score.enemy,
+            rally_hits: rally.hits,
+        });
+    }
+
+    if *state.get() != GameState::Started || clock.elapsed < log.next_sample {
+        return;
+    }
+    log.next_sample = clock.elapsed + SPEED_SAMPLE_INTERVAL;
+    if let Some(ball) = balls.iter().next() {
+        log.speed_samples.push(SpeedSample {
+            time_secs: clock.elapsed,
+            speed: ball.vel.length(),
+        });
+    }
+}
+
+pub fn export_match_log(
+    mut match_over: EventReader<MatchOver>,
+    settings: Res<Settings>,
+    mut log: ResMut<MatchLog>,
+) {
+    if match_over.read().count() == 0 {
+        return;
+    }
+    if settings.export_match_data {
+        let stem = format!("match-{}", storage::timestamp());
+        match log.write(&stem) {
+            Ok(()) => info!("Exported match data as {}", stem),
+            Err(err) => warn!("Failed to export match data: {}", err),
+        }
+    }
+    *log = MatchLog::default();
+}
@@ -0,0 +1,137 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, sprite::Mesh2dHandle};
+
+use crate::{Arena, Ball, GoalScored, Interpolated, Paddle};
+
+/// How much of the rally is kept, and so replayed, in seconds of game time.
+const HISTORY_SECONDS: f32 = 1f32;
+const SLOW_MOTION: f32 = 0.25f32;
+const FONT_SIZE: f32 = 16f32;
+
+/// Where every ball and paddle was at the end of one tick.
+struct Frame {
+    at: f32,
+    bodies: Vec<(Entity, Vec2)>,
+}
+
+/// The last second of the rally in play, kept for the replay after a goal.
+#[derive(Resource, Default)]
+pub struct History {
+    frames: VecDeque<Frame>,
+}
+
+/// Runs once a tick, after the tick's positions are recorded, so neither the frame rate nor interpolation shows in
+/// the replay.
+pub fn record_history(
+    time: Res<Time<Fixed>>,
+    mut history: ResMut<History>,
+    bodies: Query<(Entity, &Interpolated), Or<(With<Ball>, With<Paddle>)>>,
+) {
+    let at = time.elapsed_seconds();
+    let bodies = bodies.iter().map(|(entity, interp)| (entity, interp.current)).collect();
+    history.frames.push_back(Frame { at, bodies });
+    while history.frames.front().is_some_and(|frame| frame.at < at - HISTORY_SECONDS) {
+        history.frames.pop_front();
+    }
+}
+
+/// A goal's last second playing back in slow motion. The match holds still, with the real balls and paddles
+/// hidden behind stand-ins, until it ends or is skipped.
+#[derive(Resource)]
+pub struct GoalReplay {
+    frames: Vec<Frame>,
+    elapsed: f32,
+    /// Each real body, how it was shown before, and the stand-in shown instead.
+    stand_ins: Vec<(Entity, Visibility, Entity)>,
+}
+
+#[derive(Component)]
+pub struct GoalReplayText;
+
+pub fn start_goal_replay(
+    mut cmd: Commands,
+    mut goals: EventReader<GoalScored>,
+    mut history: ResMut<History>,
+    arena: Res<Arena>,
+    mut bodies: Query<(&Mesh2dHandle, &Handle<ColorMaterial>, &Transform, &mut Visibility)>,
+) {
+    if goals.read().count() == 0 {
+        return;
+    }
+    let frames: Vec<Frame> = history.frames.drain(..).collect();
+    let Some(last) = frames.last().filter(|_| frames.len() > 1) else {
+        return;
+    };
+    let mut stand_ins = Vec::new();
+    for (entity, _) in &last.bodies {
+        let Ok((mesh, material, transform, mut visibility)) = bodies.get_mut(*entity) else {
+            continue;
+        };
+        let stand_in = cmd.spawn(ColorMesh2dBundle {
+            mesh: mesh.clone(),
+            material: material.clone(),
+            transform: *transform,
+            ..default()
+        }).id();
+        stand_ins.push((*entity, *visibility, stand_in));
+        *visibility = Visibility::Hidden;
+    }
+    cmd.spawn((
+        Text2dBundle {
+            text: Text::from_section("REPLAY\npress any key to skip", TextStyle {
+                font_size: FONT_SIZE,
+                ..default()
+            })
+            .with_justify(JustifyText::Center),
+            transform: Transform::from_xyz(0f32, arena.half_size.y - 64f32, 5f32),
+            ..default()
+        },
+        GoalReplayText,
+    ));
+    cmd.insert_resource(GoalReplay { frames, elapsed: 0f32, stand_ins });
+}
+
+pub fn play_goal_replay(
+    mut cmd: Commands,
+    time: Res<Time<Real>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    mut replay: ResMut<GoalReplay>,
+    mut visibilities: Query<&mut Visibility>,
+    mut transforms: Query<&mut Transform>,
+    texts: Query<Entity, With<GoalReplayText>>,
+) {
+    replay.elapsed += time.delta_seconds() * SLOW_MOTION;
+    let start = replay.frames[0].at;
+    let at = start + replay.elapsed;
+    let skipped = keyboard_input.get_just_pressed().next().is_some() || mouse_input.get_just_pressed().next().is_some();
+    let Some(next) = replay.frames.iter().position(|frame| frame.at >= at).filter(|_| !skipped) else {
+        for (entity, visibility, stand_in) in &replay.stand_ins {
+            if let Ok(mut shown) = visibilities.get_mut(*entity) {
+                *shown = *visibility;
+            }
+            cmd.entity(*stand_in).despawn();
+        }
+        for text in texts.iter() {
+            cmd.entity(text).despawn_recursive();
+        }
+        cmd.remove_resource::<GoalReplay>();
+        return;
+    };
+
+    let (before, after) = (&replay.frames[next.saturating_sub(1)], &replay.frames[next]);
+    let t = if after.at > before.at { (at - before.at) / (after.at - before.at) } else { 1f32 };
+    for (entity, _, stand_in) in &replay.stand_ins {
+        let find = |frame: &Frame| frame.bodies.iter().find(|(body, _)| body == entity).map(|(_, pos)| *pos);
+        let Ok(mut transform) = transforms.get_mut(*stand_in) else {
+            continue;
+        };
+        let (Some(from), Some(to)) = (find(before), find(after)) else {
+            continue;
+        };
+        let pos = from.lerp(to, t.clamp(0f32, 1f32));
+        transform.translation.x = pos.x;
+        transform.translation.y = pos.y;
+    }
+}
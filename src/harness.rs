@@ -0,0 +1,164 @@
+use std::time::Duration;
+
+use bevy::{diagnostic::DiagnosticsPlugin, input::InputPlugin, prelude::*, time::TimeUpdateStrategy, window::ExitCondition};
+
+use crate::{
+    loading::LoadingState, Arena, Ball, GameState, Interpolated, Paddle, PongPlugin, Score, Side, FIXED_TIMESTEP_HZ,
+};
+
+const TEST_SEED: u64 = 7;
+const MAX_LOADING_UPDATES: usize = 600;
+
+/// The whole game on `MinimalPlugins`, with no window, GPU or disk writes. Every update advances exactly one
+/// fixed tick, and input is injected through the same keyboard state the player's keys land in.
+pub struct Harness {
+    pub app: App,
+}
+
+impl Harness {
+    pub fn new() -> Self {
+        let mut app = App::new();
+        app
+            .add_plugins((
+                MinimalPlugins,
+                TransformPlugin,
+                HierarchyPlugin,
+                DiagnosticsPlugin,
+                AssetPlugin::default(),
+                InputPlugin,
+                WindowPlugin {
+                    primary_window: None,
+                    exit_condition: ExitCondition::DontExit,
+                    ..default()
+                },
+            ))
+            .init_asset::<Mesh>()
+            .init_asset::<ColorMaterial>()
+            .init_resource::<UiScale>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(1f64/FIXED_TIMESTEP_HZ)))
+            .add_plugins(PongPlugin { seed: Some(TEST_SEED), ..default() });
+        let mut harness = Harness { app };
+        for _ in 0..MAX_LOADING_UPDATES {
+            harness.app.update();
+            if *harness.app.world.resource::<State<LoadingState>>().get() == LoadingState::Done {
+                return harness;
+            }
+        }
+        panic!("assets didn't finish loading in {} updates", MAX_LOADING_UPDATES);
+    }
+
+    pub fn hold(&mut self, key: KeyCode) {
+        self.app.world.resource_mut::<ButtonInput<KeyCode>>().press(key);
+    }
+
+    pub fn release(&mut self, key: KeyCode) {
+        self.app.world.resource_mut::<ButtonInput<KeyCode>>().release(key);
+    }
+
+    pub fn ticks(&mut self, ticks: usize) {
+        for _ in 0..ticks {
+            self.app.update();
+        }
+    }
+
+    /// Ticks until `done` holds, giving up after `max_ticks`. Returns whether it held.
+    pub fn run_until(&mut self, max_ticks: usize, mut done: impl FnMut(&mut Harness) -> bool) -> bool {
+        for _ in 0..max_ticks {
+            self.app.update();
+            if done(self) {
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn state(&self) -> GameState {
+        self.app.world.resource::<State<GameState>>().get().clone()
+    }
+
+    pub fn score(&self) -> Score {
+        self.app.world.resource::<Score>().clone()
+    }
+
+    pub fn arena(&self) -> Arena {
+        *self.app.world.resource::<Arena>()
+    }
+
+    /// Where the first ball is and how fast it's going.
+    pub fn ball(&mut self) -> (Vec2, Vec2) {
+        let mut balls = self.app.world.query::<(&Transform, &Ball)>();
+        let (transform, ball) = balls.iter(&self.app.world).next().expect("no ball");
+        (transform.translation.truncate(), ball.vel)
+    }
+
+    /// Moves the first ball to `pos`, heading along `dir` at its current speed.
+    pub fn place_ball(&mut self, pos: Vec2, dir: Vec2) {
+        let mut balls = self.app.world.query::<(&mut Transform, &mut Interpolated, &mut Ball)>();
+        let (mut transform, mut interp, mut ball) = balls.iter_mut(&mut self.app.world).next().expect("no ball");
+        transform.translation = pos.extend(transform.translation.z);
+        *interp = Interpolated::at(pos);
+        ball.vel = dir.normalize() * ball.speed;
+    }
+
+    pub fn place_paddle(&mut self, side: Side, y: f32) {
+        let mut paddles = self.app.world.query_filtered::<(&mut Transform, &mut Interpolated, &Side), With<Paddle>>();
+        for (mut transform, mut interp, _) in paddles.iter_mut(&mut self.app.world).filter(|(_, _, paddle_side)| **paddle_side == side) {
+            transform.translation.y = y;
+            *interp = Interpolated::at(transform.translation.truncate());
+        }
+    }
+
+    /// Holds up until the ball is served.
+    pub fn serve(&mut self) {
+        self.hold(KeyCode::KeyW);
+        let served = self.run_until(8, |harness| harness.state() == GameState::Started);
+        self.release(KeyCode::KeyW);
+        assert!(served, "the ball wasn't served");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waits_to_serve_until_the_player_moves() {
+        let mut harness = Harness::new();
+        harness.ticks(16);
+        assert_eq!(harness.state(), GameState::Serving);
+        assert_eq!(harness.ball().1, Vec2::ZERO);
+
+        harness.serve();
+        let (start, vel) = harness.ball();
+        assert_ne!(vel, Vec2::ZERO);
+        harness.ticks(4);
+        assert_ne!(harness.ball().0, start);
+    }
+
+    #[test]
+    fn ball_in_the_right_goal_scores_for_the_left() {
+        let mut harness = Harness::new();
+        harness.serve();
+        let arena = harness.arena();
+        harness.place_paddle(Side::Right, arena.half_size.y/2f32);
+        harness.place_ball(Vec2::new(arena.half_size.x - 6f32, -arena.half_size.y/2f32), Vec2::X);
+
+        assert!(harness.run_until(8, |harness| harness.score().player == 1));
+        assert_eq!(harness.score().enemy, 0);
+        harness.ticks(1);
+        assert_eq!(harness.state(), GameState::RoundOver);
+    }
+
+    #[test]
+    fn ball_bounces_off_the_top_wall() {
+        let mut harness = Harness::new();
+        harness.serve();
+        let arena = harness.arena();
+        harness.place_ball(Vec2::new(0f32, arena.half_size.y - 12f32), Vec2::new(1f32, 1f32));
+
+        assert!(harness.run_until(16, |harness| harness.ball().1.y < 0f32));
+        let (pos, vel) = harness.ball();
+        assert!(pos.y < arena.half_size.y);
+        assert!(vel.x > 0f32);
+    }
+}
@@ -0,0 +1,44 @@
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{storage, Side};
+
+/// What a match starts with: its paddles, its balls and the score and name texts above them.
+/// Walls and goals follow the arena's edges, so they aren't part of it. Classic pong unless `--layout` says otherwise.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Layout {
+    pub paddles: Vec<PaddleLayout>,
+    /// Balls in play at once, all served from the center.
+    pub balls: usize,
+    pub hud: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaddleLayout {
+    pub side: Side,
+    /// Steered by this end's own input rather than the AI or the network.
+    #[serde(default)]
+    pub local: bool,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout {
+            paddles: vec![
+                PaddleLayout { side: Side::Left, local: true },
+                PaddleLayout { side: Side::Right, local: false },
+            ],
+            balls: 1,
+            hud: true,
+        }
+    }
+}
+
+impl Layout {
+    pub fn load(path: &Path) -> Option<Self> {
+        storage::load_ron(path)
+    }
+}
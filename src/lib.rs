@@ -0,0 +1,2428 @@
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+use std::time::Duration;
+
+use bevy::{
+    app::{AppExit, ScheduleRunnerPlugin},
+    ecs::schedule::{LogLevel, ScheduleBuildSettings},
+    diagnostic::DiagnosticsPlugin,
+    input::{InputPlugin, InputSystem},
+    prelude::*,
+    render::{camera::ScalingMode, view::screenshot::ScreenshotManager},
+    sprite::Mesh2dHandle,
+    time::TimeUpdateStrategy,
+    transform::TransformSystem,
+    window::{ApplicationLifetime, ExitCondition, PrimaryWindow, WindowFocused, WindowOccluded, WindowResized},
+    winit::WinitWindows,
+};
+#[cfg(feature = "net")]
+use bevy_ggrs::{GgrsApp, GgrsPlugin, GgrsSchedule, LoadWorld, LoadWorldSet, ReadInputs, Session};
+#[cfg(feature = "net")]
+use bevy_matchbox::prelude::{MatchboxSocket, SingleChannel};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use cli::Cli;
+use physics::Aabb;
+use prompts::{InputDevice, Prompt};
+use records::{LeaderboardEntry, Records};
+use replay::{Replay, TickInput};
+#[cfg(feature = "net")]
+use replication::{ReplicationAppExt, Replicated};
+#[cfg(feature = "net")]
+use rollback::RollbackConfig;
+use save::SavedMatch;
+use state_scoped::{StateScoped, StateScopedAppExt};
+use stats::LifetimeStats;
+
+pub use config::GameConfig;
+pub use layout::{Layout, PaddleLayout};
+pub use rules::{MatchRules, Mutators};
+pub use profiles::{Profile, Profiles};
+pub use replay::ReplayData;
+pub use settings::{AccessibilitySettings, Bindings, Difficulty, NetSettings, Presentation, Settings, VideoSettings};
+
+#[cfg(feature = "audio")]
+mod audio_cues;
+#[cfg(feature = "net")]
+mod bot_api;
+mod chat;
+#[cfg(feature = "net")]
+mod chat_box;
+mod cheats;
+mod cli;
+mod clip;
+mod config;
+mod daily;
+mod diagnostics;
+mod effects;
+mod export;
+mod goal_replay;
+#[cfg(test)]
+mod harness;
+mod layout;
+mod loading;
+#[cfg(feature = "net")]
+mod lobby;
+#[cfg(feature = "net")]
+mod lobby_browser;
+mod online;
+mod photo;
+#[cfg(feature = "net")]
+mod net;
+pub mod physics;
+mod profiles;
+mod prompts;
+mod rating;
+mod records;
+mod replay;
+#[cfg(feature = "net")]
+mod replication;
+#[cfg(feature = "net")]
+mod rollback;
+mod rules;
+mod rulesets;
+mod save;
+#[cfg(feature = "net")]
+mod server;
+mod settings;
+mod shared_replays;
+mod speedrun;
+mod state_scoped;
+mod stats;
+mod storage;
+mod theme;
+#[cfg(feature = "net")]
+mod tournament;
+mod tutorial;
+#[cfg(feature = "twitch")]
+mod twitch;
+mod wind;
+
+const WINDOW_SIZE: (f32, f32) = (512f32, 512f32);
+
+const CONTACT_EPSILON: f32 = 0.01f32;
+const BALL_COLLISIONS: bool = true;
+
+const SPIN_PER_HIT: f32 = 2f32;
+const SPIN_DECAY: f32 = 0.5f32;
+const MAGNUS_COEFFICIENT: f32 = 0.5f32;
+
+const SPIN_MARKER_SHAPE: Rectangle = Rectangle {
+    half_size: Vec2 { x: 1.5f32, y: 1.5f32 }
+};
+const SPIN_MARKER_RADIUS: f32 = 8f32;
+const SPIN_MARKER_RATE: f32 = 4f32;
+const SPIN_MARKER_MIN_SPIN: f32 = 0.1f32;
+const SPIN_MARKER_COLOR: Color = Color::rgb(1f32, 0.8f32, 0.2f32);
+
+const FIXED_TIMESTEP_HZ: f64 = 64f64;
+
+const STICK_DEADZONE: f32 = 0.3f32;
+const POINTER_DEADZONE: f32 = 4f32;
+
+const LETTERBOX_BAR_SIZE: f32 = 8192f32;
+const LETTERBOX_COLOR: Color = Color::BLACK;
+
+const WINDOW_ICON: &[u8] = include_bytes!("../assets/icon.png");
+
+const TEXT_OFFSET_X: f32 = 32f32;
+const PAUSE_MENU_FONT_SIZE: f32 = 16f32;
+const TOAST_DURATION: f32 = 2f32;
+
+const POINTS_TO_WIN: i32 = 7;
+
+const WALL_THICKNESS: f32 = 32f32;
+const GOAL_DEPTH: f32 = 32f32;
+
+const ENEMY_AIM_ERROR: f32 = 24f32;
+
+#[derive(States, Debug, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+enum GameState {
+    #[default]
+    Serving,
+    Started,
+    RoundOver,
+}
+
+/// Reads this end's input into `PlayerInput`, before the frame's fixed ticks so they see it straight away,
+/// and then, in each tick, hands it and every other controller's input to the paddles.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InputSet;
+
+/// Steers the paddles nobody controls.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AiSet;
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MovementSet;
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CollisionSet;
+
+/// Goals, rallies and the end of rounds and matches.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ScoringSet;
+
+/// Shows the state the fixed ticks left behind, once per frame.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UiSet;
+
+/// Layered over `GameState`: a match can be paused whichever phase it's in, and picks up in the same one.
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+enum PauseState {
+    #[default]
+    Running,
+    Paused,
+}
+
+#[derive(Resource, Reflect, Default, Clone, Hash, Serialize, Deserialize)]
+#[reflect(Resource)]
+struct Score {
+    player: i32,
+    enemy: i32,
+}
+
+impl Score {
+    fn of(&self, side: Side) -> i32 {
+        match side {
+            Side::Left => self.player,
+            Side::Right => self.enemy,
+        }
+    }
+
+    fn of_mut(&mut self, side: Side) -> &mut i32 {
+        match side {
+            Side::Left => &mut self.player,
+            Side::Right => &mut self.enemy,
+        }
+    }
+}
+
+#[derive(Resource, Reflect, Clone, Copy)]
+#[reflect(Resource)]
+struct Arena {
+    half_size: Vec2,
+}
+
+impl Arena {
+    fn from_size(width: f32, height: f32) -> Self {
+        Arena {
+            half_size: Vec2::new(width, height)/2f32,
+        }
+    }
+
+    fn wall(&self, dir_y: f32) -> Aabb {
+        Aabb::new(
+            Vec2::new(0f32, dir_y * (self.half_size.y + WALL_THICKNESS/2f32)),
+            Vec2::new(self.half_size.x + GOAL_DEPTH, WALL_THICKNESS/2f32),
+        )
+    }
+
+    fn goal(&self, dir_x: f32) -> Aabb {
+        Aabb::new(
+            Vec2::new(dir_x * (self.half_size.x + GOAL_DEPTH/2f32), 0f32),
+            Vec2::new(GOAL_DEPTH/2f32, self.half_size.y),
+        )
+    }
+
+    fn paddle_x(&self, dir_x: f32, half_size: Vec2) -> f32 {
+        dir_x * (self.half_size.x - half_size.x)
+    }
+
+    fn fit(window: &Window, video: &VideoSettings) -> Self {
+        match video.presentation {
+            Presentation::Stretch => Arena::from_size(window.width(), window.height()),
+            Presentation::Letterbox => Arena::default(),
+        }
+    }
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Arena::from_size(WINDOW_SIZE.0, WINDOW_SIZE.1)
+    }
+}
+
+#[derive(Resource, Reflect, Default, Clone, Serialize, Deserialize)]
+#[reflect(Resource)]
+struct ServeDir(f32);
+
+#[derive(Resource, Reflect, Default, Clone, Serialize, Deserialize)]
+#[reflect(Resource)]
+struct Rally {
+    hits: u32,
+}
+
+#[derive(Resource, Reflect, Default, Clone, Serialize, Deserialize)]
+#[reflect(Resource)]
+struct MatchClock {
+    elapsed: f32,
+}
+
+/// Which end of the arena a paddle, score or goal belongs to. The player plays the left side against the AI.
+#[derive(Component, Reflect, Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[reflect(Component)]
+pub enum Side {
+    #[default]
+    Left,
+    Right,
+}
+
+impl Side {
+    /// Where this side's state sits in per-side arrays like network inputs and lobby names.
+    fn index(self) -> usize {
+        match self {
+            Side::Left => 0,
+            Side::Right => 1,
+        }
+    }
+
+    /// Which way along x this side's end of the arena lies.
+    fn dir(self) -> f32 {
+        match self {
+            Side::Left => -1f32,
+            Side::Right => 1f32,
+        }
+    }
+
+    fn opposite(self) -> Side {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        }
+    }
+}
+
+#[derive(Event)]
+struct BallHitPaddle {
+    ball: Entity,
+    paddle: Entity,
+}
+
+#[derive(Event)]
+struct BallHitWall {
+    ball: Entity,
+}
+
+#[derive(Event)]
+struct GoalScored {
+    ball: Entity,
+    scorer: Side,
+}
+
+#[derive(Event)]
+struct MatchOver {
+    winner: Side,
+}
+
+#[derive(Resource, Reflect, Clone)]
+#[reflect(Resource)]
+struct NextRoundTimer(Timer);
+
+impl Default for NextRoundTimer {
+    fn default() -> Self {
+        NextRoundTimer(Timer::from_seconds(GameConfig::default().next_round_interval, TimerMode::Once))
+    }
+}
+
+#[derive(Resource, Clone)]
+struct GameRng {
+    seed: u64,
+    rng: ChaCha8Rng,
+}
+
+impl GameRng {
+    fn from_seed(seed: u64) -> Self {
+        GameRng {
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+}
+
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource)]
+struct EnemyAim(f32);
+
+#[derive(Resource, Default)]
+struct PlayerInput {
+    dir: i32,
+    /// Set by the on-screen serve button until the next fixed tick picks it up.
+    serve: bool,
+}
+
+#[derive(Resource, Default)]
+struct ServeRequested(bool);
+
+#[derive(Component)]
+struct ServeButton;
+
+/// Whether this session's results are written to disk; off for replays and headless simulations.
+#[derive(Resource, Clone, Copy)]
+struct Persist(bool);
+
+#[derive(Resource)]
+struct HeadlessSim {
+    remaining: u32,
+    player_wins: u32,
+}
+
+#[derive(Component)]
+struct ScoreText;
+
+#[derive(Component)]
+struct StatsScreen;
+
+#[derive(Component)]
+struct PauseMenu;
+
+#[derive(Component)]
+struct ServePrompt;
+
+#[derive(Component)]
+struct ProfileName;
+
+#[derive(Component)]
+struct Toast(Timer);
+
+/// Keeps a HUD element this far below the top edge of the arena as the window resizes.
+#[derive(Component)]
+struct TopAnchored(f32);
+
+/// One of the four bars covering everything outside the court in letterbox mode, on the side `dir` points to.
+#[derive(Component)]
+struct LetterboxBar {
+    dir: Vec2,
+}
+
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+struct Collider {
+    half_size: Vec2,
+}
+
+#[derive(Component, Reflect, Default, Clone, Copy)]
+#[reflect(Component)]
+struct CollisionLayers {
+    membership: u32,
+    mask: u32,
+}
+
+impl CollisionLayers {
+    const BALL: u32 = 1 << 0;
+    const PADDLE: u32 = 1 << 1;
+    const WALL: u32 = 1 << 2;
+    const GOAL: u32 = 1 << 3;
+
+    fn new(membership: u32, mask: u32) -> Self {
+        CollisionLayers { membership, mask }
+    }
+
+    fn interacts(&self, other: &CollisionLayers) -> bool {
+        self.mask & other.membership != 0 && other.mask & self.membership != 0
+    }
+}
+
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+struct Surface {
+    restitution: f32,
+    friction: f32,
+}
+
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+struct Wall;
+
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+struct Trigger;
+
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+struct Goal {
+    scorer: Side,
+}
+
+/// Scales one paddle's length on top of the config, so each player can pick their own.
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+struct PaddleLength(f32);
+
+impl Default for PaddleLength {
+    fn default() -> Self {
+        PaddleLength(1f32)
+    }
+}
+
+#[derive(Component, Reflect, Clone, Serialize, Deserialize)]
+#[reflect(Component)]
+struct Paddle {
+    dir: i32,
+    vel: f32,
+}
+
+impl Default for Paddle {
+    fn default() -> Self {
+        Paddle {
+            dir: 0,
+            vel: 0f32,
+        }
+    }
+}
+
+#[derive(Component, Reflect, Default, Clone, Copy)]
+#[reflect(Component)]
+struct PaddleMotion {
+    accel: f32,
+    max_speed: f32,
+    stop_friction: f32,
+    instant: bool,
+}
+
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+struct Interpolated {
+    previous: Vec2,
+    current: Vec2,
+}
+
+impl Interpolated {
+    fn at(pos: Vec2) -> Self {
+        Interpolated {
+            previous: pos,
+            current: pos,
+        }
+    }
+}
+
+/// The paddle this end's own input steers.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+struct LocalPaddle;
+
+#[derive(Component, Reflect, Default, Clone, Serialize, Deserialize)]
+#[reflect(Component)]
+struct Ball {
+    vel: Vec2,
+    speed: f32,
+    spin: f32,
+    #[serde(skip)]
+    last_hit: Option<Entity>,
+}
+
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+struct SpinMarker {
+    angle: f32,
+}
+
+/// The ball a paddle at `pos` should go after: whichever is closest along x, since that one reaches it first.
+fn nearest_ball<'a>(
+    balls: impl IntoIterator<Item = (&'a Transform, &'a Ball)>,
+    pos: Vec3,
+) -> Option<(&'a Transform, &'a Ball)> {
+    balls.into_iter().min_by(|(a, _), (b, _)| (a.translation.x - pos.x).abs().total_cmp(&(b.translation.x - pos.x).abs()))
+}
+
+fn clamp<T>(v: T, min: T, max: T) -> T
+    where T: PartialOrd
+{
+    if v < min { min } else if v > max { max } else { v }
+}
+
+/// The game itself, for embedding in another Bevy app (as a minigame, say) or building one in a test.
+/// Add it after `DefaultPlugins`; the window, the command line and networking are left to `main`.
+pub struct PongPlugin {
+    pub settings: Settings,
+    pub profiles: Profiles,
+    /// Plays this back instead of recording a new match.
+    pub replay: Option<ReplayData>,
+    /// Seeds a new match; random when `None`.
+    pub seed: Option<u64>,
+    /// Whether records, stats, replays and unfinished matches are written to disk.
+    pub persist: bool,
+    pub layout: Layout,
+}
+
+impl Default for PongPlugin {
+    fn default() -> Self {
+        PongPlugin {
+            settings: Settings::default(),
+            profiles: Profiles::default(),
+            replay: None,
+            seed: None,
+            persist: false,
+            layout: Layout::default(),
+        }
+    }
+}
+
+impl Plugin for PongPlugin {
+    fn build(&self, app: &mut App) {
+        let replay = match &self.replay {
+            Some(data) => Replay::play(data.clone()),
+            None => Replay::record(self.seed.unwrap_or_else(rand::random), self.settings.difficulty),
+        };
+        diagnostics::register(app);
+        app
+            .add_systems(
+                Startup,
+                (config::load_game_config, loading::spawn_loading_screen, startup, set_window_icon, online::fetch_online_leaderboard),
+            )
+            .add_systems(
+                PostStartup,
+                (
+                    save::resume_match,
+                    #[cfg(feature = "net")]
+                    replication::assign_net_ids,
+                    diagnostics::spawn_debug_overlay,
+                    theme::decorate_bodies,
+                    wind::spawn_wind_indicator,
+                ),
+            )
+            .configure_sets(PreUpdate, InputSet.after(InputSystem))
+            .configure_sets(FixedUpdate, (InputSet, AiSet, MovementSet, CollisionSet, ScoringSet).chain())
+            .add_systems(PreUpdate, player_input.in_set(InputSet))
+            .add_systems(
+                Update,
+                (
+                    (
+                        resize_arena,
+                        loading::track_loading.run_if(in_state(loading::LoadingState::Loading)),
+                        config::apply_game_config.after(resize_arena),
+                        rules::apply_paddle_lengths.run_if(resource_changed::<rules::MatchRules>),
+                        config::resize_bodies.after(config::apply_game_config).after(rules::apply_paddle_lengths),
+                        config::apply_surfaces.after(config::apply_game_config).run_if(resource_changed::<GameConfig>),
+                        diagnostics::log_gameplay_events,
+                        diagnostics::log_state_transitions,
+                        diagnostics::measure_rally_rate,
+                        diagnostics::control_frame_step,
+                        profiles::save_profiles,
+                        save::clear_save_on_match_over,
+                        cycle_profile,
+                        online::submit_rally_record,
+                        online::poll_online_leaderboard.after(online::submit_rally_record),
+                    ),
+                    (
+                        settings::save_settings,
+                        settings::track_window_geometry,
+                        settings::adjust_ui_scale,
+                        settings::apply_ui_scale.after(settings::adjust_ui_scale),
+                        settings::toggle_reduced_motion,
+                        settings::adjust_juice,
+                        effects::add_shake.run_if(settings::full_motion),
+                        effects::apply_shake.after(effects::add_shake),
+                        theme::cycle_palette,
+                        theme::toggle_high_contrast,
+                        theme::apply_theme.after(theme::cycle_palette).after(theme::toggle_high_contrast),
+                        theme::fit_decorations,
+                        settings::toggle_fullscreen,
+                        settings::apply_video_settings.after(settings::toggle_fullscreen),
+                    ),
+                    (
+                        (
+                            update_ui,
+                            update_spin_markers,
+                            update_serve_prompt,
+                            prompts::update_prompts.after(prompts::track_input_device),
+                            update_toasts,
+                            wind::update_wind_indicator,
+                            diagnostics::update_debug_overlay.after(diagnostics::control_frame_step),
+                        ).in_set(UiSet),
+                        toggle_stats_screen,
+                        take_screenshot,
+                        serve_button,
+                        prompts::track_input_device,
+                        update_cursor,
+                        handle_app_lifecycle.run_if(in_state(PauseState::Running)),
+                        resume_from_pause
+                            .run_if(in_state(PauseState::Paused).and_then(not(resource_exists::<photo::PhotoMode>))),
+                        // After resuming, so the Esc that leaves photo mode doesn't also leave the pause menu.
+                        (
+                            photo::enter_photo_mode
+                                .run_if(in_state(PauseState::Paused).and_then(not(resource_exists::<photo::PhotoMode>))),
+                            (photo::move_photo_camera, photo::edit_photo_mode)
+                                .run_if(resource_exists::<photo::PhotoMode>),
+                        ).chain().after(resume_from_pause),
+                        clip::capture_rally_frames.after(take_screenshot),
+                        clip::export_rally_clip,
+                    ),
+                )
+            )
+            .edit_schedule(FixedFirst, deny_ambiguities)
+            .edit_schedule(FixedUpdate, deny_ambiguities)
+            .edit_schedule(FixedLast, deny_ambiguities)
+            .add_systems(
+                FixedFirst,
+                (
+                    // Apply state changes every tick rather than every frame so replays stay in sync.
+                    apply_state_transition::<GameState>,
+                    restore_interpolated.run_if(not(in_rollback)),
+                    diagnostics::begin_tick,
+                ).chain()
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    (
+                        apply_player_input,
+                        #[cfg(feature = "net")]
+                        (
+                            bot_api::receive_bot_commands.run_if(resource_exists::<bot_api::BotApi>),
+                            tournament::drive_entrants.run_if(resource_exists::<tournament::Tournament>),
+                        ).chain(),
+                        #[cfg(feature = "net")]
+                        net::receive_guest_input.run_if(resource_exists::<net::LanHost>),
+                        #[cfg(feature = "net")]
+                        server::receive_clients.run_if(resource_exists::<server::DedicatedServer>),
+                        chat::apply_network_control,
+                        pre_serve.run_if(
+                            in_state(GameState::Serving)
+                                .and_then(not(setting_up_match))
+                                .and_then(tutorial::serve_allowed)
+                        ),
+                    ).chain().in_set(InputSet),
+                    enemy_ai.run_if(in_state(GameState::Started)).in_set(AiSet),
+                    (
+                        tick_match_clock,
+                        move_paddle,
+                        #[cfg(feature = "net")]
+                        net::compensate_guest_paddle.run_if(resource_exists::<net::LanHost>),
+                        #[cfg(feature = "net")]
+                        server::compensate_client_paddles.run_if(resource_exists::<server::DedicatedServer>),
+                        move_ball.run_if(in_state(GameState::Started)),
+                    ).chain().in_set(MovementSet),
+                    collide_balls.run_if(in_state(GameState::Started).and_then(|| BALL_COLLISIONS)).in_set(CollisionSet),
+                    (
+                        score_goal,
+                        track_rally,
+                        finish_match,
+                        export::record_match_log,
+                        export::export_match_log,
+                        round_over.run_if(in_state(GameState::RoundOver)),
+                    ).chain().in_set(ScoringSet),
+                ).run_if(
+                    in_state(PauseState::Running)
+                        .and_then(in_state(loading::LoadingState::Done))
+                        .and_then(diagnostics::tick_allowed)
+                        .and_then(not(following_host))
+                        .and_then(not(in_rollback))
+                        .and_then(not(resource_exists::<goal_replay::GoalReplay>))
+                )
+            )
+            .add_systems(
+                FixedLast,
+                (
+                    record_interpolated,
+                    diagnostics::record_tick_deltas.run_if(diagnostics::tick_allowed),
+                ).chain().run_if(not(in_rollback)),
+            )
+            .add_systems(Last, (replay::save_replay_on_exit, save::save_match_on_exit, settings::save_window_geometry_on_exit, settings::limit_frame_rate))
+            .add_systems(
+                PostUpdate,
+                interpolate_transforms
+                    .before(TransformSystem::TransformPropagate)
+                    .run_if(not(in_rollback))
+            )
+            .add_systems(
+                OnEnter(GameState::Started),
+                (on_round_started, clip::start_rally_clip)
+            )
+            .add_systems(
+                OnEnter(GameState::RoundOver),
+                on_round_over
+            )
+            .add_systems(
+                OnEnter(GameState::Serving),
+                on_start_serving
+            )
+            .add_systems(OnEnter(PauseState::Paused), on_pause)
+            .add_systems(OnExit(PauseState::Paused), on_resume)
+            .init_state::<GameState>()
+            .init_state::<PauseState>()
+            .init_state::<loading::LoadingState>()
+            .enable_state_scoped_entities::<GameState>()
+            .enable_state_scoped_entities::<PauseState>()
+            .enable_state_scoped_entities::<loading::LoadingState>()
+            .init_resource::<loading::Preload>()
+            .init_asset::<GameConfig>()
+            .init_asset_loader::<config::GameConfigLoader>()
+            .init_resource::<GameConfig>()
+            .add_event::<BallHitPaddle>()
+            .add_event::<BallHitWall>()
+            .add_event::<GoalScored>()
+            .add_event::<MatchOver>()
+            .init_resource::<Score>()
+            .init_resource::<Arena>()
+            .init_resource::<NextRoundTimer>()
+            .init_resource::<EnemyAim>()
+            .init_resource::<ServeDir>()
+            .init_resource::<Rally>()
+            .init_resource::<MatchClock>()
+            .init_resource::<effects::Shake>()
+            .init_resource::<PlayerInput>()
+            .init_resource::<ServeRequested>()
+            .init_resource::<MatchRules>()
+            .init_resource::<InputDevice>()
+            .init_resource::<online::OnlineLeaderboard>()
+            .init_resource::<export::MatchLog>()
+            .init_resource::<clip::RallyClip>()
+            .init_resource::<diagnostics::RallyRate>()
+            .init_resource::<diagnostics::FrameStep>()
+            .register_type::<Ball>()
+            .register_type::<Paddle>()
+            .register_type::<PaddleMotion>()
+            .register_type::<LocalPaddle>()
+            .register_type::<Side>()
+            .register_type::<Interpolated>()
+            .register_type::<SpinMarker>()
+            .register_type::<Collider>()
+            .register_type::<CollisionLayers>()
+            .register_type::<Surface>()
+            .register_type::<Wall>()
+            .register_type::<Trigger>()
+            .register_type::<Goal>()
+            .register_type::<Score>()
+            .register_type::<Arena>()
+            .register_type::<ServeDir>()
+            .register_type::<Rally>()
+            .register_type::<MatchClock>()
+            .register_type::<NextRoundTimer>()
+            .register_type::<EnemyAim>()
+            .register_type::<GameConfig>()
+            .insert_resource(Time::<Fixed>::from_hz(FIXED_TIMESTEP_HZ))
+            .insert_resource(GameRng::from_seed(replay.data().seed))
+            .insert_resource(replay)
+            .insert_resource(Persist(self.persist))
+            .insert_resource(self.layout.clone())
+            .insert_resource(self.settings.clone())
+            .insert_resource(Records::load())
+            .insert_resource(LifetimeStats::load(self.profiles.active()))
+            .insert_resource(rating::Rating::load(self.profiles.active()))
+            .insert_resource(self.profiles.clone());
+        #[cfg(feature = "net")]
+        app
+            .init_resource::<chat_box::ChatBox>()
+            .add_event::<chat_box::ChatReceived>()
+            .add_event::<chat_box::ChatSent>()
+            .init_resource::<replication::NetIds>()
+            .init_resource::<replication::ReceivedState>()
+            .replicate::<Paddle>()
+            .replicate::<Ball>()
+            .replicate_resource::<Score>();
+    }
+}
+
+/// Whether a rollback session is running the match in its own schedule, in place of the fixed schedules.
+#[cfg(feature = "net")]
+fn in_rollback(session: Option<Res<Session<RollbackConfig>>>) -> bool {
+    session.is_some()
+}
+
+#[cfg(not(feature = "net"))]
+fn in_rollback() -> bool {
+    false
+}
+
+/// Whether this end is a LAN guest, which shows the host's simulation rather than running its own.
+#[cfg(feature = "net")]
+fn following_host(guest: Option<Res<net::LanGuest>>) -> bool {
+    guest.is_some()
+}
+
+#[cfg(not(feature = "net"))]
+fn following_host() -> bool {
+    false
+}
+
+/// Nobody serves while the lobby, the lobby browser or the ruleset builder is still open.
+fn setting_up_match(
+    #[cfg(feature = "net")] lobby: Option<Res<lobby::Lobby>>,
+    #[cfg(feature = "net")] browser: Option<Res<lobby_browser::LobbyBrowser>>,
+    builder: Option<Res<rulesets::RulesetBuilder>>,
+) -> bool {
+    #[cfg(feature = "net")]
+    if lobby.is_some() || browser.is_some() {
+        return true;
+    }
+    builder.is_some()
+}
+
+/// Just the simulation, updating every `wait`: no window, GPU or audio. Meshes and materials are still
+/// registered as plain assets since startup builds them.
+fn add_headless_plugins(app: &mut App, wait: Duration) {
+    app
+        .add_plugins((
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(wait)),
+            TransformPlugin,
+            HierarchyPlugin,
+            DiagnosticsPlugin,
+            AssetPlugin::default(),
+            InputPlugin,
+            WindowPlugin {
+                primary_window: None,
+                exit_condition: ExitCondition::DontExit,
+                ..default()
+            },
+        ))
+        .init_asset::<Mesh>()
+        .init_asset::<ColorMaterial>()
+        .init_resource::<UiScale>();
+}
+
+/// Entry point for both the desktop binary and the Android activity, which is built from `android/`.
+pub fn main() {
+    let cli = Cli::parse();
+    #[cfg(not(feature = "net"))]
+    {
+        let networked = [
+            (cli.host.is_some(), "--host"),
+            (cli.join.is_some(), "--join"),
+            (cli.spectate.is_some(), "--spectate"),
+            (cli.server.is_some(), "--server"),
+            (cli.room.is_some(), "--room"),
+            (cli.browse, "--browse"),
+            (cli.rollback, "--rollback"),
+            (cli.bot_api.is_some(), "--bot-api"),
+        ];
+        if let Some((_, flag)) = networked.iter().find(|(on, _)| *on) {
+            eprintln!("Built without networking support; {} isn't available", flag);
+            std::process::exit(2);
+        }
+    }
+    #[cfg(not(feature = "twitch"))]
+    if cli.twitch.is_some() {
+        eprintln!("Built without Twitch support; --twitch isn't available");
+        std::process::exit(2);
+    }
+    let mut settings = Settings::load();
+    if let Some(difficulty) = cli.difficulty {
+        settings.difficulty = difficulty;
+    }
+    if cli.fullscreen {
+        settings.video.fullscreen = true;
+    }
+    if let Some(present_mode) = cli.present_mode {
+        settings.video.present_mode = present_mode;
+    }
+    if let Some(monitor) = cli.monitor {
+        settings.video.monitor = Some(monitor);
+        settings.video.window_position = None;
+    }
+    if let Some(input_delay) = cli.input_delay {
+        settings.net.input_delay = input_delay;
+    }
+    if cli.headless_sim.is_some() || cli.server.is_some() {
+        settings.video.frame_cap = None;
+    }
+    if cli.speedrun {
+        settings.difficulty = speedrun::GAUNTLET[0];
+    }
+    // Everyone plays the daily challenge against the same AI, with the same serves.
+    let daily = cli.daily.then(|| daily::DailyChallenge::new(daily::today()));
+    if daily.is_some() {
+        settings.difficulty = Difficulty::Normal;
+    }
+    let replay = match &cli.replay {
+        Some(path) => {
+            let Some(data) = ReplayData::load(path) else {
+                eprintln!("Couldn't load replay {}", path.display());
+                std::process::exit(1);
+            };
+            settings.difficulty = data.difficulty;
+            Some(data)
+        },
+        None => None,
+    };
+    let layout = match &cli.layout {
+        Some(path) => Layout::load(path).unwrap_or_else(|| {
+            eprintln!("Couldn't load layout {}", path.display());
+            std::process::exit(1);
+        }),
+        None => Layout::default(),
+    };
+    // Networked matches depend on the other player's input, so they're neither replayable nor recorded.
+    // Neither are bot matches, which shouldn't count toward the player's stats.
+    let lan = cli.host.is_some() || cli.join.is_some() || cli.spectate.is_some();
+    let online = lan || cli.room.is_some() || cli.browse || cli.server.is_some();
+    // Nor are the tutorial, speedruns, daily challenges and custom rules, whose scripted serves, changing AI
+    // and mutators a replay couldn't reproduce. Speedruns and daily challenges keep their own boards.
+    let persist = replay.is_none()
+        && cli.watch.is_none()
+        && cli.headless_sim.is_none()
+        && cli.bot_api.is_none()
+        && !cli.tutorial
+        && !cli.speedrun
+        && daily.is_none()
+        && !cli.rules
+        && !online;
+    #[cfg(feature = "net")]
+    let rollback_delay = cli.rollback.then_some(settings.net.input_delay);
+    let saved = if persist { SavedMatch::take() } else { None };
+    let mut profiles = Profiles::load();
+    if let Some(name) = &cli.profile {
+        if profiles.select(name) {
+            if let Err(err) = profiles.save() {
+                eprintln!("Failed to save profiles: {}", err);
+            }
+        }
+    }
+
+    let mut app = App::new();
+    if let Some(matches) = cli.headless_sim {
+        // Every update advances exactly one fixed tick so matches run as fast as possible.
+        add_headless_plugins(&mut app, Duration::ZERO);
+        app
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(1f64/FIXED_TIMESTEP_HZ)))
+            .insert_resource(HeadlessSim { remaining: matches, player_wins: 0 })
+            .add_systems(PreUpdate, autopilot_input.after(player_input).in_set(InputSet))
+            .add_systems(Update, count_simulated_matches);
+    }
+    else if cli.server.is_some() {
+        add_headless_plugins(&mut app, Duration::from_secs_f64(1f64/FIXED_TIMESTEP_HZ));
+    }
+    else {
+        app.add_plugins(
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: Some(Window {
+                        title: "KPong".into(),
+                        mode: settings.video.window_mode(),
+                        present_mode: settings.video.present_mode,
+                        position: settings.video.window_position(),
+                        resolution: cli.size
+                            .or(settings.video.window_size.map(|size| (size.x, size.y)))
+                            .unwrap_or(WINDOW_SIZE)
+                            .into(),
+                        canvas: Some("#bevy".into()),
+                        fit_canvas_to_parent: true,
+                        ..default()
+                    }),
+                    ..default()
+                })
+        );
+        #[cfg(feature = "dev")]
+        app.add_plugins(
+            bevy_inspector_egui::quick::WorldInspectorPlugin::new()
+                .run_if(bevy::input::common_conditions::input_toggle_active(false, KeyCode::F1)),
+        );
+    }
+    app.add_plugins(PongPlugin {
+        settings,
+        profiles,
+        replay,
+        seed: cli.seed.or(daily.as_ref().map(daily::DailyChallenge::seed)),
+        persist,
+        layout,
+    });
+    if let Some(saved) = saved {
+        saved.rules.apply(&mut app.world.resource_mut::<GameConfig>());
+        app
+            .insert_resource(saved.rules.clone())
+            .insert_resource(State::new(saved.state.clone()))
+            .insert_resource(saved);
+    }
+    #[cfg(feature = "net")]
+    if let Some(port) = cli.host {
+        let host = net::LanHost::bind(port, rollback_delay, cli.net_conditions).unwrap_or_else(|err| {
+            eprintln!("Couldn't host on port {}: {}", port, err);
+            std::process::exit(1);
+        });
+        let lobby = lobby::Lobby::new(
+            app.world.resource::<Profiles>().active().name.clone(),
+            app.world.resource::<GameRng>().seed,
+        );
+        app
+            .insert_resource(host)
+            .insert_resource(lobby)
+            .add_systems(PostStartup, (net::attach_guest_controller, net::spawn_reconnect_notice));
+    }
+    else if let Some(addr) = cli.join.or(cli.spectate) {
+        let spectating = cli.join.is_none();
+        let guest = if spectating {
+            net::LanGuest::spectate(addr, cli.net_conditions)
+        }
+        else {
+            net::LanGuest::connect(addr, rollback_delay, cli.net_conditions)
+        };
+        let guest = guest.unwrap_or_else(|err| {
+            eprintln!("Couldn't join {}: {}", addr, err);
+            std::process::exit(1);
+        });
+        if spectating {
+            app.add_systems(PostStartup, net::spawn_spectator_hud);
+        }
+        else {
+            app.init_resource::<lobby::LobbyChoice>();
+        }
+        app
+            .insert_resource(guest)
+            .add_systems(PostStartup, net::spawn_reconnect_notice)
+            .add_systems(
+                Update,
+                (net::watch_host_connection, net::greet_host, net::send_guest_input)
+                    .run_if(resource_exists::<net::LanGuest>),
+            )
+            .add_systems(
+                FixedUpdate,
+                // Never runs alongside the host's simulation, but is ordered after it so the schedule has no ambiguities.
+                (net::predict_local_paddle, move_paddle, net::apply_snapshot, replication::apply_received_state)
+                    .chain()
+                    .after(ScoringSet)
+                    .run_if(resource_exists::<net::LanGuest>),
+            );
+    }
+    else if let Some(port) = cli.server {
+        let server = server::DedicatedServer::bind(port, cli.net_conditions).unwrap_or_else(|err| {
+            eprintln!("Couldn't serve on port {}: {}", port, err);
+            std::process::exit(1);
+        });
+        info!("Serving on port {}", port);
+        app
+            .insert_resource(server)
+            .add_systems(PostStartup, server::attach_client_controllers)
+            .add_systems(Update, server::ping_clients)
+            .add_systems(
+                FixedLast,
+                (replication::assign_net_ids, server::send_snapshot.after(record_interpolated)).chain(),
+            );
+        if let Some(entrants) = cli.tournament.clone() {
+            app
+                .insert_resource(tournament::Tournament::new(entrants))
+                .add_systems(PostStartup, tournament::open_tournament)
+                .add_systems(Update, (tournament::record_results, tournament::schedule_matches).chain())
+                .add_systems(OnEnter(GameState::Started), tournament::aim_entrants);
+        }
+    }
+    else if cli.room.is_some() || cli.browse {
+        if let Some(room) = &cli.room {
+            app
+                .insert_resource(rollback::RelayRoom(room.clone()))
+                .add_systems(Startup, rollback::open_relay_room)
+                .add_systems(PostStartup, rollback::label_waiting_opponent);
+        }
+        if cli.public {
+            app.add_systems(PostStartup, lobby_browser::advertise_relay_room);
+        }
+        if cli.browse {
+            app
+                .add_systems(Startup, lobby_browser::open_lobby_browser)
+                .add_systems(
+                    Update,
+                    (lobby_browser::browse_lobbies, lobby_browser::poll_lobby_browser)
+                        .chain()
+                        .run_if(resource_exists::<lobby_browser::LobbyBrowser>),
+                );
+        }
+        app.add_systems(
+            Update,
+            (
+                rollback::wait_for_opponent.run_if(resource_exists::<MatchboxSocket<SingleChannel>>),
+                lobby_browser::advertise_lobby.run_if(resource_exists::<lobby_browser::AdvertisedLobby>),
+            ),
+        );
+    }
+    if !online && cli.headless_sim.is_none() && cli.bot_api.is_none() {
+        // Only offline matches are replayable, though sharing them goes through the relay.
+        app
+            .init_resource::<shared_replays::SharedReplays>()
+            .init_resource::<cheats::CheatMatcher>()
+            .init_resource::<goal_replay::History>()
+            .add_systems(PostStartup, shared_replays::spawn_shared_replays_screen)
+            .add_systems(
+                Update,
+                (
+                    (shared_replays::browse_shared_replays, shared_replays::poll_shared_replays).chain(),
+                    cheats::enter_pause_cheats
+                        .run_if(in_state(PauseState::Paused).and_then(not(resource_exists::<photo::PhotoMode>))),
+                    // Before a speedrun moves on to its next opponent.
+                    rating::rate_match
+                        .run_if(not(resource_exists::<tutorial::Tutorial>))
+                        .before(speedrun::track_speedrun),
+                    settings::adjust_game_speed,
+                    settings::apply_game_speed.after(settings::adjust_game_speed),
+                    // Slow motion is left out with reduced motion, and the match goes straight on to the next serve.
+                    (
+                        goal_replay::start_goal_replay
+                            .run_if(settings::full_motion.and_then(not(resource_exists::<goal_replay::GoalReplay>))),
+                        goal_replay::play_goal_replay.run_if(resource_exists::<goal_replay::GoalReplay>),
+                    ).chain(),
+                ),
+            )
+            .add_systems(
+                FixedLast,
+                goal_replay::record_history
+                    .after(record_interpolated)
+                    .run_if(in_state(GameState::Started).and_then(in_state(PauseState::Running))),
+            );
+        if let Some(code) = &cli.watch {
+            app
+                .insert_resource(shared_replays::WatchCode(code.clone()))
+                .add_systems(Startup, shared_replays::download_shared_replay);
+        }
+    }
+    if cli.tutorial {
+        app
+            .init_resource::<tutorial::Tutorial>()
+            .add_systems(PostStartup, tutorial::spawn_tutorial_prompt)
+            .add_systems(Update, tutorial::advance_tutorial)
+            .add_systems(OnEnter(GameState::Started), tutorial::feed_ball.after(on_round_started));
+    }
+    if cli.speedrun {
+        let best = speedrun::BestTimes::load(app.world.resource::<Profiles>().active());
+        app
+            .insert_resource(speedrun::Speedrun::new(best))
+            .add_systems(PostStartup, speedrun::spawn_speedrun_text)
+            .add_systems(Update, (speedrun::track_speedrun, speedrun::update_speedrun_text).chain());
+    }
+    if cli.rules {
+        app
+            .add_systems(Startup, rulesets::open_ruleset_builder)
+            .add_systems(
+                Update,
+                (rulesets::edit_ruleset, rulesets::update_ruleset_screen)
+                    .chain()
+                    .run_if(resource_exists::<rulesets::RulesetBuilder>),
+            );
+    }
+    if let Some(daily) = daily {
+        info!("Daily challenge {}: {}", daily.day, daily.mutators.names().join(", "));
+        let rules = MatchRules { mutators: daily.mutators.clone(), ..default() };
+        rules.apply(&mut app.world.resource_mut::<GameConfig>());
+        app
+            .insert_resource(rules)
+            .insert_resource(daily)
+            .add_systems(PostStartup, daily::spawn_daily_text)
+            .add_systems(Update, (daily::track_daily, daily::update_daily_text).chain());
+    }
+    #[cfg(feature = "net")]
+    if cli.host.is_some() || cli.join.is_some() {
+        // A guest takes over as host when the host is gone for good.
+        app
+            .add_systems(
+                Update,
+                (
+                    net::ping_guest.run_if(resource_exists::<net::LanHost>),
+                    net::receive_guest_input.run_if(net::awaiting_guest),
+                    net::watch_guest_connection.run_if(resource_exists::<net::LanHost>),
+                )
+                    .chain(),
+            )
+            .add_systems(
+                FixedLast,
+                (replication::assign_net_ids, net::send_snapshot.after(record_interpolated))
+                    .chain()
+                    .run_if(resource_exists::<net::LanHost>),
+            );
+    }
+    #[cfg(feature = "net")]
+    if online && cli.spectate.is_none() && cli.server.is_none() {
+        app
+            .add_systems(PostStartup, net::spawn_ping_hud)
+            .add_systems(Update, net::update_ping_hud);
+    }
+    #[cfg(feature = "net")]
+    if lan {
+        // Chat rides on the LAN link, which rollback hands over to its session.
+        app
+            .init_resource::<cheats::CheatMatcher>()
+            .add_systems(PostStartup, (chat_box::spawn_chat_overlay, lobby::spawn_lobby_overlay))
+            .add_systems(
+                Update,
+                (
+                    lobby::edit_lobby,
+                    cheats::enter_lobby_cheats.run_if(resource_exists::<net::LanHost>.and_then(resource_exists::<lobby::Lobby>)),
+                    net::send_lobby.run_if(resource_exists::<net::LanHost>.and_then(resource_exists::<lobby::Lobby>)),
+                    lobby::start_lobby_match.run_if(resource_exists::<lobby::Lobby>),
+                    lobby::update_lobby_overlay,
+                ).chain(),
+            )
+            .add_systems(
+                Update,
+                (
+                    chat_box::type_chat,
+                    net::send_chat.after(chat_box::type_chat),
+                    chat_box::receive_chat,
+                    chat_box::update_chat_overlay.after(chat_box::receive_chat),
+                ),
+            );
+    }
+    #[cfg(feature = "net")]
+    if (lan && rollback_delay.is_some()) || cli.room.is_some() || cli.browse {
+        // Both ends run the simulation and rewind whenever the other's input turns out different than predicted.
+        app
+            .add_plugins(GgrsPlugin::<RollbackConfig>::default())
+            .edit_schedule(GgrsSchedule, deny_ambiguities)
+            .set_rollback_schedule_fps(FIXED_TIMESTEP_HZ as usize)
+            .rollback_component_with_clone::<Transform>()
+            .rollback_component_with_clone::<Ball>()
+            .rollback_component_with_clone::<Paddle>()
+            .rollback_resource_with_clone::<Score>()
+            .rollback_resource_with_clone::<ServeDir>()
+            .rollback_resource_with_clone::<Rally>()
+            .rollback_resource_with_clone::<MatchClock>()
+            .rollback_resource_with_clone::<NextRoundTimer>()
+            .rollback_resource_with_clone::<GameRng>()
+            .rollback_resource_with_clone::<rollback::RollbackState>()
+            .checksum_component::<Transform>(rollback::checksum_transform)
+            .checksum_component::<Ball>(rollback::checksum_ball)
+            .checksum_resource_with_hash::<Score>()
+            .add_systems(ReadInputs, rollback::read_local_inputs)
+            .add_systems(PostStartup, rollback::spawn_desync_warning)
+            .add_systems(Update, rollback::detect_desync.run_if(rollback::in_session))
+            .add_systems(LoadWorld, rollback::restore_game_state.after(LoadWorldSet::Data))
+            .add_systems(
+                Update,
+                rollback::add_rollback_entities.run_if(resource_added::<Session<RollbackConfig>>),
+            )
+            .add_systems(
+                GgrsSchedule,
+                (
+                    rollback::begin_tick,
+                    rollback::apply_inputs,
+                    pre_serve.run_if(in_state(GameState::Serving)),
+                    tick_match_clock,
+                    move_paddle,
+                    move_ball.run_if(in_state(GameState::Started)),
+                    collide_balls.run_if(in_state(GameState::Started).and_then(|| BALL_COLLISIONS)),
+                    score_goal,
+                    track_rally,
+                    round_over.run_if(in_state(GameState::RoundOver)),
+                    // Transition at the end of the tick, so no pending state change is left out of the snapshot.
+                    apply_state_transition::<GameState>,
+                    rollback::end_tick,
+                ).chain(),
+            );
+    }
+    #[cfg(feature = "net")]
+    if let Some(port) = cli.bot_api {
+        let api = bot_api::BotApi::bind(port).unwrap_or_else(|err| {
+            eprintln!("Couldn't open the bot API on port {}: {}", port, err);
+            std::process::exit(1);
+        });
+        app
+            .insert_resource(api)
+            .add_systems(PostStartup, bot_api::attach_bot_controller)
+            .add_systems(FixedLast, bot_api::send_bot_state.after(record_interpolated));
+    }
+    // Chat needs a socket, and replays and simulations need the regular AI to stay deterministic.
+    // Headless runs have no audio output to play cues on.
+    #[cfg(feature = "audio")]
+    if cli.headless_sim.is_none() && cli.server.is_none() {
+        app
+            .insert_resource(bevy::audio::SpatialScale::new_2d(audio_cues::SPATIAL_SCALE))
+            .add_systems(
+                Update,
+                (
+                    audio_cues::toggle_audio_cues,
+                    audio_cues::attach_listener,
+                    audio_cues::sync_ball_tones.after(audio_cues::toggle_audio_cues),
+                    audio_cues::update_ball_tones,
+                    (audio_cues::announce_points, audio_cues::play_earcons).chain(),
+                ),
+            );
+    }
+    #[cfg(feature = "twitch")]
+    if let Some(channel) = cli.twitch.clone().filter(|_| persist && !cfg!(target_arch = "wasm32")) {
+        app
+            .insert_resource(twitch::ChatVotes::connect(&channel))
+            .add_systems(PostStartup, twitch::attach_network_controller)
+            .add_systems(Update, twitch::tally_chat_votes);
+    }
+    app.run();
+}
+
+fn startup(
+    mut cmd: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut arena: ResMut<Arena>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    rng: Res<GameRng>,
+    replay: Res<Replay>,
+    config: Res<GameConfig>,
+    settings: Res<Settings>,
+    layout: Res<Layout>,
+){
+    if let Ok(window) = windows.get_single() {
+        *arena = Arena::fit(window, &settings.video);
+    }
+    if replay.is_playing() {
+        info!("Playing back replay with seed {}", rng.seed);
+    }
+    else {
+        info!("Game seed: {} (pass --seed {} to reproduce)", rng.seed, rng.seed);
+    }
+
+    let paddle_mesh = Mesh2dHandle(meshes.add(Rectangle { half_size: config.paddle_half_size }));
+    let paddle_mat = materials.add(Color::WHITE);
+
+    cmd.spawn(Camera2dBundle::default());
+
+    let bar_mesh = Mesh2dHandle(meshes.add(Rectangle::new(LETTERBOX_BAR_SIZE, LETTERBOX_BAR_SIZE)));
+    let bar_mat = materials.add(LETTERBOX_COLOR);
+    for dir in [Vec2::X, Vec2::NEG_X, Vec2::Y, Vec2::NEG_Y] {
+        cmd.spawn((
+            ColorMesh2dBundle {
+                mesh: bar_mesh.clone(),
+                material: bar_mat.clone(),
+                transform: Transform::from_translation((dir * (arena.half_size + LETTERBOX_BAR_SIZE/2f32)).extend(10f32)),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            LetterboxBar { dir },
+        ));
+    }
+
+    for paddle in &layout.paddles {
+        let pos = Vec2::new(arena.paddle_x(paddle.side.dir(), config.paddle_half_size), 0f32);
+        let mut entity = cmd.spawn((
+            ColorMesh2dBundle {
+                mesh: paddle_mesh.clone(),
+                material: paddle_mat.clone(),
+                transform: Transform::from_translation(pos.extend(0f32)),
+                ..default()
+            },
+            Paddle::default(),
+            PaddleLength::default(),
+            config.paddle_motion(),
+            Collider { half_size: config.paddle_half_size },
+            config.paddle_surface(),
+            CollisionLayers::new(CollisionLayers::PADDLE, CollisionLayers::BALL),
+            Interpolated::at(pos),
+            #[cfg(feature = "net")]
+            Replicated,
+            paddle.side,
+        ));
+        if paddle.local {
+            entity.insert(LocalPaddle);
+        }
+    }
+
+    let ball_mesh = Mesh2dHandle(meshes.add(Rectangle { half_size: config.ball_half_size }));
+    let spin_marker_mesh = Mesh2dHandle(meshes.add(SPIN_MARKER_SHAPE));
+    let spin_marker_mat = materials.add(SPIN_MARKER_COLOR);
+    for _ in 0..layout.balls {
+        cmd.spawn((
+            ColorMesh2dBundle {
+                mesh: ball_mesh.clone(),
+                material: paddle_mat.clone(),
+                transform: Transform::default(),
+                ..default()
+            },
+            Ball::default(),
+            CollisionLayers::new(
+                CollisionLayers::BALL,
+                CollisionLayers::BALL | CollisionLayers::PADDLE | CollisionLayers::WALL | CollisionLayers::GOAL
+            ),
+            Interpolated::at(Vec2::ZERO),
+            #[cfg(feature = "net")]
+            Replicated,
+        )).with_children(|ball| {
+            ball.spawn((
+                ColorMesh2dBundle {
+                    mesh: spin_marker_mesh.clone(),
+                    material: spin_marker_mat.clone(),
+                    transform: Transform::from_xyz(SPIN_MARKER_RADIUS, 0f32, 1f32),
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+                SpinMarker::default(),
+            ));
+        });
+    }
+
+    for dir_y in [-1f32, 1f32] {
+        let wall = arena.wall(dir_y);
+        cmd.spawn((
+            TransformBundle::from_transform(Transform::from_translation(wall.center.extend(0f32))),
+            Collider { half_size: wall.half_size },
+            config.wall_surface(),
+            CollisionLayers::new(CollisionLayers::WALL, CollisionLayers::BALL),
+            Wall,
+        ));
+    }
+
+    for side in [Side::Left, Side::Right] {
+        let goal = arena.goal(side.dir());
+        cmd.spawn((
+            TransformBundle::from_transform(Transform::from_translation(goal.center.extend(0f32))),
+            Collider { half_size: goal.half_size },
+            CollisionLayers::new(CollisionLayers::GOAL, CollisionLayers::BALL),
+            Trigger,
+            Goal { scorer: side.opposite() },
+        ));
+    }
+
+    const FONT_SIZE: f32 = 32f32;
+    let text_style = TextStyle {
+        font_size: FONT_SIZE,
+        ..default()
+    };
+    let name_style = TextStyle {
+        font_size: FONT_SIZE/2f32,
+        ..default()
+    };
+    if layout.hud {
+        for side in [Side::Left, Side::Right] {
+            let x = side.dir() * TEXT_OFFSET_X;
+            cmd.spawn((
+                Text2dBundle {
+                    text: Text::from_section("0", text_style.clone()),
+                    transform: Transform::from_xyz(x, arena.half_size.y - FONT_SIZE, 0f32),
+                    ..default()
+                },
+                TopAnchored(FONT_SIZE),
+                ScoreText,
+                side,
+            ));
+            cmd.spawn((
+                Text2dBundle {
+                    text: Text::from_section(if side == Side::Left { "" } else { "CPU" }, name_style.clone()),
+                    transform: Transform::from_xyz(x, arena.half_size.y - FONT_SIZE/2f32, 0f32),
+                    ..default()
+                },
+                TopAnchored(FONT_SIZE/2f32),
+                ProfileName,
+                side,
+            ));
+        }
+    }
+
+    // Touch screens have no key to serve with.
+    if cfg!(target_os = "android") {
+        cmd.spawn((
+            ButtonBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(32f32),
+                    left: Val::Percent(50f32),
+                    width: Val::Px(128f32),
+                    height: Val::Px(48f32),
+                    margin: UiRect::left(Val::Px(-64f32)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::rgba(1f32, 1f32, 1f32, 0.2f32).into(),
+                ..default()
+            },
+            ServeButton,
+        )).with_children(|button| {
+            button.spawn(TextBundle::from_section("Serve", TextStyle {
+                font_size: 24f32,
+                ..default()
+            }));
+        });
+    }
+
+    cmd.spawn((
+        Text2dBundle {
+            text: Text::from_section("", TextStyle {
+                font_size: FONT_SIZE/2f32,
+                ..default()
+            }),
+            transform: Transform::from_xyz(0f32, 0f32, 1f32),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        StatsScreen,
+    ));
+
+    // Android has the serve button instead.
+    if !cfg!(target_os = "android") {
+        cmd.spawn((
+            Text2dBundle {
+                text: Text::from_section("", TextStyle {
+                    font_size: FONT_SIZE/2f32,
+                    ..default()
+                }),
+                transform: Transform::from_xyz(0f32, -arena.half_size.y / 2f32, 1f32),
+                ..default()
+            },
+            ServePrompt,
+            Prompt::Serve,
+        ));
+    }
+}
+
+fn set_window_icon(windows: Option<NonSend<WinitWindows>>) {
+    let Some(windows) = windows else {
+        return;
+    };
+    let icon = image::load_from_memory(WINDOW_ICON)
+        .map_err(|err| err.to_string())
+        .and_then(|image| {
+            let image = image.into_rgba8();
+            let (width, height) = image.dimensions();
+            winit::window::Icon::from_rgba(image.into_raw(), width, height).map_err(|err| err.to_string())
+        });
+    match icon {
+        Ok(icon) => {
+            for window in windows.windows.values() {
+                window.set_window_icon(Some(icon.clone()));
+            }
+        },
+        Err(err) => warn!("Failed to load window icon: {}", err),
+    }
+}
+
+fn update_cursor(
+    state: Res<State<GameState>>,
+    settings: Res<Settings>,
+    pause: Res<State<PauseState>>,
+    time: Res<Time<Virtual>>,
+    stats_screens: Query<&Visibility, With<StatsScreen>>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let on_stats_screen = stats_screens.iter().any(|visibility| *visibility == Visibility::Visible);
+    let visible = !settings.video.hide_cursor
+        || *state.get() != GameState::Started
+        || on_stats_screen
+        || *pause.get() == PauseState::Paused
+        || time.is_paused();
+    for mut window in windows.iter_mut() {
+        if window.cursor.visible != visible {
+            window.cursor.visible = visible;
+        }
+    }
+}
+
+fn pre_serve(
+    paddles: Query<(&Paddle, &Side)>,
+    serve: Res<ServeRequested>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if serve.0 || paddles.iter().any(|(paddle, side)| *side == Side::Left && paddle.dir != 0) {
+        next_state.set(GameState::Started);
+    }
+}
+
+fn on_round_started(
+    mut balls: Query<&mut Ball>,
+    mut rng: ResMut<GameRng>,
+    mut enemy_aim: ResMut<EnemyAim>,
+    mut serve_dir: ResMut<ServeDir>,
+    mut score: ResMut<Score>,
+    mut rally: ResMut<Rally>,
+    mut clock: ResMut<MatchClock>,
+    replay: Res<Replay>,
+    config: Res<GameConfig>,
+    rules: Res<MatchRules>,
+){
+    if rules.won(score.player, score.enemy) || rules.won(score.enemy, score.player) {
+        *score = Score::default();
+        clock.elapsed = 0f32;
+    }
+    // A ball resumed from a save is already in play, with its rally and the RNG where they were left.
+    let resumed = balls.iter().any(|ball| ball.vel != Vec2::ZERO);
+    if !resumed {
+        rally.hits = 0;
+        let aim_error = replay.data().difficulty.aim_error(ENEMY_AIM_ERROR);
+        enemy_aim.0 = rng.rng.gen_range(-aim_error..=aim_error);
+    }
+    if serve_dir.0 == 0f32 {
+        serve_dir.0 = if rng.rng.gen_bool(0.5) { 1f32 } else { -1f32 };
+    }
+    if balls.is_empty() {
+        warn!("Serving without a ball; nobody can score until one is spawned");
+    }
+    for mut ball in balls.iter_mut() {
+        if ball.vel != Vec2::ZERO {
+            continue;
+        }
+        let angle = rng.rng.gen_range(-config.serve_max_angle..=config.serve_max_angle);
+        ball.speed = config.ball_start_speed;
+        ball.vel = physics::from_angle(angle).rotate(Vec2::new(serve_dir.0 * ball.speed, 0f32));
+    }
+}
+
+fn player_input(
+    keyboard_input_res: Res<ButtonInput<KeyCode>>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    touches: Res<Touches>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    profiles: Res<Profiles>,
+    #[cfg(feature = "net")] chat: Res<chat_box::ChatBox>,
+    #[cfg(feature = "net")] browser: Option<Res<lobby_browser::LobbyBrowser>>,
+    builder: Option<Res<rulesets::RulesetBuilder>>,
+    mut input: ResMut<PlayerInput>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    paddles: Query<(&Transform, &Side), With<Paddle>>,
+) {
+    // Touching or clicking-and-holding pulls the paddle toward the pointer.
+    let pointer = touches.first_pressed_position().or_else(|| {
+        windows.get_single().ok()
+            .filter(|_| mouse_input.pressed(MouseButton::Left))
+            .and_then(|window| window.cursor_position())
+    });
+    let target = pointer.zip(cameras.get_single().ok())
+        .and_then(|(pointer, (camera, camera_trans))| camera.viewport_to_world_2d(camera_trans, pointer));
+    let paddle = paddles.iter().find(|(_, side)| **side == Side::Left);
+    if let (Some(target), Some((paddle_trans, _))) = (target, paddle) {
+        let diff = target.y - paddle_trans.translation.y;
+        input.dir = if diff.abs() < POINTER_DEADZONE { 0 } else { diff.signum() as i32 };
+        return;
+    }
+
+    let keyboard_input: &ButtonInput<KeyCode> = &keyboard_input_res;
+    let bindings = &profiles.active().bindings;
+    // Keys type into the chat box, the lobby browser's filter or the ruleset's name while it's open.
+    let typing = builder.is_some();
+    #[cfg(feature = "net")]
+    let typing = typing || chat.is_open() || browser.is_some();
+    input.dir = if typing { 0 }
+        else if keyboard_input.pressed(bindings.down) { -1 }
+        else if keyboard_input.pressed(bindings.up) { 1 }
+        else { 0 };
+
+    for gamepad in gamepads.iter() {
+        let button = |button_type| GamepadButton::new(gamepad, button_type);
+        if gamepad_buttons.just_pressed(button(GamepadButtonType::South)) {
+            input.serve = true;
+        }
+        if input.dir != 0 {
+            continue;
+        }
+        let stick = gamepad_axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY)).unwrap_or_default();
+        input.dir = if gamepad_buttons.pressed(button(GamepadButtonType::DPadDown)) || stick < -STICK_DEADZONE { -1 }
+            else if gamepad_buttons.pressed(button(GamepadButtonType::DPadUp)) || stick > STICK_DEADZONE { 1 }
+            else { 0 };
+    }
+}
+
+fn apply_player_input(
+    mut input: ResMut<PlayerInput>,
+    mut replay: ResMut<Replay>,
+    mut serve: ResMut<ServeRequested>,
+    mut paddle: Query<&mut Paddle, With<LocalPaddle>>
+) {
+    let tick = replay.next_input(TickInput { dir: input.dir as i8, serve: input.serve });
+    input.serve = false;
+    serve.0 = tick.serve;
+    for mut paddle in paddle.iter_mut() {
+        paddle.dir = tick.dir as i32;
+    }
+}
+
+fn update_serve_prompt(
+    state: Res<State<GameState>>,
+    tutorial: Option<Res<tutorial::Tutorial>>,
+    mut prompts: Query<&mut Visibility, With<ServePrompt>>,
+) {
+    let serving = *state.get() == GameState::Serving && tutorial::serve_allowed(tutorial);
+    let visibility = if serving { Visibility::Inherited } else { Visibility::Hidden };
+    for mut prompt in prompts.iter_mut() {
+        if *prompt != visibility {
+            *prompt = visibility;
+        }
+    }
+}
+
+fn serve_button(
+    state: Res<State<GameState>>,
+    mut input: ResMut<PlayerInput>,
+    mut buttons: Query<(Ref<Interaction>, &mut Visibility), With<ServeButton>>,
+) {
+    for (interaction, mut visibility) in buttons.iter_mut() {
+        *visibility = if *state.get() == GameState::Serving { Visibility::Inherited } else { Visibility::Hidden };
+        if interaction.is_changed() && *interaction == Interaction::Pressed {
+            input.serve = true;
+        }
+    }
+}
+
+/// Pauses when the app is suspended, unfocused or minimized, so the enemy can't score while nobody is looking.
+/// The match stays paused behind the pause menu until the player resumes it.
+fn handle_app_lifecycle(
+    mut lifetimes: EventReader<ApplicationLifetime>,
+    mut focused: EventReader<WindowFocused>,
+    mut occluded: EventReader<WindowOccluded>,
+    mut next_pause: ResMut<NextState<PauseState>>,
+) {
+    let suspended = lifetimes.read().any(|lifetime| *lifetime == ApplicationLifetime::Suspended);
+    let unfocused = focused.read().any(|event| !event.focused);
+    let minimized = occluded.read().any(|event| event.occluded);
+    if suspended || unfocused || minimized {
+        next_pause.set(PauseState::Paused);
+    }
+}
+
+fn resume_from_pause(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    touches: Res<Touches>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    mut next_pause: ResMut<NextState<PauseState>>,
+) {
+    // Clicks don't resume, since the click that refocuses the window would dismiss the menu straight away.
+    let resume = keyboard_input.just_pressed(KeyCode::Escape)
+        || touches.any_just_pressed()
+        || gamepad_buttons.get_just_pressed().any(|button| button.button_type == GamepadButtonType::Start);
+    if resume {
+        next_pause.set(PauseState::Running);
+    }
+}
+
+fn on_pause(mut cmd: Commands, mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+    cmd.spawn((
+        Text2dBundle {
+            text: Text::from_section("", TextStyle {
+                font_size: PAUSE_MENU_FONT_SIZE,
+                ..default()
+            })
+            .with_justify(JustifyText::Center),
+            transform: Transform::from_xyz(0f32, 0f32, 2f32),
+            ..default()
+        },
+        PauseMenu,
+        Prompt::Resume,
+        StateScoped(PauseState::Paused),
+    ));
+}
+
+fn on_resume(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+fn autopilot_input(
+    mut input: ResMut<PlayerInput>,
+    paddles: Query<(&Transform, &Side), With<Paddle>>,
+    balls: Query<(&Transform, &Ball)>,
+) {
+    let Some((paddle_trans, _)) = paddles.iter().find(|(_, side)| **side == Side::Left) else {
+        return;
+    };
+    let Some((ball_trans, _)) = nearest_ball(&balls, paddle_trans.translation) else {
+        return;
+    };
+    input.dir = (ball_trans.translation.y - paddle_trans.translation.y).signum() as i32;
+}
+
+fn count_simulated_matches(
+    mut match_over: EventReader<MatchOver>,
+    mut sim: ResMut<HeadlessSim>,
+    mut exit: EventWriter<AppExit>,
+    score: Res<Score>,
+    clock: Res<MatchClock>,
+) {
+    for over in match_over.read() {
+        sim.remaining = sim.remaining.saturating_sub(1);
+        if over.winner == Side::Left {
+            sim.player_wins += 1;
+        }
+        info!("{:?} won {}-{} in {:.1}s", over.winner, score.player, score.enemy, clock.elapsed);
+        if sim.remaining == 0 {
+            info!("Simulation finished, player won {} matches", sim.player_wins);
+            exit.send(AppExit);
+        }
+    }
+}
+
+fn enemy_ai(
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    arena: Res<Arena>,
+    rng: Res<GameRng>,
+    clock: Res<MatchClock>,
+    enemy_aim: Res<EnemyAim>,
+    rules: Res<rules::MatchRules>,
+    mut paddles: Query<
+        (&mut Paddle, &PaddleMotion, &Transform, &Side),
+        (Without<chat::NetworkController>, Without<LocalPaddle>),
+    >,
+    balls: Query<(&Transform, &Ball)>
+) {
+    let wind = wind::gust_at(rng.seed, clock.elapsed).push * config.ball_wind;
+    for (mut paddle, motion, paddle_trans, _) in paddles.iter_mut().filter(|(.., side)| **side == Side::Right) {
+        let paddle_pos = paddle_trans.translation;
+        let Some((ball_trans, ball)) = nearest_ball(&balls, paddle_pos) else {
+            paddle.dir = 0;
+            continue;
+        };
+        // Aim where the ball will cross the paddle's face, curve and bounces and all; track it directly while it's
+        // heading away.
+        let body = physics::Body { pos: ball_trans.translation.truncate(), vel: ball.vel };
+        let face_x = paddle_pos.x - config.paddle_half_size.x - config.ball_half_size.x;
+        let target_y = physics::predict_intercept(
+            body,
+            ball.spin,
+            wind - config.ball_gravity,
+            face_x,
+            MAGNUS_COEFFICIENT,
+            SPIN_DECAY,
+            time.delta_seconds(),
+        )
+        .map(|y| physics::fold_between_walls(y, arena.half_size.y - config.ball_half_size.y))
+        .unwrap_or(ball_trans.translation.y);
+        let offset = target_y + enemy_aim.0 - paddle_pos.y;
+        // On ice, chasing the ball head-on only slides past it, so the AI starts braking early.
+        paddle.dir = if rules.mutators.ice {
+            physics::steer_to(offset, paddle.vel, motion.accel)
+        }
+        else {
+            offset.signum() as i32
+        };
+    }
+}
+
+fn move_paddle(
+    mut paddle: Query<(&mut Paddle, &PaddleMotion, &Collider, &mut Transform)>,
+    arena: Res<Arena>,
+    time: Res<Time>,
+) {
+    for (mut paddle, motion, collider, mut transform) in paddle.iter_mut() {
+        let dir = paddle.dir as f32;
+        paddle.vel = if motion.instant {
+            dir * motion.max_speed
+        }
+        else {
+            physics::paddle_velocity(
+                paddle.vel,
+                dir,
+                motion.accel,
+                motion.max_speed,
+                motion.stop_friction,
+                time.delta_seconds(),
+            )
+        };
+
+        let max_y = arena.half_size.y - collider.half_size.y;
+        transform.translation.y += paddle.vel * time.delta_seconds();
+        if transform.translation.y.abs() >= max_y {
+            paddle.vel = 0f32;
+        }
+        transform.translation.y = clamp(transform.translation.y, -max_y, max_y);
+    }
+}
+
+fn move_ball(
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    rng: Res<GameRng>,
+    clock: Res<MatchClock>,
+    mut paddle_hits: EventWriter<BallHitPaddle>,
+    mut wall_hits: EventWriter<BallHitWall>,
+    mut goals: EventWriter<GoalScored>,
+    mut balls: Query<(Entity, &mut Ball, &mut Transform, &CollisionLayers), Without<Collider>>,
+    colliders: Query<(
+        Entity,
+        &Collider,
+        &Transform,
+        &CollisionLayers,
+        Option<&Surface>,
+        Option<&Paddle>,
+        Option<&Trigger>,
+        Option<&Goal>,
+    )>,
+) {
+    let wind = wind::gust_at(rng.seed, clock.elapsed).push * config.ball_wind;
+    let tuning = physics::BallTuning {
+        half_size: config.ball_half_size,
+        acceleration: config.ball_acceleration,
+        max_speed: config.ball_max_speed,
+        lift: wind - config.ball_gravity,
+        magnus_coefficient: MAGNUS_COEFFICIENT,
+        spin_decay: SPIN_DECAY,
+        spin_per_hit: SPIN_PER_HIT,
+        max_step: config.max_ball_step(),
+        max_angle: config.collision_max_angle,
+        min_horizontal_ratio: config.min_horizontal_speed_ratio,
+        epsilon: CONTACT_EPSILON,
+    };
+    let mut obstacles = Vec::new();
+    let mut targets = Vec::new();
+    let mut events = Vec::new();
+    for (ball_entity, mut ball, mut transform, ball_layers) in balls.iter_mut() {
+        obstacles.clear();
+        targets.clear();
+        for (entity, collider, collider_trans, layers, surface, paddle, trigger, goal) in colliders.iter() {
+            if !ball_layers.interacts(layers) {
+                continue;
+            }
+            let kind = match (trigger, paddle) {
+                (Some(_), _) => physics::ObstacleKind::Trigger,
+                (None, Some(paddle)) => physics::ObstacleKind::Paddle { dir: paddle.dir as f32 },
+                (None, None) => physics::ObstacleKind::Wall,
+            };
+            obstacles.push(physics::Obstacle {
+                aabb: Aabb::new(collider_trans.translation.truncate(), collider.half_size),
+                kind,
+                restitution: surface.map_or(1f32, |surface| surface.restitution),
+                friction: surface.map_or(0f32, |surface| surface.friction),
+            });
+            targets.push((entity, goal));
+        }
+
+        let mut body = physics::BallBody {
+            pos: transform.translation.truncate(),
+            vel: ball.vel,
+            speed: ball.speed,
+            spin: ball.spin,
+        };
+        events.clear();
+        physics::step_ball(&mut body, &obstacles, &tuning, time.delta_seconds(), &mut events);
+        ball.vel = body.vel;
+        ball.speed = body.speed;
+        ball.spin = body.spin;
+        transform.translation.x = body.pos.x;
+        transform.translation.y = body.pos.y;
+
+        for event in &events {
+            match *event {
+                physics::BallEvent::Entered(i) => {
+                    if let (_, Some(goal)) = targets[i] {
+                        goals.send(GoalScored { ball: ball_entity, scorer: goal.scorer });
+                    }
+                },
+                physics::BallEvent::Bounced(i) => {
+                    let (entity, _) = targets[i];
+                    if matches!(obstacles[i].kind, physics::ObstacleKind::Paddle { .. }) {
+                        ball.last_hit = Some(entity);
+                        paddle_hits.send(BallHitPaddle { ball: ball_entity, paddle: entity });
+                    }
+                    else {
+                        wall_hits.send(BallHitWall { ball: ball_entity });
+                    }
+                },
+            }
+        }
+    }
+}
+
+fn collide_balls(
+    config: Res<GameConfig>,
+    mut balls: Query<(&mut Ball, &mut Transform, &CollisionLayers)>,
+) {
+    let (mut bodies, layers): (Vec<_>, Vec<_>) = balls.iter()
+        .map(|(ball, transform, layers)| (physics::Body { pos: transform.translation.truncate(), vel: ball.vel }, *layers))
+        .unzip();
+    let before = bodies.clone();
+    physics::collide_all(&mut bodies, config.ball_half_size, |a, b| layers[a].interacts(&layers[b]));
+
+    for (((mut ball, mut transform, _), body), before) in balls.iter_mut().zip(&bodies).zip(&before) {
+        if body == before {
+            continue;
+        }
+        ball.vel = body.vel;
+        transform.translation = body.pos.extend(transform.translation.z);
+    }
+}
+
+fn score_goal(
+    mut goals: EventReader<GoalScored>,
+    mut score: ResMut<Score>,
+    mut serve_dir: ResMut<ServeDir>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut match_over: EventWriter<MatchOver>,
+    rules: Res<MatchRules>,
+) {
+    for goal in goals.read() {
+        *score.of_mut(goal.scorer) += 1;
+        // The side that conceded is served at.
+        serve_dir.0 = goal.scorer.opposite().dir();
+        if rules.won(score.of(goal.scorer), score.of(goal.scorer.opposite())) {
+            match_over.send(MatchOver { winner: goal.scorer });
+        }
+        next_state.set(GameState::RoundOver);
+    }
+}
+
+fn track_rally(
+    mut paddle_hits: EventReader<BallHitPaddle>,
+    mut goals: EventReader<GoalScored>,
+    mut rally: ResMut<Rally>,
+    mut records: ResMut<Records>,
+    persist: Res<Persist>,
+) {
+    rally.hits += paddle_hits.read().count() as u32;
+    if goals.read().count() > 0 && persist.0 && records.record_rally(rally.hits) {
+        if let Err(err) = records.save() {
+            warn!("Failed to save records: {}", err);
+        }
+    }
+}
+
+fn tick_match_clock(
+    time: Res<Time>,
+    mut clock: ResMut<MatchClock>,
+) {
+    clock.elapsed += time.delta_seconds();
+}
+
+fn finish_match(
+    score: Res<Score>,
+    clock: Res<MatchClock>,
+    mut records: ResMut<Records>,
+    mut stats: ResMut<LifetimeStats>,
+    profiles: Res<Profiles>,
+    replay: Res<Replay>,
+    persist: Res<Persist>,
+    controlled: Query<(), With<chat::NetworkController>>,
+    mut match_over: EventReader<MatchOver>,
+) {
+    for over in match_over.read() {
+        if !persist.0 {
+            continue;
+        }
+        // A paddle steered by Twitch chat can't be played back.
+        if controlled.is_empty() {
+            if let Err(err) = replay.save() {
+                warn!("Failed to save replay: {}", err);
+            }
+        }
+
+        stats.record_match(
+            score.player as u32,
+            score.enemy as u32,
+            over.winner == Side::Left,
+            clock.elapsed,
+        );
+        if let Err(err) = stats.save(profiles.active()) {
+            warn!("Failed to save stats: {}", err);
+        }
+
+        if over.winner == Side::Left {
+            records.record_win(LeaderboardEntry {
+                points_for: score.player,
+                points_against: score.enemy,
+                duration_secs: clock.elapsed,
+            });
+            if let Err(err) = records.save() {
+                warn!("Failed to save records: {}", err);
+            }
+        }
+    }
+}
+
+/// Replays and rollback need every tick to run its systems in the same order, so two fixed-step systems that
+/// touch the same data without an order between them stop the app from starting.
+fn deny_ambiguities(schedule: &mut Schedule) {
+    schedule.set_build_settings(ScheduleBuildSettings {
+        ambiguity_detection: LogLevel::Error,
+        ..default()
+    });
+}
+
+fn restore_interpolated(
+    mut query: Query<(&mut Interpolated, &mut Transform)>,
+) {
+    for (mut interp, mut transform) in query.iter_mut() {
+        interp.previous = interp.current;
+        transform.translation.x = interp.current.x;
+        transform.translation.y = interp.current.y;
+    }
+}
+
+fn record_interpolated(
+    mut query: Query<(&mut Interpolated, &Transform)>,
+) {
+    for (mut interp, transform) in query.iter_mut() {
+        interp.current = transform.translation.truncate();
+    }
+}
+
+fn interpolate_transforms(
+    fixed_time: Res<Time<Fixed>>,
+    mut query: Query<(&Interpolated, &mut Transform)>,
+) {
+    let alpha = fixed_time.overstep_fraction();
+    for (interp, mut transform) in query.iter_mut() {
+        let pos = interp.previous.lerp(interp.current, alpha);
+        transform.translation.x = pos.x;
+        transform.translation.y = pos.y;
+    }
+}
+
+fn update_spin_markers(
+    time: Res<Time>,
+    balls: Query<&Ball>,
+    mut markers: Query<(&Parent, &mut SpinMarker, &mut Transform, &mut Visibility)>,
+) {
+    for (parent, mut marker, mut transform, mut visibility) in markers.iter_mut() {
+        let Ok(ball) = balls.get(parent.get()) else {
+            continue;
+        };
+        if ball.spin.abs() < SPIN_MARKER_MIN_SPIN {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        }
+        visibility.set_if_neq(Visibility::Inherited);
+        marker.angle += ball.spin * SPIN_MARKER_RATE * time.delta_seconds();
+        let offset = Vec2::from_angle(marker.angle) * SPIN_MARKER_RADIUS;
+        transform.translation = offset.extend(transform.translation.z);
+    }
+}
+
+/// Rewrites HUD text only when what it shows has changed, or when the text is new.
+fn update_ui(
+    score: Res<Score>,
+    profiles: Res<Profiles>,
+    mut scores: Query<(&mut Text, &Side, Ref<ScoreText>)>,
+    mut names: Query<(&mut Text, &Side, Ref<ProfileName>), Without<ScoreText>>,
+){
+    for (mut text, side, marker) in scores.iter_mut() {
+        if score.is_changed() || marker.is_added() {
+            text.sections[0].value = score.of(*side).to_string();
+        }
+    }
+    for (mut text, side, marker) in names.iter_mut() {
+        if *side == Side::Left && (profiles.is_changed() || marker.is_added()) {
+            text.sections[0].value = profiles.active().name.clone();
+        }
+    }
+}
+
+fn cycle_profile(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    state: Res<State<GameState>>,
+    score: Res<Score>,
+    mut profiles: ResMut<Profiles>,
+    mut stats: ResMut<LifetimeStats>,
+    mut rating: ResMut<rating::Rating>,
+) {
+    // Only between matches, so a match's stats all land on one profile.
+    let between_matches = *state.get() == GameState::Serving && score.player == 0 && score.enemy == 0;
+    if !keyboard_input.just_pressed(KeyCode::F2) || !between_matches || profiles.profiles.len() < 2 {
+        return;
+    }
+    profiles.cycle();
+    *stats = LifetimeStats::load(profiles.active());
+    *rating = rating::Rating::load(profiles.active());
+    info!("Switched to profile {}", profiles.active().name);
+}
+
+fn take_screenshot(
+    mut cmd: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    arena: Res<Arena>,
+    screenshots: Option<ResMut<ScreenshotManager>>,
+    windows: Query<Entity, With<PrimaryWindow>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F12) {
+        return;
+    }
+    let (Some(mut screenshots), Ok(window)) = (screenshots, windows.get_single()) else {
+        return;
+    };
+    save_screenshot(&mut cmd, &arena, &mut screenshots, window, "screenshot");
+}
+
+/// Saves the next frame of `window` to the screenshots folder under a fresh name starting with `prefix`.
+fn save_screenshot(cmd: &mut Commands, arena: &Arena, screenshots: &mut ScreenshotManager, window: Entity, prefix: &str) {
+    let Some(dir) = storage::data_path("screenshots") else {
+        return;
+    };
+    // In the browser the screenshot is offered as a download instead.
+    if !cfg!(target_arch = "wasm32") {
+        if let Err(err) = std::fs::create_dir_all(&dir) {
+            warn!("Failed to create {}: {}", dir.display(), err);
+            return;
+        }
+    }
+    let stamp = storage::timestamp();
+    let name = (0..)
+        .map(|i| if i == 0 { format!("{}-{}.png", prefix, stamp) } else { format!("{}-{}-{}.png", prefix, stamp, i) })
+        .find(|name| !dir.join(name).exists())
+        .unwrap();
+    if let Err(err) = screenshots.save_screenshot_to_disk(window, dir.join(&name)) {
+        warn!("Failed to take screenshot: {}", err);
+        return;
+    }
+    spawn_toast(cmd, arena, format!("Saved {}", name));
+}
+
+fn resize_arena(
+    mut resized: EventReader<WindowResized>,
+    settings: Res<Settings>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut arena: ResMut<Arena>,
+    mut projections: Query<&mut OrthographicProjection>,
+    mut bars: Query<(&LetterboxBar, &mut Transform, &mut Visibility)>,
+    mut query: Query<(
+        &mut Transform,
+        Option<&mut Collider>,
+        Option<&mut Interpolated>,
+        Option<&TopAnchored>,
+        Has<Wall>,
+        Has<Goal>,
+        Has<Paddle>,
+    ), Without<LetterboxBar>>,
+) {
+    if resized.read().count() == 0 && !settings.is_changed() {
+        return;
+    }
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+
+    let letterbox = settings.video.presentation == Presentation::Letterbox;
+    let fitted = Arena::fit(window, &settings.video);
+    for mut projection in projections.iter_mut() {
+        projection.scaling_mode = if letterbox {
+            let size = fitted.half_size * 2f32;
+            ScalingMode::AutoMin { min_width: size.x, min_height: size.y }
+        }
+        else {
+            ScalingMode::WindowSize(1f32)
+        };
+    }
+    for (bar, mut transform, mut visibility) in bars.iter_mut() {
+        *visibility = if letterbox { Visibility::Visible } else { Visibility::Hidden };
+        let pos = bar.dir * (fitted.half_size + LETTERBOX_BAR_SIZE/2f32);
+        transform.translation = pos.extend(transform.translation.z);
+    }
+
+    if fitted.half_size == arena.half_size {
+        return;
+    }
+    *arena = fitted;
+
+    for (mut transform, collider, interp, anchor, wall, goal, paddle) in query.iter_mut() {
+        let side = transform.translation.truncate().signum();
+        if let Some(mut collider) = collider {
+            let bounds = if wall {
+                arena.wall(side.y)
+            }
+            else if goal {
+                arena.goal(side.x)
+            }
+            else if paddle {
+                let x = arena.paddle_x(side.x, collider.half_size);
+                Aabb::new(Vec2::new(x, transform.translation.y), collider.half_size)
+            }
+            else {
+                continue;
+            };
+            collider.half_size = bounds.half_size;
+            transform.translation.x = bounds.center.x;
+            transform.translation.y = bounds.center.y;
+            if let Some(mut interp) = interp {
+                interp.previous.x = bounds.center.x;
+                interp.current.x = bounds.center.x;
+            }
+        }
+        if let Some(anchor) = anchor {
+            transform.translation.y = arena.half_size.y - anchor.0;
+        }
+    }
+}
+
+fn spawn_toast(cmd: &mut Commands, arena: &Arena, message: String) {
+    cmd.spawn((
+        Text2dBundle {
+            text: Text::from_section(message, TextStyle {
+                font_size: 16f32,
+                ..default()
+            }),
+            transform: Transform::from_xyz(0f32, -arena.half_size.y + 16f32, 2f32),
+            ..default()
+        },
+        Toast(Timer::from_seconds(TOAST_DURATION, TimerMode::Once)),
+    ));
+}
+
+fn update_toasts(
+    mut cmd: Commands,
+    time: Res<Time<Real>>,
+    mut toasts: Query<(Entity, &mut Toast)>,
+) {
+    for (entity, mut toast) in toasts.iter_mut() {
+        if toast.0.tick(time.delta()).finished() {
+            cmd.entity(entity).despawn();
+        }
+    }
+}
+
+fn toggle_stats_screen(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    stats: Res<LifetimeStats>,
+    rating: Res<rating::Rating>,
+    records: Res<Records>,
+    settings: Res<Settings>,
+    online: Res<online::OnlineLeaderboard>,
+    mut screens: Query<(&mut Text, &mut Visibility), With<StatsScreen>>,
+) {
+    for (mut text, mut visibility) in screens.iter_mut() {
+        if keyboard_input.just_pressed(KeyCode::Tab) {
+            *visibility = match *visibility {
+                Visibility::Hidden => Visibility::Visible,
+                _ => Visibility::Hidden,
+            };
+        }
+        let changed = stats.is_changed() || rating.is_changed() || records.is_changed() || online.is_changed();
+        if changed || keyboard_input.just_pressed(KeyCode::Tab) {
+            let mut summary = format!("{}\nLongest rally: {}\n{}", stats.summary(), records.longest_rally, rating.summary());
+            if settings.online_leaderboard {
+                summary = format!("{}\n\n{}", summary, online.summary());
+            }
+            text.sections[0].value = summary;
+        }
+    }
+}
+
+fn on_start_serving(
+    mut paddles: Query<(&mut Paddle, &mut Transform, &mut Interpolated), Without<Ball>>,
+    mut balls: Query<(&mut Ball, &mut Transform, &mut Interpolated), Without<Paddle>>,
+    config: Res<GameConfig>,
+){
+    for (mut ball, mut ball_trans, mut ball_interp) in balls.iter_mut() {
+        ball.vel = Vec2::default();
+        ball.speed = config.ball_start_speed;
+        ball.spin = 0f32;
+        ball.last_hit = None;
+        ball_trans.translation = Vec3::default();
+        *ball_interp = Interpolated::at(Vec2::ZERO);
+    }
+
+    for (mut paddle, mut trans, mut interp) in paddles.iter_mut() {
+        paddle.dir = 0;
+        paddle.vel = 0f32;
+        trans.translation.y = 0f32;
+        *interp = Interpolated::at(trans.translation.truncate());
+    }
+}
+
+/// Starts a fresh match from `seed`, the way one starts from launch, whatever state the current one is in.
+fn restart_match(cmd: &mut Commands, seed: u64) {
+    cmd.insert_resource(GameRng::from_seed(seed));
+    cmd.insert_resource(Score::default());
+    cmd.insert_resource(ServeDir::default());
+    cmd.insert_resource(Rally::default());
+    cmd.insert_resource(MatchClock::default());
+    cmd.insert_resource(ServeRequested::default());
+    cmd.add(|world: &mut World| {
+        world.insert_resource(State::new(GameState::Serving));
+        world.insert_resource(NextState::<GameState>::default());
+        world.run_schedule(OnEnter(GameState::Serving));
+    });
+}
+
+fn on_round_over(
+    mut paddles: Query<(&mut Paddle, &Side)>,
+    mut timer: ResMut<NextRoundTimer>,
+    config: Res<GameConfig>,
+){
+    for (mut paddle, _) in paddles.iter_mut().filter(|(_, side)| **side == Side::Right) {
+        paddle.dir = 0;
+    }
+
+    timer.0.set_duration(Duration::from_secs_f32(config.next_round_interval));
+    timer.0.reset();
+}
+
+fn round_over(
+    time: Res<Time>,
+    mut timer: ResMut<NextRoundTimer>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    timer.0.tick(time.delta());
+    if timer.0.finished() {
+        next_state.set(GameState::Serving);
+    }
+}
\ No newline at end of file
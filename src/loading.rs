@@ -0,0 +1,89 @@
+use bevy::{asset::RecursiveDependencyLoadState, prelude::*, sprite::Anchor};
+
+use crate::state_scoped::StateScoped;
+
+const BAR_SIZE: Vec2 = Vec2::new(160f32, 8f32);
+const FONT_SIZE: f32 = 16f32;
+
+/// Play holds off until every preloaded asset has loaded, so the first serve doesn't hitch while they stream in.
+#[derive(States, Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum LoadingState {
+    #[default]
+    Loading,
+    Done,
+}
+
+/// Handles the loading screen waits on. Add to it at startup.
+#[derive(Resource, Default)]
+pub struct Preload(Vec<UntypedHandle>);
+
+impl Preload {
+    pub fn add(&mut self, handle: impl Into<UntypedHandle>) {
+        self.0.push(handle.into());
+    }
+}
+
+#[derive(Component)]
+pub struct LoadingBar;
+
+pub fn spawn_loading_screen(mut cmd: Commands) {
+    let scoped = StateScoped(LoadingState::Loading);
+    cmd.spawn((
+        Text2dBundle {
+            text: Text::from_section("Loading...", TextStyle {
+                font_size: FONT_SIZE,
+                ..default()
+            }),
+            transform: Transform::from_xyz(0f32, BAR_SIZE.y + FONT_SIZE, 6f32),
+            ..default()
+        },
+        scoped.clone(),
+    ));
+    cmd.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::DARK_GRAY,
+                custom_size: Some(BAR_SIZE),
+                ..default()
+            },
+            transform: Transform::from_xyz(0f32, 0f32, 6f32),
+            ..default()
+        },
+        scoped.clone(),
+    ));
+    cmd.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(BAR_SIZE),
+                anchor: Anchor::CenterLeft,
+                ..default()
+            },
+            transform: Transform::from_xyz(-BAR_SIZE.x / 2f32, 0f32, 7f32).with_scale(Vec3::new(0f32, 1f32, 1f32)),
+            ..default()
+        },
+        LoadingBar,
+        scoped,
+    ));
+}
+
+/// Fills the bar with the share of preloaded assets that are done, failed ones included, and moves on once they all are.
+pub fn track_loading(
+    asset_server: Res<AssetServer>,
+    preload: Res<Preload>,
+    mut next_state: ResMut<NextState<LoadingState>>,
+    mut bars: Query<&mut Transform, With<LoadingBar>>,
+) {
+    let done = preload.0.iter()
+        .filter(|handle| matches!(
+            asset_server.get_recursive_dependency_load_state(handle.id()),
+            Some(RecursiveDependencyLoadState::Loaded | RecursiveDependencyLoadState::Failed)
+        ))
+        .count();
+    let progress = if preload.0.is_empty() { 1f32 } else { done as f32 / preload.0.len() as f32 };
+    for mut transform in bars.iter_mut() {
+        transform.scale.x = progress;
+    }
+    if done == preload.0.len() {
+        next_state.set(LoadingState::Done);
+    }
+}
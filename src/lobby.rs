@@ -0,0 +1,266 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chat::NetworkController,
+    chat_box::ChatBox,
+    config::GameConfig,
+    profiles::Profiles,
+    rules::{next_paddle_length, BallSize, MatchRules, MAX_POINTS_TO_WIN},
+    settings::Settings,
+    theme::{PaddleColor, SWATCHES},
+    Ball, LocalPaddle, PaddleMotion, ProfileName, Side,
+};
+
+const FONT_SIZE: f32 = 16f32;
+
+/// The lobby before a LAN match. The host owns it and sends a copy to the guest on every frame;
+/// `names`, `colors`, `lengths` and `ready` hold the host's entry first and the guest's second.
+#[derive(Resource, Clone, Serialize, Deserialize)]
+pub struct Lobby {
+    pub names: [String; 2],
+    pub colors: [usize; 2],
+    pub lengths: [f32; 2],
+    pub ready: [bool; 2],
+    pub host_left: bool,
+    pub rules: MatchRules,
+    /// With rollback, both ends restart the match from this seed.
+    pub seed: u64,
+    /// Set by the host once both are ready; everyone leaves the lobby on seeing it.
+    pub started: bool,
+}
+
+impl Lobby {
+    pub fn new(host_name: String, seed: u64) -> Self {
+        Lobby {
+            names: [host_name, "Waiting...".into()],
+            colors: [0, 1],
+            lengths: [1f32, 1f32],
+            ready: [false, false],
+            host_left: true,
+            rules: MatchRules::default(),
+            seed,
+            started: false,
+        }
+    }
+
+    /// Colors left to right.
+    fn side_colors(&self) -> [usize; 2] {
+        if self.host_left { self.colors } else { [self.colors[1], self.colors[0]] }
+    }
+
+    /// Paddle lengths left to right.
+    fn side_lengths(&self) -> [f32; 2] {
+        if self.host_left { self.lengths } else { [self.lengths[1], self.lengths[0]] }
+    }
+}
+
+/// The guest's own entry, which it keeps sending until the match starts.
+#[derive(Resource, Clone, Serialize, Deserialize)]
+pub struct LobbyChoice {
+    pub name: String,
+    pub color: usize,
+    pub length: f32,
+    pub ready: bool,
+}
+
+impl Default for LobbyChoice {
+    fn default() -> Self {
+        LobbyChoice { name: String::new(), color: 0, length: 1f32, ready: false }
+    }
+}
+
+#[derive(Component)]
+pub struct LobbyOverlay;
+
+pub fn spawn_lobby_overlay(mut cmd: Commands) {
+    cmd.spawn((
+        Text2dBundle {
+            text: Text::from_section("", TextStyle {
+                font_size: FONT_SIZE,
+                ..default()
+            })
+            .with_justify(JustifyText::Center),
+            transform: Transform::from_xyz(0f32, 0f32, 5f32),
+            ..default()
+        },
+        LobbyOverlay,
+    ));
+}
+
+/// The host changes the rules and sides; both pick a color with C and a paddle length with L, and ready up with R. The host starts with Space.
+pub fn edit_lobby(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    chat: Res<ChatBox>,
+    profiles: Res<Profiles>,
+    lobby: Option<ResMut<Lobby>>,
+    choice: Option<ResMut<LobbyChoice>>,
+) {
+    if chat.is_open() {
+        return;
+    }
+    let pressed = |key| keyboard_input.just_pressed(key);
+    if let Some(mut choice) = choice {
+        if profiles.is_changed() {
+            choice.name = profiles.active().name.clone();
+        }
+        if pressed(KeyCode::KeyC) {
+            choice.color = (choice.color + 1) % SWATCHES;
+        }
+        if pressed(KeyCode::KeyL) {
+            choice.length = next_paddle_length(choice.length);
+        }
+        if pressed(KeyCode::KeyR) {
+            choice.ready = !choice.ready;
+        }
+        return;
+    }
+    let Some(mut lobby) = lobby else {
+        return;
+    };
+    if profiles.is_changed() {
+        lobby.names[0] = profiles.active().name.clone();
+    }
+    if pressed(KeyCode::KeyC) {
+        lobby.colors[0] = (lobby.colors[0] + 1) % SWATCHES;
+    }
+    if pressed(KeyCode::KeyL) {
+        lobby.lengths[0] = next_paddle_length(lobby.lengths[0]);
+    }
+    if pressed(KeyCode::KeyR) {
+        lobby.ready[0] = !lobby.ready[0];
+    }
+    // Changing the rules takes back both players' ready.
+    let before = (lobby.host_left, lobby.rules.clone());
+    if pressed(KeyCode::KeyX) {
+        lobby.host_left = !lobby.host_left;
+    }
+    if pressed(KeyCode::BracketRight) {
+        lobby.rules.points_to_win = (lobby.rules.points_to_win + 1).min(MAX_POINTS_TO_WIN);
+    }
+    if pressed(KeyCode::BracketLeft) {
+        lobby.rules.points_to_win = (lobby.rules.points_to_win - 1).max(1);
+    }
+    if pressed(KeyCode::Digit1) {
+        lobby.rules.mutators.fast_ball = !lobby.rules.mutators.fast_ball;
+    }
+    if pressed(KeyCode::Digit2) {
+        lobby.rules.mutators.instant_paddles = !lobby.rules.mutators.instant_paddles;
+    }
+    if pressed(KeyCode::Digit3) {
+        lobby.rules.ball_size = match lobby.rules.ball_size {
+            BallSize::Huge => BallSize::Tiny,
+            size => size.step(1),
+        };
+    }
+    if before != (lobby.host_left, lobby.rules.clone()) {
+        lobby.ready = [false, false];
+    }
+    if pressed(KeyCode::Space) && lobby.ready == [true, true] {
+        lobby.started = true;
+    }
+}
+
+pub fn update_lobby_overlay(
+    lobby: Option<Res<Lobby>>,
+    choice: Option<Res<LobbyChoice>>,
+    settings: Res<Settings>,
+    mut overlays: Query<&mut Text, With<LobbyOverlay>>,
+) {
+    let Some(lobby) = lobby else {
+        return;
+    };
+    let mut lobby = lobby.clone();
+    // The guest shows its own choice straight away rather than waiting for the host to echo it.
+    if let Some(choice) = &choice {
+        lobby.colors[1] = choice.color;
+        lobby.lengths[1] = choice.length;
+        lobby.ready[1] = choice.ready;
+    }
+    let on_off = |on: bool| if on { "on" } else { "off" };
+    let swatches = settings.accessibility.palette.swatches();
+    let entry = |i: usize| {
+        let ready = if lobby.ready[i] { " - ready" } else { "" };
+        format!("{} ({}, {:.0}%){}", lobby.names[i], swatches[lobby.colors[i]].0, lobby.lengths[i] * 100f32, ready)
+    };
+    let (left, right) = if lobby.host_left { (0, 1) } else { (1, 0) };
+    let controls = if choice.is_some() {
+        "C color   L length   R ready"
+    }
+    else {
+        "C color   L length   R ready   X swap sides\n[ ] points   1/2 mutators   3 ball size   Space start"
+    };
+    let mutators = &lobby.rules.mutators;
+    let extras = [
+        (mutators.giant_ball, "Giant ball"),
+        (mutators.tiny_paddles, "Tiny paddles"),
+        (mutators.gravity, "Gravity"),
+        (mutators.wind, "Wind"),
+        (mutators.ice, "Ice"),
+    ]
+        .iter()
+        .filter(|(on, _)| *on)
+        .map(|(_, name)| format!("\n{}!", name))
+        .collect::<String>();
+    let value = format!(
+        "LOBBY\n\n{}   vs   {}\n\nFirst to {}   Ball size: {:?}\nFast ball: {}   Instant paddles: {}{}\n\n{}",
+        entry(left),
+        entry(right),
+        lobby.rules.points_to_win,
+        lobby.rules.ball_size,
+        on_off(mutators.fast_ball),
+        on_off(mutators.instant_paddles),
+        extras,
+        controls,
+    );
+    for mut text in overlays.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+/// Leaves the lobby once it's started: applies the rules, colors and sides, and lets the match begin.
+pub fn start_lobby_match(
+    mut cmd: Commands,
+    lobby: Res<Lobby>,
+    mut config: ResMut<GameConfig>,
+    overlays: Query<Entity, With<LobbyOverlay>>,
+    mut paddles: Query<(Entity, &mut PaddleMotion, &Side, Has<LocalPaddle>, Has<NetworkController>)>,
+    mut balls: Query<&mut Ball>,
+    mut names: Query<(&mut Text, &Side), With<ProfileName>>,
+) {
+    if !lobby.started {
+        return;
+    }
+    cmd.remove_resource::<Lobby>();
+    for overlay in overlays.iter() {
+        cmd.entity(overlay).despawn_recursive();
+    }
+
+    lobby.rules.apply(&mut config);
+    for mut ball in balls.iter_mut() {
+        ball.speed = config.ball_start_speed;
+    }
+    cmd.insert_resource(MatchRules { paddle_lengths: lobby.side_lengths(), ..lobby.rules.clone() });
+
+    let colors = lobby.side_colors();
+    for (entity, mut motion, side, local, remote) in paddles.iter_mut() {
+        *motion = config.paddle_motion();
+        cmd.entity(entity).insert(PaddleColor(colors[side.index()]));
+        // On the host, whichever paddle the guest steers has a network controller.
+        if !lobby.host_left && (local || remote) {
+            if local {
+                cmd.entity(entity).remove::<LocalPaddle>().insert(NetworkController::default());
+            }
+            else {
+                cmd.entity(entity).remove::<NetworkController>().insert(LocalPaddle);
+            }
+        }
+    }
+
+    let (left, right) = if lobby.host_left { (0, 1) } else { (1, 0) };
+    for (mut text, side) in names.iter_mut() {
+        text.sections[0].value = lobby.names[if *side == Side::Left { left } else { right }].clone();
+    }
+}
@@ -0,0 +1,314 @@
+use std::fmt::Write as _;
+
+use bevy::{
+    prelude::*,
+    tasks::{block_on, futures_lite::future, IoTaskPool, Task},
+    utils::Instant,
+};
+use bevy_ggrs::Session;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    profiles::Profiles,
+    rollback::{self, RollbackConfig},
+    settings::Settings,
+    ProfileName, Side,
+};
+
+const FONT_SIZE: f32 = 16f32;
+const MAX_LISTED: usize = 10;
+const MAX_FILTER_LEN: usize = 16;
+/// How often a public lobby checks in with the relay, which forgets lobbies that stop checking in.
+const ADVERTISE_INTERVAL: f32 = 10f32;
+const ROOM_CODE_LEN: usize = 6;
+/// Letters and digits that can't be mistaken for each other when read out.
+const ROOM_CODE_CHARS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/// A lobby waiting for an opponent, as the relay lists it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PublicLobby {
+    pub room: String,
+    pub name: String,
+    pub mode: String,
+    /// The host's round trip to the relay when it last checked in.
+    pub ping_ms: u32,
+}
+
+impl PublicLobby {
+    fn new(room: String, profiles: &Profiles, settings: &Settings) -> Self {
+        PublicLobby {
+            room,
+            name: profiles.active().name.clone(),
+            mode: format!("Rollback, {} frame delay", settings.net.input_delay),
+            ping_ms: 0,
+        }
+    }
+
+    fn matches(&self, filter: &str) -> bool {
+        let filter = filter.to_lowercase();
+        self.name.to_lowercase().contains(&filter) || self.mode.to_lowercase().contains(&filter)
+    }
+}
+
+/// The relay keeps the list of public lobbies next to its rooms: `GET /lobbies` lists them, `POST /lobbies`
+/// adds or refreshes one, and `DELETE /lobbies/<room>` takes one down once its match starts.
+#[cfg(all(feature = "online", not(target_arch = "wasm32")))]
+mod http {
+    use super::PublicLobby;
+
+    pub fn list(url: &str) -> Result<Vec<PublicLobby>, String> {
+        ureq::get(&format!("{}/lobbies", url))
+            .call()
+            .map_err(|err| err.to_string())?
+            .into_json()
+            .map_err(|err| err.to_string())
+    }
+
+    pub fn advertise(url: &str, lobby: &PublicLobby) -> Result<(), String> {
+        ureq::post(&format!("{}/lobbies", url)).send_json(lobby).map_err(|err| err.to_string())?;
+        Ok(())
+    }
+
+    pub fn withdraw(url: &str, room: &str) -> Result<(), String> {
+        ureq::delete(&format!("{}/lobbies/{}", url, room)).call().map_err(|err| err.to_string())?;
+        Ok(())
+    }
+}
+
+#[cfg(not(all(feature = "online", not(target_arch = "wasm32"))))]
+mod http {
+    use super::PublicLobby;
+
+    pub fn list(_url: &str) -> Result<Vec<PublicLobby>, String> {
+        Err("the lobby browser isn't available in this build".into())
+    }
+
+    pub fn advertise(_url: &str, _lobby: &PublicLobby) -> Result<(), String> {
+        Err("public lobbies aren't available in this build".into())
+    }
+
+    pub fn withdraw(_url: &str, _room: &str) -> Result<(), String> {
+        Err("public lobbies aren't available in this build".into())
+    }
+}
+
+/// Runs `request` on the IO pool, along with how long it took in milliseconds.
+fn timed<T: Send + 'static>(request: impl FnOnce() -> Result<T, String> + Send + 'static) -> Task<Result<(T, u32), String>> {
+    IoTaskPool::get().spawn(async move {
+        let started = Instant::now();
+        let result = request()?;
+        Ok((result, started.elapsed().as_millis() as u32))
+    })
+}
+
+/// The list of public lobbies, open until one is picked.
+#[derive(Resource, Default)]
+pub struct LobbyBrowser {
+    lobbies: Vec<PublicLobby>,
+    /// This end's round trip to the relay, from the last refresh.
+    relay_ping_ms: Option<u32>,
+    /// Only lobbies whose name or mode contains this are listed.
+    filter: String,
+    selected: usize,
+    request: Option<Task<Result<(Vec<PublicLobby>, u32), String>>>,
+}
+
+impl LobbyBrowser {
+    fn refresh(&mut self, settings: &Settings) {
+        let url = settings.net.relay_http_url();
+        self.request = Some(timed(move || http::list(&url)));
+    }
+
+    fn listed(&self) -> Vec<&PublicLobby> {
+        self.lobbies.iter().filter(|lobby| lobby.matches(&self.filter)).take(MAX_LISTED).collect()
+    }
+
+    fn summary(&self) -> String {
+        let mut summary = format!("PUBLIC LOBBIES\nFilter: {}_\n", self.filter);
+        let listed = self.listed();
+        if self.request.is_some() && self.lobbies.is_empty() {
+            summary.push_str("\nLoading...");
+        }
+        else if listed.is_empty() {
+            summary.push_str("\nNo open lobbies");
+        }
+        for (i, lobby) in listed.iter().enumerate() {
+            // Players meet through the relay, so the way there and back from both ends is a fair guess.
+            let ping = self.relay_ping_ms.map_or_else(|| "--".into(), |ms| (ms + lobby.ping_ms).to_string());
+            let cursor = if i == self.selected { ">" } else { " " };
+            let _ = write!(summary, "\n{} {}   {}   ~{} ms", cursor, lobby.name, lobby.mode, ping);
+        }
+        summary.push_str("\n\nUp/Down select   Enter join   F3 host   F5 refresh   type to filter");
+        summary
+    }
+}
+
+/// A lobby this end hosts and lists on the relay until an opponent joins.
+#[derive(Resource)]
+pub struct AdvertisedLobby {
+    lobby: PublicLobby,
+    timer: Timer,
+    request: Option<Task<Result<((), u32), String>>>,
+}
+
+impl AdvertisedLobby {
+    fn new(room: String, profiles: &Profiles, settings: &Settings) -> Self {
+        AdvertisedLobby {
+            lobby: PublicLobby::new(room, profiles, settings),
+            timer: Timer::from_seconds(ADVERTISE_INTERVAL, TimerMode::Repeating),
+            request: None,
+        }
+    }
+}
+
+fn room_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..ROOM_CODE_LEN).map(|_| ROOM_CODE_CHARS[rng.gen_range(0..ROOM_CODE_CHARS.len())] as char).collect()
+}
+
+/// Lists the room passed to `--room` when `--public` is too.
+pub fn advertise_relay_room(
+    mut cmd: Commands,
+    room: Res<rollback::RelayRoom>,
+    profiles: Res<Profiles>,
+    settings: Res<Settings>,
+) {
+    cmd.insert_resource(AdvertisedLobby::new(room.0.clone(), &profiles, &settings));
+}
+
+#[derive(Component)]
+pub struct LobbyBrowserScreen;
+
+pub fn open_lobby_browser(mut cmd: Commands, settings: Res<Settings>) {
+    let mut browser = LobbyBrowser::default();
+    browser.refresh(&settings);
+    cmd.insert_resource(browser);
+    cmd.spawn((
+        Text2dBundle {
+            text: Text::from_section("", TextStyle {
+                font_size: FONT_SIZE,
+                ..default()
+            })
+            .with_justify(JustifyText::Center),
+            transform: Transform::from_xyz(0f32, 0f32, 5f32),
+            ..default()
+        },
+        LobbyBrowserScreen,
+    ));
+}
+
+pub fn browse_lobbies(
+    mut cmd: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    settings: Res<Settings>,
+    profiles: Res<Profiles>,
+    mut browser: ResMut<LobbyBrowser>,
+    screens: Query<Entity, With<LobbyBrowserScreen>>,
+    mut names: Query<(&mut Text, &Side), With<ProfileName>>,
+) {
+    for character in characters.read() {
+        for c in character.char.chars().filter(|c| !c.is_control()) {
+            if browser.filter.chars().count() < MAX_FILTER_LEN {
+                browser.filter.push(c);
+                browser.selected = 0;
+            }
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Backspace) && browser.filter.pop().is_some() {
+        browser.selected = 0;
+    }
+    let count = browser.listed().len();
+    if keyboard_input.just_pressed(KeyCode::ArrowDown) && browser.selected + 1 < count {
+        browser.selected += 1;
+    }
+    if keyboard_input.just_pressed(KeyCode::ArrowUp) {
+        browser.selected = browser.selected.saturating_sub(1);
+    }
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        browser.refresh(&settings);
+    }
+
+    let (room, opponent) = if keyboard_input.just_pressed(KeyCode::F3) {
+        let room = room_code();
+        cmd.insert_resource(AdvertisedLobby::new(room.clone(), &profiles, &settings));
+        (room, "Waiting...".to_string())
+    }
+    else if keyboard_input.just_pressed(KeyCode::Enter) {
+        let Some(lobby) = browser.listed().get(browser.selected).map(|lobby| (*lobby).clone()) else {
+            return;
+        };
+        (lobby.room, lobby.name)
+    }
+    else {
+        return;
+    };
+    rollback::join_relay_room(&mut cmd, &settings, &room);
+    for (mut name, _) in names.iter_mut().filter(|(_, side)| **side == Side::Right) {
+        name.sections[0].value = opponent.clone();
+    }
+    cmd.remove_resource::<LobbyBrowser>();
+    for screen in screens.iter() {
+        cmd.entity(screen).despawn_recursive();
+    }
+}
+
+pub fn poll_lobby_browser(mut browser: ResMut<LobbyBrowser>, mut screens: Query<&mut Text, With<LobbyBrowserScreen>>) {
+    if let Some(request) = browser.bypass_change_detection().request.as_mut() {
+        if let Some(result) = block_on(future::poll_once(request)) {
+            browser.request = None;
+            match result {
+                Ok((lobbies, ping_ms)) => {
+                    browser.lobbies = lobbies;
+                    browser.relay_ping_ms = Some(ping_ms);
+                    browser.selected = 0;
+                },
+                Err(err) => warn!("Listing public lobbies failed: {}", err),
+            }
+        }
+    }
+    if !browser.is_changed() {
+        return;
+    }
+    for mut text in screens.iter_mut() {
+        text.sections[0].value = browser.summary();
+    }
+}
+
+/// Keeps the hosted lobby listed while it waits, and takes it down once the match starts.
+pub fn advertise_lobby(
+    mut cmd: Commands,
+    time: Res<Time<Real>>,
+    settings: Res<Settings>,
+    session: Option<Res<Session<RollbackConfig>>>,
+    mut advertised: ResMut<AdvertisedLobby>,
+) {
+    let url = settings.net.relay_http_url();
+    if session.is_some() {
+        let room = advertised.lobby.room.clone();
+        IoTaskPool::get()
+            .spawn(async move {
+                if let Err(err) = http::withdraw(&url, &room) {
+                    warn!("Failed to unlist lobby {}: {}", room, err);
+                }
+            })
+            .detach();
+        cmd.remove_resource::<AdvertisedLobby>();
+        return;
+    }
+    if let Some(request) = advertised.request.as_mut() {
+        if let Some(result) = block_on(future::poll_once(request)) {
+            advertised.request = None;
+            match result {
+                Ok(((), ping_ms)) => advertised.lobby.ping_ms = ping_ms,
+                Err(err) => warn!("Failed to list lobby {}: {}", advertised.lobby.room, err),
+            }
+        }
+    }
+    // Listed straight away, then every so often.
+    if advertised.timer.tick(time.delta()).just_finished() || advertised.is_added() {
+        let lobby = advertised.lobby.clone();
+        advertised.request = Some(timed(move || http::advertise(&url, &lobby)));
+    }
+}
@@ -0,0 +1,1076 @@
+use std::{
+    collections::VecDeque,
+    fmt, io,
+    net::{SocketAddr, UdpSocket},
+    sync::Mutex,
+    time::Duration,
+};
+
+use bevy::{app::AppExit, diagnostic::Diagnostics, ecs::system::SystemParam, prelude::*, utils::Instant};
+use bevy_ggrs::{
+    ggrs::{Message, PlayerType},
+    Session,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chat::NetworkController,
+    cli::NetConditions,
+    chat_box::{ChatReceived, ChatSent},
+    diagnostics,
+    lobby::{Lobby, LobbyChoice},
+    profiles::Profiles,
+    rollback::{self, PeerAddr, RollbackConfig, RollbackSocket},
+    settings::Settings,
+    replication::{NetId, NetIds, Predicted, ReceivedState, ReplicationRules, Replicated, WorldState},
+    Arena, Collider, Interpolated, LocalPaddle, PaddleMotion, GameState, Paddle, PlayerInput, ProfileName, ServeRequested,
+    Side,
+};
+
+/// Bump whenever `NetMessage` or anything in it changes shape, so old and new builds refuse each other instead of desyncing.
+pub const PROTOCOL_VERSION: u32 = 3;
+
+const HELLO_INTERVAL: f32 = 0.5f32;
+const PING_INTERVAL: f32 = 1f32;
+const MAX_PACKET: usize = 4096;
+/// How far behind the host the guest draws the remote paddle and ball, so there's usually a newer snapshot to move toward.
+const INTERPOLATION_DELAY: f64 = 0.05f64;
+/// The longest the ball keeps moving on its own when snapshots stop arriving.
+const MAX_EXTRAPOLATION: f64 = 0.1f64;
+/// A ball that moves further than this between snapshots was reset, not hit, so it isn't interpolated.
+const TELEPORT_DISTANCE: f32 = 64f32;
+/// Past this, the predicted paddle snaps to where the host has it.
+const RECONCILE_DISTANCE: f32 = 24f32;
+/// How much of the gap to the host's paddle an idle predicted paddle closes per snapshot.
+const RECONCILE_RATE: f32 = 0.2f32;
+/// Silence from the other end for this long counts as a dropped connection.
+pub const DISCONNECT_TIMEOUT: f64 = 3f64;
+/// How long a dropped player has to come back before someone else can take their place.
+const RECONNECT_GRACE: f64 = 30f64;
+/// Slack on top of half the round trip when trusting where a guest says its paddle is.
+const LAG_TOLERANCE: f32 = 0.05f32;
+/// The longest an input is trusted to have been in flight, however slow a guest makes its ping look.
+const MAX_IN_FLIGHT: f32 = 0.25f32;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum NetMessage {
+    /// Sent by the guest until the host answers with `Welcome`.
+    Hello { name: String },
+    /// Like `Hello`, but only to watch.
+    Spectate { name: String },
+    /// `left` is whether the guest plays the left paddle.
+    Welcome { name: String, left: bool },
+    /// The host's lobby, sent every frame until the match starts.
+    Lobby(Lobby),
+    /// The guest's entry in the lobby.
+    LobbyChoice(LobbyChoice),
+    /// Answered with a `Pong` carrying the same time, to measure round trips.
+    Ping { sent: f64 },
+    Pong { sent: f64 },
+    Chat { name: String, text: String },
+    /// `y` is where the guest's own paddle is on its screen, for lag compensation.
+    Input { dir: i8, serve: bool, y: f32 },
+    Snapshot(Snapshot),
+    /// Rollback session traffic, once both ends have switched over.
+    Ggrs(Message),
+}
+
+/// Every packet is stamped with the protocol version, which is read on its own first so any build can tell
+/// a packet from a different version apart from garbage.
+#[derive(Serialize, Deserialize)]
+struct Packet<T> {
+    version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<T>,
+}
+
+#[derive(Deserialize)]
+struct PacketVersion {
+    version: u32,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ProtocolError {
+    Malformed,
+    Version(u32),
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProtocolError::Malformed => write!(f, "malformed packet"),
+            ProtocolError::Version(version) => write!(
+                f,
+                "it speaks network protocol v{} and this build speaks v{}; both need the same version of the game",
+                version, PROTOCOL_VERSION,
+            ),
+        }
+    }
+}
+
+impl NetMessage {
+    /// The wire format, the same over UDP and WebRTC.
+    pub fn encode(&self) -> Vec<u8> {
+        let packet = Packet { version: PROTOCOL_VERSION, message: Some(self) };
+        serde_json::to_vec(&packet).expect("net messages always serialize")
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ProtocolError> {
+        let header: PacketVersion = serde_json::from_slice(bytes).map_err(|_| ProtocolError::Malformed)?;
+        if header.version != PROTOCOL_VERSION {
+            return Err(ProtocolError::Version(header.version));
+        }
+        let packet: Packet<NetMessage> = serde_json::from_slice(bytes).map_err(|_| ProtocolError::Malformed)?;
+        packet.message.ok_or(ProtocolError::Malformed)
+    }
+}
+
+/// Everything the guest needs to draw the host's match.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// The host's fixed clock, so the guest can place snapshots in time however late they arrive.
+    time: f64,
+    state: GameState,
+    world: WorldState,
+    /// The players' names, left to right.
+    names: [String; 2],
+    /// Each player's round trip to the host, when the host has measured it.
+    pings_ms: [Option<u32>; 2],
+}
+
+/// What's needed to capture a `Snapshot` of the match.
+#[derive(SystemParam)]
+pub struct MatchView<'w> {
+    time: Res<'w, Time<Fixed>>,
+    state: Res<'w, State<GameState>>,
+    rules: Res<'w, ReplicationRules>,
+    world: &'w World,
+}
+
+impl Snapshot {
+    /// Where entity `id` was at host time `at`, between this snapshot and `next`, or carried a little past `next`
+    /// along the way it was going.
+    fn sample(&self, next: Option<&Snapshot>, id: NetId, at: f64) -> Option<Vec2> {
+        let from = self.world.entity(id)?.pos;
+        let Some((next, to)) = next.filter(|next| next.time > self.time).and_then(|next| Some((next, next.world.entity(id)?.pos)))
+        else {
+            return Some(from);
+        };
+        if from.distance(to) > TELEPORT_DISTANCE {
+            return Some(to);
+        }
+        let span = next.time - self.time;
+        let t = ((at - self.time) / span).clamp(0f64, 1f64 + MAX_EXTRAPOLATION / span) as f32;
+        Some(from + (to - from) * t)
+    }
+}
+
+impl MatchView<'_> {
+    pub fn snapshot(&self, names: [String; 2], pings_ms: [Option<u32>; 2]) -> NetMessage {
+        NetMessage::Snapshot(Snapshot {
+            time: self.time.elapsed_seconds_f64(),
+            state: self.state.get().clone(),
+            world: self.rules.capture(self.world),
+            names,
+            pings_ms,
+        })
+    }
+}
+
+impl NetConditions {
+    fn is_perfect(&self) -> bool {
+        self.latency_ms == 0 && self.jitter_ms == 0 && self.loss <= 0f32
+    }
+
+    /// When a packet passing through now comes out the other side, unless it's lost.
+    fn arrival(&self, now: Instant) -> Option<Instant> {
+        let mut rng = rand::thread_rng();
+        if rng.gen::<f32>() < self.loss {
+            return None;
+        }
+        let jitter = self.jitter_ms as i64;
+        let delay_ms = (self.latency_ms as i64 + rng.gen_range(-jitter..=jitter)).max(0);
+        Some(now + Duration::from_millis(delay_ms as u64))
+    }
+}
+
+struct DelayedPacket {
+    due: Instant,
+    addr: SocketAddr,
+    bytes: Vec<u8>,
+}
+
+/// Packets held back by simulated `NetConditions`, until they're due.
+#[derive(Default)]
+struct DelayedPackets {
+    outgoing: Vec<DelayedPacket>,
+    incoming: Vec<DelayedPacket>,
+}
+
+fn take_due(packets: &mut Vec<DelayedPacket>, now: Instant) -> Vec<DelayedPacket> {
+    let (due, waiting) = packets.drain(..).partition(|packet| packet.due <= now);
+    *packets = waiting;
+    due
+}
+
+/// A non-blocking UDP socket and the one peer it talks to.
+pub struct Link {
+    socket: UdpSocket,
+    pub peer: Option<SocketAddr>,
+    conditions: NetConditions,
+    // Sending only takes `&self`, like the socket's own `send_to`.
+    delayed: Mutex<DelayedPackets>,
+}
+
+impl Link {
+    pub fn bind(addr: SocketAddr, peer: Option<SocketAddr>, conditions: NetConditions) -> io::Result<Self> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+        if !conditions.is_perfect() {
+            warn!(target: "kpong::net", "Simulating network conditions: {:?}", conditions);
+        }
+        Ok(Link { socket, peer, conditions, delayed: Mutex::default() })
+    }
+
+    fn try_clone(&self) -> io::Result<Self> {
+        Ok(Link {
+            socket: self.socket.try_clone()?,
+            peer: self.peer,
+            conditions: self.conditions,
+            delayed: Mutex::default(),
+        })
+    }
+
+    pub fn send(&self, message: &NetMessage) {
+        if let Some(peer) = self.peer {
+            self.send_to(message, peer);
+        }
+    }
+
+    pub fn send_to(&self, message: &NetMessage, peer: SocketAddr) {
+        self.send_bytes(message.encode(), peer);
+    }
+
+    /// Tells whoever sent a packet from another protocol version which version this end speaks.
+    /// The reply has no message, so it's never answered in turn.
+    pub fn reject(&self, peer: SocketAddr) {
+        let packet: Packet<NetMessage> = Packet { version: PROTOCOL_VERSION, message: None };
+        self.send_bytes(serde_json::to_vec(&packet).expect("net messages always serialize"), peer);
+    }
+
+    fn send_bytes(&self, bytes: Vec<u8>, peer: SocketAddr) {
+        if self.conditions.is_perfect() {
+            self.send_now(&bytes, peer);
+        }
+        else if let Some(due) = self.conditions.arrival(Instant::now()) {
+            self.delayed.lock().unwrap().outgoing.push(DelayedPacket { due, addr: peer, bytes });
+        }
+    }
+
+    fn send_now(&self, bytes: &[u8], peer: SocketAddr) {
+        // Packets are sent every frame, so a dropped one is soon replaced.
+        if let Err(err) = self.socket.send_to(bytes, peer) {
+            if err.kind() != io::ErrorKind::WouldBlock {
+                warn!(target: "kpong::net", "Failed to send to {}: {}", peer, err);
+            }
+        }
+    }
+
+    /// Everything that arrived since the last call, from anyone, minus anything malformed.
+    /// With simulated conditions, this is also when held-back packets go out.
+    pub fn receive(&mut self) -> Vec<(SocketAddr, Result<NetMessage, ProtocolError>)> {
+        let now = Instant::now();
+        let mut packets = Vec::new();
+        let mut buf = [0u8; MAX_PACKET];
+        while let Ok((len, from)) = self.socket.recv_from(&mut buf) {
+            packets.push(DelayedPacket { due: now, addr: from, bytes: buf[..len].to_vec() });
+        }
+        if !self.conditions.is_perfect() {
+            for packet in take_due(&mut self.delayed.get_mut().unwrap().outgoing, now) {
+                self.send_now(&packet.bytes, packet.addr);
+            }
+            let delayed = self.delayed.get_mut().unwrap();
+            for packet in packets {
+                if let Some(due) = self.conditions.arrival(now) {
+                    delayed.incoming.push(DelayedPacket { due, ..packet });
+                }
+            }
+            packets = take_due(&mut delayed.incoming, now);
+        }
+        packets
+            .into_iter()
+            .map(|packet| (packet.addr, NetMessage::decode(&packet.bytes)))
+            .filter(|(_, message)| !matches!(message, Err(ProtocolError::Malformed)))
+            .collect()
+    }
+}
+
+/// Runs the match and plays the left paddle; the guest steers the right one.
+#[derive(Resource)]
+pub struct LanHost {
+    link: Link,
+    /// With rollback, the host hands the link to a rollback session once the lobby starts.
+    rollback_delay: Option<usize>,
+    guest_name: Option<String>,
+    last_heard: f64,
+    /// Set while the guest is gone; the match stays paused until they're back.
+    reconnect_deadline: Option<f64>,
+    guest_ping_ms: Option<u32>,
+    guest_paddle_y: Option<f32>,
+    ping_timer: Timer,
+    spectators: Vec<SocketAddr>,
+    /// The started lobby, repeated to a guest that still sends lobby choices because it missed it.
+    started_lobby: Option<NetMessage>,
+    left: bool,
+}
+
+impl LanHost {
+    pub fn bind(port: u16, rollback_delay: Option<usize>, conditions: NetConditions) -> io::Result<Self> {
+        let link = Link::bind(SocketAddr::from(([0, 0, 0, 0], port)), None, conditions)?;
+        Ok(LanHost {
+            link,
+            rollback_delay,
+            guest_name: None,
+            last_heard: 0f64,
+            reconnect_deadline: None,
+            guest_ping_ms: None,
+            guest_paddle_y: None,
+            ping_timer: Timer::from_seconds(PING_INTERVAL, TimerMode::Repeating),
+            spectators: Vec::new(),
+            started_lobby: None,
+            left: true,
+        })
+    }
+}
+
+/// Sends its inputs to the host and draws whatever state the host sends back.
+#[derive(Resource)]
+pub struct LanGuest {
+    link: Link,
+    welcomed: bool,
+    hello_timer: Timer,
+    rollback_delay: Option<usize>,
+    /// Spectators only watch; they send no input and ping the host instead.
+    spectator: bool,
+    /// Players learn their round trip from the host's snapshots, spectators by pinging.
+    ping_ms: Option<u32>,
+    /// Whether the guest plays the left paddle, which it moves itself instead of waiting on the host.
+    left: bool,
+    last_heard: f64,
+    /// Whether the host went quiet after welcoming us, so we're saying hello again.
+    reconnecting: bool,
+    /// Snapshots by host time, oldest first.
+    snapshots: VecDeque<Snapshot>,
+    /// Host time minus local time, taken from the quickest snapshot lately.
+    clock_offset: Option<f64>,
+}
+
+impl LanGuest {
+    pub fn connect(host: SocketAddr, rollback_delay: Option<usize>, conditions: NetConditions) -> io::Result<Self> {
+        let link = Link::bind(SocketAddr::from(([0, 0, 0, 0], 0)), Some(host), conditions)?;
+        Ok(LanGuest {
+            link,
+            welcomed: false,
+            hello_timer: Timer::from_seconds(HELLO_INTERVAL, TimerMode::Repeating),
+            rollback_delay,
+            spectator: false,
+            ping_ms: None,
+            left: false,
+            last_heard: 0f64,
+            reconnecting: false,
+            snapshots: VecDeque::new(),
+            clock_offset: None,
+        })
+    }
+
+    pub fn spectate(host: SocketAddr, conditions: NetConditions) -> io::Result<Self> {
+        Ok(LanGuest { spectator: true, ..LanGuest::connect(host, None, conditions)? })
+    }
+
+    /// The host this guest becomes once the real one is gone for good. It listens on the old host's port
+    /// (or any, if that's taken on this machine) and waits for them to rejoin, the same as for a dropped guest.
+    fn take_over(&self, now: f64) -> io::Result<LanHost> {
+        let port = self.link.peer.map_or(0, |host| host.port());
+        let conditions = self.link.conditions;
+        let mut host = LanHost::bind(port, None, conditions).or_else(|_| LanHost::bind(0, None, conditions))?;
+        host.left = self.left;
+        host.guest_name = self.snapshots.back().map(|snapshot| snapshot.names[if self.left { 1 } else { 0 }].clone());
+        host.last_heard = now;
+        host.reconnect_deadline = Some(now + RECONNECT_GRACE);
+        info!(target: "kpong::net", "Took over as host on {}", host.link.socket.local_addr()?);
+        Ok(host)
+    }
+}
+
+impl LanHost {
+    /// A dropped guest can come back from a new address, within the grace period, as long as the name matches.
+    fn is_rejoining(&self, name: &str) -> bool {
+        self.reconnect_deadline.is_some() && self.guest_name.as_deref() == Some(name)
+    }
+
+    /// Sends to the guest and every spectator, except whoever `except` is.
+    fn broadcast(&self, message: &NetMessage, except: Option<SocketAddr>) {
+        for addr in self.link.peer.iter().chain(&self.spectators) {
+            if Some(*addr) != except {
+                self.link.send_to(message, *addr);
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct SpectatorHud;
+
+pub fn ping_ms(time: &Time<Real>, sent: f64) -> u32 {
+    ((time.elapsed_seconds_f64() - sent) * 1000f64) as u32
+}
+
+pub fn attach_guest_controller(
+    mut cmd: Commands,
+    paddles: Query<(Entity, &Side), With<Paddle>>,
+    mut names: Query<(&mut Text, &Side), With<ProfileName>>,
+) {
+    for (paddle, _) in paddles.iter().filter(|(_, side)| **side == Side::Right) {
+        cmd.entity(paddle).insert(NetworkController::default());
+    }
+    for (mut name, _) in names.iter_mut().filter(|(_, side)| **side == Side::Right) {
+        name.sections[0].value = "Waiting...".into();
+    }
+}
+
+pub fn receive_guest_input(
+    mut host: ResMut<LanHost>,
+    time: Res<Time<Real>>,
+    profiles: Res<Profiles>,
+    mut lobby: Option<ResMut<Lobby>>,
+    mut serve: ResMut<ServeRequested>,
+    mut chat: EventWriter<ChatReceived>,
+    mut controllers: Query<&mut NetworkController>,
+    mut names: Query<(&mut Text, &Side), With<ProfileName>>,
+) {
+    let welcome = NetMessage::Welcome { name: profiles.active().name.clone(), left: !host.left };
+    let now = time.elapsed_seconds_f64();
+    for (from, message) in host.link.receive() {
+        let from_guest = host.link.peer == Some(from);
+        if from_guest {
+            host.last_heard = now;
+        }
+        let message = match message {
+            Ok(message) => message,
+            Err(err) => {
+                warn!(target: "kpong::net", "Turned away {}: {}", from, err);
+                host.link.reject(from);
+                continue;
+            },
+        };
+        match message {
+            NetMessage::Hello { name } if host.link.peer.is_none() || from_guest || host.is_rejoining(&name) => {
+                if !from_guest {
+                    info!(target: "kpong::net", "{} joined from {}", name, from);
+                    host.link.peer = Some(from);
+                    host.last_heard = now;
+                }
+                // After the lobby swaps sides, the labels already show the guest's name where it belongs.
+                if host.left {
+                    for (mut text, _) in names.iter_mut().filter(|(_, side)| **side == Side::Right) {
+                        text.sections[0].value = name.clone();
+                    }
+                }
+                if let Some(lobby) = lobby.as_mut() {
+                    lobby.names[1] = name.clone();
+                }
+                host.guest_name = Some(name);
+                host.link.send(&welcome);
+            },
+            NetMessage::LobbyChoice(choice) if from_guest => {
+                if let Some(started) = &host.started_lobby {
+                    host.link.send(started);
+                }
+                else if let Some(lobby) = lobby.as_mut() {
+                    lobby.colors[1] = choice.color;
+                    lobby.lengths[1] = choice.length;
+                    lobby.ready[1] = choice.ready;
+                    if !choice.name.is_empty() && lobby.names[1] != choice.name {
+                        lobby.names[1] = choice.name.clone();
+                        host.guest_name = Some(choice.name);
+                    }
+                }
+            },
+            NetMessage::Spectate { name } => {
+                if !host.spectators.contains(&from) {
+                    info!(target: "kpong::net", "{} is spectating from {}", name, from);
+                    host.spectators.push(from);
+                }
+                host.link.send_to(&welcome, from);
+            },
+            NetMessage::Ping { sent } => host.link.send_to(&NetMessage::Pong { sent }, from),
+            NetMessage::Chat { name, text } if from_guest || host.spectators.contains(&from) => {
+                host.broadcast(&NetMessage::Chat { name: name.clone(), text: text.clone() }, Some(from));
+                chat.send(ChatReceived { name, text });
+            },
+            NetMessage::Pong { sent } if from_guest => host.guest_ping_ms = Some(ping_ms(&time, sent)),
+            NetMessage::Input { dir, serve: guest_serve, y } if from_guest => {
+                for mut controller in controllers.iter_mut() {
+                    controller.dir = validate_dir(dir);
+                }
+                host.guest_paddle_y = y.is_finite().then_some(y);
+                // Moving serves, the same as it does for the host.
+                serve.0 |= guest_serve || dir != 0;
+            },
+            _ => {},
+        }
+    }
+}
+
+/// Keeps everyone's copy of the lobby current, and with rollback, hands the link over once it starts.
+pub fn send_lobby(mut cmd: Commands, mut host: ResMut<LanHost>, lobby: Res<Lobby>) {
+    let message = NetMessage::Lobby(lobby.clone());
+    host.broadcast(&message, None);
+    if !lobby.started {
+        return;
+    }
+    host.started_lobby = Some(message.clone());
+    host.left = lobby.host_left;
+    if let Some(delay) = host.rollback_delay {
+        if start_rollback(&mut cmd, &host.link, Some(message), lobby.host_left, delay, lobby.seed) {
+            cmd.remove_resource::<LanHost>();
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct ReconnectNotice;
+
+pub fn spawn_reconnect_notice(mut cmd: Commands) {
+    cmd.spawn((
+        Text2dBundle {
+            text: Text::from_section("", TextStyle {
+                font_size: 20f32,
+                ..default()
+            })
+            .with_justify(JustifyText::Center),
+            transform: Transform::from_xyz(0f32, 64f32, 6f32),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        ReconnectNotice,
+    ));
+}
+
+fn show_notice(notices: &mut Query<(&mut Text, &mut Visibility), With<ReconnectNotice>>, notice: Option<String>) {
+    for (mut text, mut visibility) in notices.iter_mut() {
+        *visibility = if notice.is_some() { Visibility::Visible } else { Visibility::Hidden };
+        if let Some(notice) = &notice {
+            text.sections[0].value = notice.clone();
+        }
+    }
+}
+
+/// While the host is paused, the fixed-step systems don't run, so this reads the link in their place.
+pub fn awaiting_guest(host: Option<Res<LanHost>>) -> bool {
+    host.is_some_and(|host| host.reconnect_deadline.is_some())
+}
+
+/// Pauses the match when the guest goes quiet, and resumes it when they, or after the grace period anyone, join.
+pub fn watch_guest_connection(
+    mut host: ResMut<LanHost>,
+    time: Res<Time<Real>>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut notices: Query<(&mut Text, &mut Visibility), With<ReconnectNotice>>,
+) {
+    let now = time.elapsed_seconds_f64();
+    let Some(name) = host.guest_name.clone() else {
+        return;
+    };
+    if host.link.peer.is_some() && now - host.last_heard < DISCONNECT_TIMEOUT {
+        if host.reconnect_deadline.take().is_some() {
+            info!(target: "kpong::net", "{} is back", name);
+            virtual_time.unpause();
+            show_notice(&mut notices, None);
+        }
+        return;
+    }
+    let deadline = match host.reconnect_deadline {
+        Some(deadline) => deadline,
+        None => {
+            warn!(target: "kpong::net", "Lost {}, waiting {} s for them to reconnect", name, RECONNECT_GRACE);
+            now + RECONNECT_GRACE
+        },
+    };
+    host.reconnect_deadline = Some(deadline);
+    virtual_time.pause();
+    if now >= deadline && host.link.peer.is_some() {
+        info!(target: "kpong::net", "{} didn't come back; the next player to join takes their place", name);
+        host.link.peer = None;
+    }
+    let notice = if now < deadline {
+        format!("Waiting for {} to reconnect ({} s)", name, (deadline - now).ceil())
+    }
+    else {
+        "Waiting for a player to join".into()
+    };
+    show_notice(&mut notices, Some(notice));
+}
+
+/// Says hello again when the host goes quiet. Once the grace period is over, a spectator gives up, and a player
+/// takes over as host from the last snapshot, so the match and its score carry on.
+pub fn watch_host_connection(
+    mut cmd: Commands,
+    mut guest: ResMut<LanGuest>,
+    time: Res<Time<Real>>,
+    mut ids: ResMut<NetIds>,
+    mut exit: EventWriter<AppExit>,
+    mut notices: Query<(&mut Text, &mut Visibility), With<ReconnectNotice>>,
+    mut entities: Query<(Entity, &NetId, &mut Transform, &mut Interpolated, Has<Paddle>, &Side), With<Replicated>>,
+) {
+    if !guest.welcomed && !guest.reconnecting {
+        return;
+    }
+    let silence = time.elapsed_seconds_f64() - guest.last_heard;
+    if silence < DISCONNECT_TIMEOUT {
+        if guest.reconnecting && guest.welcomed {
+            info!(target: "kpong::net", "Reconnected to the host");
+            guest.reconnecting = false;
+            show_notice(&mut notices, None);
+        }
+        return;
+    }
+    if !guest.reconnecting {
+        warn!(target: "kpong::net", "Lost the host, trying to reconnect");
+        guest.reconnecting = true;
+    }
+    guest.welcomed = false;
+    let left = DISCONNECT_TIMEOUT + RECONNECT_GRACE - silence;
+    if left > 0f64 {
+        show_notice(&mut notices, Some(format!("Connection lost, reconnecting ({} s)", left.ceil())));
+        return;
+    }
+    let Some(newest) = guest.snapshots.back().filter(|_| !guest.spectator) else {
+        error!(target: "kpong::net", "Couldn't reconnect to the host");
+        exit.send(AppExit);
+        return;
+    };
+    let host = match guest.take_over(time.elapsed_seconds_f64()) {
+        Ok(host) => host,
+        Err(err) => {
+            error!(target: "kpong::net", "Couldn't take over from the host: {}", err);
+            exit.send(AppExit);
+            return;
+        },
+    };
+    for (entity, id, mut transform, mut interp, is_paddle, side) in entities.iter_mut() {
+        if let Some(state) = newest.world.entity(*id) {
+            if !is_paddle {
+                transform.translation.x = state.pos.x;
+            }
+            transform.translation.y = state.pos.y;
+            *interp = Interpolated::at(transform.translation.truncate());
+        }
+        if !is_paddle {
+            continue;
+        }
+        let mut entity = cmd.entity(entity);
+        entity.remove::<Predicted>();
+        if (*side == Side::Left) == guest.left {
+            entity.remove::<NetworkController>().insert(LocalPaddle);
+        }
+        else {
+            entity.remove::<LocalPaddle>().insert(NetworkController::default());
+        }
+    }
+    ids.resume_after(&newest.world);
+    cmd.remove_resource::<LanGuest>();
+    cmd.insert_resource(host);
+}
+
+pub fn ping_guest(time: Res<Time<Real>>, mut host: ResMut<LanHost>) {
+    if host.ping_timer.tick(time.delta()).just_finished() {
+        host.link.send(&NetMessage::Ping { sent: time.elapsed_seconds_f64() });
+    }
+}
+
+pub fn send_snapshot(host: Res<LanHost>, profiles: Res<Profiles>, view: MatchView) {
+    let mut names = [
+        profiles.active().name.clone(),
+        host.guest_name.clone().unwrap_or_else(|| "Waiting...".into()),
+    ];
+    let mut pings = [None, host.guest_ping_ms];
+    if !host.left {
+        names.reverse();
+        pings.reverse();
+    }
+    host.broadcast(&view.snapshot(names, pings), None);
+}
+
+pub fn send_chat(
+    mut sent: EventReader<ChatSent>,
+    host: Option<Res<LanHost>>,
+    guest: Option<Res<LanGuest>>,
+) {
+    for message in sent.read() {
+        let chat = NetMessage::Chat { name: message.name.clone(), text: message.text.clone() };
+        if let Some(host) = &host {
+            host.broadcast(&chat, None);
+        }
+        if let Some(guest) = &guest {
+            guest.link.send(&chat);
+        }
+    }
+}
+
+pub fn greet_host(
+    time: Res<Time<Real>>,
+    profiles: Res<Profiles>,
+    choice: Option<Res<LobbyChoice>>,
+    mut guest: ResMut<LanGuest>,
+) {
+    // Lobby choices go out as soon as they change, so the host sees them right away.
+    let choice_changed = guest.welcomed && choice.as_ref().is_some_and(|choice| choice.is_changed());
+    if !guest.hello_timer.tick(time.delta()).just_finished() && !choice_changed {
+        return;
+    }
+    let name = profiles.active().name.clone();
+    let message = match (guest.welcomed, guest.spectator, choice) {
+        (false, false, _) => NetMessage::Hello { name },
+        (false, true, _) => NetMessage::Spectate { name },
+        (true, true, _) => NetMessage::Ping { sent: time.elapsed_seconds_f64() },
+        (true, false, Some(choice)) => NetMessage::LobbyChoice(choice.clone()),
+        (true, false, None) => return,
+    };
+    guest.link.send(&message);
+}
+
+pub fn spawn_spectator_hud(mut cmd: Commands, arena: Res<Arena>) {
+    cmd.spawn((
+        Text2dBundle {
+            text: Text::from_section("Spectating", TextStyle {
+                font_size: 16f32,
+                ..default()
+            })
+            .with_justify(JustifyText::Center),
+            transform: Transform::from_xyz(0f32, -arena.half_size.y + 32f32, 1f32),
+            ..default()
+        },
+        SpectatorHud,
+    ));
+}
+
+pub fn send_guest_input(
+    mut input: ResMut<PlayerInput>,
+    guest: Res<LanGuest>,
+    paddles: Query<(&Interpolated, &Side), With<Paddle>>,
+) {
+    if guest.spectator {
+        return;
+    }
+    let y = paddles.iter().find(|(_, side)| (**side == Side::Left) == guest.left).map_or(0f32, |(interp, _)| interp.current.y);
+    guest.link.send(&NetMessage::Input { dir: input.dir as i8, serve: input.serve, y });
+    input.serve = false;
+}
+
+/// A remote player only ever moves one step up or down, whatever a modified client sends.
+pub fn validate_dir(dir: i8) -> i32 {
+    (dir as i32).signum()
+}
+
+/// Moves a remote player's paddle to where they saw it, as far as it could have gone while their input
+/// was in flight, so a ball they blocked on their screen is blocked on the host's too. It never leaves the arena.
+pub fn compensate_lag(
+    transform: &mut Transform,
+    motion: &PaddleMotion,
+    max_y: f32,
+    reported_y: f32,
+    ping_ms: Option<u32>,
+) {
+    let in_flight = (ping_ms.map_or(0f32, |ms| ms as f32 / 2000f32) + LAG_TOLERANCE).min(MAX_IN_FLIGHT);
+    let reach = motion.max_speed * in_flight;
+    let y = transform.translation.y;
+    transform.translation.y = reported_y.clamp(y - reach, y + reach).clamp(-max_y, max_y);
+}
+
+pub fn compensate_guest_paddle(
+    host: Res<LanHost>,
+    settings: Res<Settings>,
+    arena: Res<Arena>,
+    mut paddles: Query<(&mut Transform, &PaddleMotion, &Collider), With<NetworkController>>,
+) {
+    let Some(reported_y) = host.guest_paddle_y.filter(|_| settings.net.lag_compensation) else {
+        return;
+    };
+    for (mut transform, motion, collider) in paddles.iter_mut() {
+        let max_y = arena.half_size.y - collider.half_size.y;
+        compensate_lag(&mut transform, motion, max_y, reported_y, host.guest_ping_ms);
+    }
+}
+
+#[derive(Component)]
+pub struct PingHud;
+
+pub fn spawn_ping_hud(mut cmd: Commands, arena: Res<Arena>) {
+    cmd.spawn((
+        Text2dBundle {
+            text: Text::from_section("", TextStyle {
+                font_size: 14f32,
+                ..default()
+            }),
+            text_anchor: bevy::sprite::Anchor::BottomRight,
+            transform: Transform::from_xyz(arena.half_size.x - 8f32, -arena.half_size.y + 8f32, 3f32),
+            ..default()
+        },
+        PingHud,
+    ));
+}
+
+pub fn update_ping_hud(
+    host: Option<Res<LanHost>>,
+    guest: Option<Res<LanGuest>>,
+    session: Option<Res<Session<RollbackConfig>>>,
+    mut diagnostics: Diagnostics,
+    mut huds: Query<&mut Text, With<PingHud>>,
+) {
+    let ping_ms = match (host, guest, session) {
+        (Some(host), _, _) => host.guest_ping_ms,
+        (_, Some(guest), _) => guest.ping_ms,
+        (_, _, Some(session)) => rollback::ping_ms(&session),
+        _ => None,
+    };
+    if let Some(ms) = ping_ms {
+        diagnostics.add_measurement(diagnostics::PING, || ms as f64);
+    }
+    let value = ping_ms.map_or_else(|| "Ping -- ms".into(), |ms| format!("Ping {} ms", ms));
+    for mut text in huds.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+/// Hands the link to a rollback session. The host passes the started lobby, to repeat to a guest that missed it.
+fn start_rollback(
+    cmd: &mut Commands,
+    link: &Link,
+    reply: Option<NetMessage>,
+    local_left: bool,
+    delay: usize,
+    seed: u64,
+) -> bool {
+    let result = link
+        .try_clone()
+        .map_err(|err| err.to_string())
+        .and_then(|link| {
+            let remote = PlayerType::Remote(PeerAddr::Lan(link.peer.ok_or("no peer to play against")?));
+            let players = if local_left { vec![PlayerType::Local, remote] } else { vec![remote, PlayerType::Local] };
+            rollback::start_session(cmd, players, RollbackSocket::Lan { link, reply }, delay, seed)
+        });
+    match result {
+        Ok(()) => {
+            info!(target: "kpong::net", "Started rollback session with {} frames of input delay", delay);
+            true
+        },
+        Err(err) => {
+            warn!(target: "kpong::net", "Failed to start rollback session: {}", err);
+            false
+        },
+    }
+}
+
+/// Moves the guest's own paddle as soon as it's pressed, rather than a round trip later.
+pub fn predict_local_paddle(
+    mut cmd: Commands,
+    guest: Res<LanGuest>,
+    input: Res<PlayerInput>,
+    mut paddles: Query<(Entity, &mut Paddle, &Side, Has<Predicted>)>,
+) {
+    for (entity, mut paddle, side, predicted) in paddles.iter_mut() {
+        let local = !guest.spectator && (*side == Side::Left) == guest.left;
+        paddle.dir = if local { input.dir } else { 0 };
+        if local && !predicted {
+            cmd.entity(entity).insert(Predicted);
+        }
+        else if !local && predicted {
+            cmd.entity(entity).remove::<Predicted>();
+        }
+    }
+}
+
+/// Stands in for the rest of the simulation on the guest, which mirrors the host. Replicated components
+/// and resources are left for `apply_received_state`; this places entities in time and handles everything else.
+pub fn apply_snapshot(
+    mut cmd: Commands,
+    mut guest: ResMut<LanGuest>,
+    mut received: ResMut<ReceivedState>,
+    time: Res<Time<Real>>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut entities: Query<(&mut Transform, &NetId, Option<&Paddle>, Has<Predicted>), With<Replicated>>,
+    mut names: Query<(&mut Text, &Side), (With<ProfileName>, Without<SpectatorHud>)>,
+    mut huds: Query<&mut Text, (With<SpectatorHud>, Without<ProfileName>)>,
+    mut chat: EventWriter<ChatReceived>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let now = time.elapsed_seconds_f64();
+    let mut latest = None;
+    for (from, message) in guest.link.receive() {
+        if guest.link.peer != Some(from) {
+            continue;
+        }
+        let message = match message {
+            Ok(message) => {
+                guest.last_heard = now;
+                message
+            },
+            Err(err) => {
+                error!(target: "kpong::net", "Can't play with the host at {}: {}", from, err);
+                exit.send(AppExit);
+                return;
+            },
+        };
+        match message {
+            NetMessage::Welcome { name: host_name, left } => {
+                guest.welcomed = true;
+                guest.left = left;
+                info!(target: "kpong::net", "Joined {}'s match", host_name);
+            },
+            NetMessage::Lobby(lobby) => {
+                let started = lobby.started;
+                if started {
+                    guest.left = !lobby.host_left;
+                    cmd.remove_resource::<LobbyChoice>();
+                }
+                let seed = lobby.seed;
+                cmd.insert_resource(lobby);
+                if let Some(delay) = guest.rollback_delay.filter(|_| started && !guest.spectator) {
+                    if start_rollback(&mut cmd, &guest.link, None, guest.left, delay, seed) {
+                        cmd.remove_resource::<LanGuest>();
+                    }
+                    return;
+                }
+            },
+            NetMessage::Snapshot(snapshot) => {
+                // The quickest snapshot says the most about the clock; slower ones only nudge it, in case it drifts.
+                let offset = snapshot.time - now;
+                guest.clock_offset = Some(match guest.clock_offset {
+                    Some(current) if offset < current => current + (offset - current) * 0.01f64,
+                    _ => offset,
+                });
+                if guest.snapshots.back().map_or(true, |last| last.time < snapshot.time) {
+                    guest.snapshots.push_back(snapshot.clone());
+                }
+                latest = Some(snapshot);
+            },
+            NetMessage::Ping { sent } => guest.link.send(&NetMessage::Pong { sent }),
+            NetMessage::Pong { sent } => guest.ping_ms = Some(ping_ms(&time, sent)),
+            NetMessage::Chat { name, text } => {
+                chat.send(ChatReceived { name, text });
+            },
+            _ => {},
+        }
+    }
+
+    if let Some(snapshot) = &latest {
+        if *state.get() != snapshot.state {
+            next_state.set(snapshot.state.clone());
+        }
+        received.0 = Some(snapshot.world.clone());
+        if !guest.spectator {
+            guest.ping_ms = snapshot.pings_ms[if guest.left { 0 } else { 1 }];
+        }
+        for (mut text, side) in names.iter_mut() {
+            text.sections[0].value = snapshot.names[side.index()].clone();
+        }
+        if guest.spectator {
+            let ping = |ms: Option<u32>| ms.map_or_else(|| "--".into(), |ms| format!("{} ms", ms));
+            for mut hud in huds.iter_mut() {
+                hud.sections[0].value = format!(
+                    "Spectating\n{}: {}   {}: {}",
+                    snapshot.names[0],
+                    ping(snapshot.pings_ms[0].or(guest.ping_ms)),
+                    snapshot.names[1],
+                    ping(snapshot.pings_ms[1].or(guest.ping_ms)),
+                );
+            }
+        }
+    }
+
+    let Some(offset) = guest.clock_offset else {
+        return;
+    };
+    let at = now + offset - INTERPOLATION_DELAY;
+    while guest.snapshots.len() > 2 && guest.snapshots[1].time <= at {
+        guest.snapshots.pop_front();
+    }
+    let (Some(first), Some(newest)) = (guest.snapshots.front(), guest.snapshots.back()) else {
+        return;
+    };
+    for (mut transform, id, paddle, predicted) in entities.iter_mut() {
+        if predicted {
+            let Some(host_y) = newest.world.entity(*id).map(|entity| entity.pos.y) else {
+                continue;
+            };
+            let error = host_y - transform.translation.y;
+            if error.abs() > RECONCILE_DISTANCE {
+                transform.translation.y = host_y;
+            }
+            else if paddle.is_some_and(|paddle| paddle.dir == 0) && latest.is_some() {
+                transform.translation.y += error * RECONCILE_RATE;
+            }
+            continue;
+        }
+        let Some(pos) = first.sample(guest.snapshots.get(1), *id, at) else {
+            continue;
+        };
+        // Paddles sit at the edge of this end's own arena, so only their height comes from the host.
+        if paddle.is_none() {
+            transform.translation.x = pos.x;
+        }
+        transform.translation.y = pos.y;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_remote_paddles_within_reach() {
+        let motion = PaddleMotion { accel: 0f32, max_speed: 400f32, stop_friction: 0f32, instant: false };
+        let compensated = |y: f32, reported_y: f32, ping_ms: Option<u32>| {
+            let mut transform = Transform::from_xyz(0f32, y, 0f32);
+            compensate_lag(&mut transform, &motion, 200f32, reported_y, ping_ms);
+            transform.translation.y
+        };
+        let near = |a: f32, b: f32| (a - b).abs() < 1e-3f32;
+        assert!(near(compensated(0f32, 10f32, Some(50)), 10f32));
+        assert!(near(compensated(0f32, 150f32, Some(50)), 30f32));
+        // A ping inflated by holding back pongs doesn't buy a teleport.
+        assert!(near(compensated(0f32, 150f32, Some(10_000)), 100f32));
+        assert!(near(compensated(190f32, 500f32, Some(100)), 200f32));
+        assert_eq!(validate_dir(i8::MAX), 1);
+        assert_eq!(validate_dir(-3), -1);
+    }
+
+    #[test]
+    fn delays_and_drops_simulated_packets() {
+        let now = Instant::now();
+        let lagged = NetConditions { latency_ms: 80, jitter_ms: 0, loss: 0f32 };
+        assert_eq!(lagged.arrival(now), Some(now + Duration::from_millis(80)));
+        let jittery = NetConditions { latency_ms: 10, jitter_ms: 20, loss: 0f32 };
+        let due = jittery.arrival(now).unwrap();
+        assert!(due >= now && due <= now + Duration::from_millis(30));
+        assert_eq!(NetConditions { loss: 1f32, ..lagged }.arrival(now), None);
+        assert!(NetConditions::default().is_perfect());
+    }
+
+    #[test]
+    fn rejects_other_protocol_versions() {
+        let bytes = NetMessage::Ping { sent: 1.5f64 }.encode();
+        assert!(matches!(NetMessage::decode(&bytes), Ok(NetMessage::Ping { sent }) if sent == 1.5f64));
+
+        let old = br#"{"version":0,"message":{"Hello":{"name":"ana"}}}"#;
+        assert_eq!(NetMessage::decode(old).err(), Some(ProtocolError::Version(0)));
+        let reject = format!(r#"{{"version":{}}}"#, PROTOCOL_VERSION + 1);
+        assert_eq!(NetMessage::decode(reject.as_bytes()).err(), Some(ProtocolError::Version(PROTOCOL_VERSION + 1)));
+        assert_eq!(NetMessage::decode(b"not json").err(), Some(ProtocolError::Malformed));
+    }
+}
@@ -0,0 +1,122 @@
+use std::fmt::Write as _;
+
+use bevy::{
+    prelude::*,
+    tasks::{block_on, futures_lite::future, IoTaskPool, Task},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{profiles::Profiles, records::Records, settings::Settings};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OnlineEntry {
+    pub name: String,
+    pub rally_hits: u32,
+}
+
+type Request = Task<Result<Vec<OnlineEntry>, String>>;
+
+/// The global top 10 longest rallies, as last reported by the leaderboard server.
+#[derive(Resource, Default)]
+pub struct OnlineLeaderboard {
+    pub top: Vec<OnlineEntry>,
+    request: Option<Request>,
+}
+
+impl OnlineLeaderboard {
+    /// Runs `request` on the IO pool; any request still in flight is dropped, since the newer one returns a fresher board.
+    fn start(&mut self, request: impl FnOnce() -> Result<Vec<OnlineEntry>, String> + Send + 'static) {
+        self.request = Some(IoTaskPool::get().spawn(async move { request() }));
+    }
+
+    pub fn summary(&self) -> String {
+        let mut summary = String::from("Global top 10:");
+        for (rank, entry) in self.top.iter().enumerate() {
+            let _ = write!(summary, "\n{}. {} - {}", rank + 1, entry.name, entry.rally_hits);
+        }
+        summary
+    }
+}
+
+/// The server answers both a `GET` of and a `POST` to `<url>/scores` with its top 10.
+#[cfg(all(feature = "online", not(target_arch = "wasm32")))]
+mod http {
+    use super::OnlineEntry;
+
+    pub fn fetch(url: &str) -> Result<Vec<OnlineEntry>, String> {
+        ureq::get(&format!("{}/scores", url))
+            .call()
+            .map_err(|err| err.to_string())?
+            .into_json()
+            .map_err(|err| err.to_string())
+    }
+
+    pub fn submit(url: &str, entry: &OnlineEntry) -> Result<Vec<OnlineEntry>, String> {
+        ureq::post(&format!("{}/scores", url))
+            .send_json(entry)
+            .map_err(|err| err.to_string())?
+            .into_json()
+            .map_err(|err| err.to_string())
+    }
+}
+
+#[cfg(not(all(feature = "online", not(target_arch = "wasm32"))))]
+mod http {
+    use super::OnlineEntry;
+
+    pub fn fetch(_url: &str) -> Result<Vec<OnlineEntry>, String> {
+        Err("the online leaderboard isn't available in this build".into())
+    }
+
+    pub fn submit(_url: &str, _entry: &OnlineEntry) -> Result<Vec<OnlineEntry>, String> {
+        Err("the online leaderboard isn't available in this build".into())
+    }
+}
+
+fn leaderboard_url(settings: &Settings) -> Option<String> {
+    Some(settings.leaderboard_url.trim_end_matches('/').to_string())
+        .filter(|url| settings.online_leaderboard && !url.is_empty())
+}
+
+pub fn fetch_online_leaderboard(settings: Res<Settings>, mut online: ResMut<OnlineLeaderboard>) {
+    if let Some(url) = leaderboard_url(&settings) {
+        online.start(move || http::fetch(&url));
+    }
+}
+
+/// Sends the longest rally whenever it beats the one last sent.
+pub fn submit_rally_record(
+    settings: Res<Settings>,
+    records: Res<Records>,
+    profiles: Res<Profiles>,
+    mut online: ResMut<OnlineLeaderboard>,
+    mut submitted: Local<Option<u32>>,
+) {
+    let best = *submitted.get_or_insert(records.longest_rally);
+    if records.longest_rally <= best {
+        return;
+    }
+    *submitted = Some(records.longest_rally);
+    let Some(url) = leaderboard_url(&settings) else {
+        return;
+    };
+    let entry = OnlineEntry {
+        name: profiles.active().name.clone(),
+        rally_hits: records.longest_rally,
+    };
+    online.start(move || http::submit(&url, &entry));
+}
+
+pub fn poll_online_leaderboard(mut online: ResMut<OnlineLeaderboard>) {
+    let Some(request) = online.bypass_change_detection().request.as_mut() else {
+        return;
+    };
+    let Some(result) = block_on(future::poll_once(request)) else {
+        return;
+    };
+    online.request = None;
+    match result {
+        Ok(top) => online.top = top,
+        Err(err) => warn!("Online leaderboard request failed: {}", err),
+    }
+}
@@ -0,0 +1,222 @@
+use bevy::{
+    input::mouse::{MouseMotion, MouseWheel},
+    prelude::*,
+    render::view::screenshot::ScreenshotManager,
+    sprite::Mesh2dHandle,
+    window::PrimaryWindow,
+};
+
+use crate::{effects::Shake, save_screenshot, theme::PhotoFilter, Arena, PauseMenu};
+
+/// World units per second at normal zoom.
+const PAN_SPEED: f32 = 256f32;
+const ZOOM_STEP: f32 = 1.25f32;
+/// Projection scales, so the smallest is the furthest in.
+const MIN_ZOOM_SCALE: f32 = 0.25f32;
+const MAX_ZOOM_SCALE: f32 = 2f32;
+/// Big enough to cover the view at the furthest zoom out.
+const OVERLAY_SIZE: f32 = 8192f32;
+const FONT_SIZE: f32 = 14f32;
+const HELP: &str = "PHOTO MODE\nArrows or drag pan   Wheel or +/- zoom   H hide HUD   T filter   Space capture   Esc back";
+
+/// The free camera entered from the pause menu. Everything it moved or hid is put back on leaving.
+#[derive(Resource)]
+pub struct PhotoMode {
+    filter: PhotoFilter,
+    hud_hidden: Vec<(Entity, Visibility)>,
+    home: (Vec3, f32),
+    /// Set on the frame a capture is taken, so the help comes back once it's saved.
+    capturing: bool,
+}
+
+#[derive(Component)]
+pub struct PhotoHelp;
+
+#[derive(Component)]
+pub struct PhotoOverlay;
+
+pub fn enter_photo_mode(
+    mut cmd: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    arena: Res<Arena>,
+    mut shake: ResMut<Shake>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut cameras: Query<(Entity, &mut Transform, &OrthographicProjection), With<Camera2d>>,
+    mut menus: Query<&mut Visibility, With<PauseMenu>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyF) {
+        return;
+    }
+    let Ok((camera, mut transform, projection)) = cameras.get_single_mut() else {
+        return;
+    };
+    // Photos start from the camera's resting place, and that's where leaving puts it back.
+    transform.translation -= shake.settle().extend(0f32);
+    for mut menu in menus.iter_mut() {
+        *menu = Visibility::Hidden;
+    }
+    cmd.entity(camera).with_children(|camera| {
+        camera.spawn((
+            ColorMesh2dBundle {
+                mesh: Mesh2dHandle(meshes.add(Rectangle::new(OVERLAY_SIZE, OVERLAY_SIZE))),
+                material: materials.add(Color::NONE),
+                transform: Transform::from_xyz(0f32, 0f32, -1f32),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            PhotoOverlay,
+        ));
+        camera.spawn((
+            Text2dBundle {
+                text: Text::from_section(HELP, TextStyle {
+                    font_size: FONT_SIZE,
+                    ..default()
+                })
+                .with_justify(JustifyText::Center),
+                transform: Transform::from_xyz(0f32, -arena.half_size.y + 24f32, -0.5f32),
+                ..default()
+            },
+            PhotoHelp,
+        ));
+    });
+    cmd.insert_resource(PhotoMode {
+        filter: PhotoFilter::None,
+        hud_hidden: Vec::new(),
+        home: (transform.translation, projection.scale),
+        capturing: false,
+    });
+}
+
+/// Pans with the arrows or a left-drag and zooms with the wheel or +/-, on real time since the game is paused.
+pub fn move_photo_camera(
+    time: Res<Time<Real>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    mut motion: EventReader<MouseMotion>,
+    mut wheel: EventReader<MouseWheel>,
+    mut cameras: Query<(&mut Transform, &mut OrthographicProjection), With<Camera2d>>,
+    mut helps: Query<&mut Transform, (With<PhotoHelp>, Without<Camera2d>)>,
+) {
+    let Ok((mut transform, mut projection)) = cameras.get_single_mut() else {
+        return;
+    };
+    let mut pan = Vec2::ZERO;
+    for (key, dir) in [
+        (KeyCode::ArrowLeft, Vec2::NEG_X),
+        (KeyCode::ArrowRight, Vec2::X),
+        (KeyCode::ArrowDown, Vec2::NEG_Y),
+        (KeyCode::ArrowUp, Vec2::Y),
+    ] {
+        if keyboard_input.pressed(key) {
+            pan += dir * PAN_SPEED * time.delta_seconds();
+        }
+    }
+    let dragged = motion.read().map(|motion| motion.delta).sum::<Vec2>();
+    if mouse_input.pressed(MouseButton::Left) {
+        pan += Vec2::new(-dragged.x, dragged.y);
+    }
+    transform.translation += (pan * projection.scale).extend(0f32);
+
+    let mut zoom = wheel.read().map(|wheel| wheel.y.signum()).sum::<f32>();
+    if keyboard_input.any_just_pressed([KeyCode::Equal, KeyCode::NumpadAdd]) {
+        zoom += 1f32;
+    }
+    if keyboard_input.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        zoom -= 1f32;
+    }
+    if zoom != 0f32 {
+        projection.scale = (projection.scale / ZOOM_STEP.powf(zoom)).clamp(MIN_ZOOM_SCALE, MAX_ZOOM_SCALE);
+    }
+    // The help sits on the camera, so it's kept the same size on screen however far in the camera is.
+    for mut help in helps.iter_mut() {
+        help.scale = Vec3::new(projection.scale, projection.scale, 1f32);
+    }
+}
+
+/// H hides the HUD, T steps through the filters, Space saves a capture and Esc goes back to the pause menu.
+pub fn edit_photo_mode(
+    mut cmd: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    arena: Res<Arena>,
+    mut photo: ResMut<PhotoMode>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    screenshots: Option<ResMut<ScreenshotManager>>,
+    windows: Query<Entity, With<PrimaryWindow>>,
+    mut cameras: Query<(&mut Transform, &mut OrthographicProjection), With<Camera2d>>,
+    mut overlays: Query<(Entity, &mut Handle<ColorMaterial>, &mut Visibility), With<PhotoOverlay>>,
+    mut helps: Query<(Entity, &mut Visibility), (With<PhotoHelp>, Without<PhotoOverlay>)>,
+    mut hud: Query<
+        (Entity, &mut Visibility),
+        (With<Text>, Without<PhotoHelp>, Without<PhotoOverlay>, Without<PauseMenu>),
+    >,
+    mut menus: Query<&mut Visibility, (With<PauseMenu>, Without<PhotoHelp>, Without<PhotoOverlay>)>,
+) {
+    let pressed = |key| keyboard_input.just_pressed(key);
+    if pressed(KeyCode::Escape) {
+        for (entity, visibility) in photo.hud_hidden.drain(..) {
+            if let Ok((_, mut hidden)) = hud.get_mut(entity) {
+                *hidden = visibility;
+            }
+        }
+        if let Ok((mut transform, mut projection)) = cameras.get_single_mut() {
+            (transform.translation, projection.scale) = photo.home;
+        }
+        for (overlay, ..) in overlays.iter() {
+            cmd.entity(overlay).despawn_recursive();
+        }
+        for (help, _) in helps.iter() {
+            cmd.entity(help).despawn_recursive();
+        }
+        for mut menu in menus.iter_mut() {
+            *menu = Visibility::Inherited;
+        }
+        cmd.remove_resource::<PhotoMode>();
+        return;
+    }
+
+    if pressed(KeyCode::KeyH) {
+        if photo.hud_hidden.is_empty() {
+            for (entity, mut visibility) in hud.iter_mut() {
+                photo.hud_hidden.push((entity, *visibility));
+                *visibility = Visibility::Hidden;
+            }
+        }
+        else {
+            for (entity, visibility) in photo.hud_hidden.drain(..) {
+                if let Ok((_, mut hidden)) = hud.get_mut(entity) {
+                    *hidden = visibility;
+                }
+            }
+        }
+    }
+    if pressed(KeyCode::KeyT) {
+        photo.filter = photo.filter.next();
+        for (_, mut material, mut visibility) in overlays.iter_mut() {
+            match photo.filter.tint() {
+                Some(tint) => {
+                    *material = materials.add(tint);
+                    *visibility = Visibility::Inherited;
+                },
+                None => *visibility = Visibility::Hidden,
+            }
+        }
+    }
+
+    // The help is left out of the capture, then shown again once the frame it was hidden for has been saved.
+    let capture = pressed(KeyCode::Space);
+    let help_shown = if capture { Visibility::Hidden } else { Visibility::Inherited };
+    if capture || photo.capturing {
+        for (_, mut visibility) in helps.iter_mut() {
+            *visibility = help_shown;
+        }
+    }
+    photo.capturing = capture;
+    if !capture {
+        return;
+    }
+    let (Some(mut screenshots), Ok(window)) = (screenshots, windows.get_single()) else {
+        return;
+    };
+    save_screenshot(&mut cmd, &arena, &mut screenshots, window, "photo");
+}
@@ -0,0 +1,796 @@
+use bevy::math::Vec2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub center: Vec2,
+    pub half_size: Vec2,
+}
+
+impl Aabb {
+    pub fn new(center: Vec2, half_size: Vec2) -> Self {
+        Aabb { center, half_size }
+    }
+
+    pub fn min(&self) -> Vec2 {
+        self.center - self.half_size
+    }
+
+    pub fn max(&self) -> Vec2 {
+        self.center + self.half_size
+    }
+
+    /// Minkowski sum with a box of `half_size`, so a moving box can be treated as a point.
+    pub fn expand(&self, half_size: Vec2) -> Aabb {
+        Aabb::new(self.center, self.half_size + half_size)
+    }
+}
+
+pub fn overlaps(pos: Vec2, half_size: Vec2, target: Aabb) -> bool {
+    let expanded = target.expand(half_size);
+    let min = expanded.min();
+    let max = expanded.max();
+    pos.x > min.x && pos.x < max.x && pos.y > min.y && pos.y < max.y
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contact {
+    /// Fraction of the move from `prev` to `pos` at which the faces touched.
+    pub toi: f32,
+    pub normal: Vec2,
+}
+
+/// Finds the face of `target` that a box of `half_size` crossed while moving
+/// from `prev` to `pos`, if any.
+pub fn sweep(prev: Vec2, pos: Vec2, half_size: Vec2, target: Aabb) -> Option<Contact> {
+    let expanded = target.expand(half_size);
+    let min = expanded.min();
+    let max = expanded.max();
+    let delta = pos - prev;
+
+    if pos.y >= min.y && pos.y <= max.y {
+        if prev.x >= max.x && pos.x < max.x {
+            return Some(Contact { toi: (max.x - prev.x)/delta.x, normal: Vec2::X });
+        }
+        if prev.x <= min.x && pos.x > min.x {
+            return Some(Contact { toi: (min.x - prev.x)/delta.x, normal: Vec2::NEG_X });
+        }
+    }
+    if pos.x >= min.x && pos.x <= max.x {
+        if prev.y >= max.y && pos.y < max.y {
+            return Some(Contact { toi: (max.y - prev.y)/delta.y, normal: Vec2::Y });
+        }
+        if prev.y <= min.y && pos.y > min.y {
+            return Some(Contact { toi: (min.y - prev.y)/delta.y, normal: Vec2::NEG_Y });
+        }
+    }
+    None
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Body {
+    pub pos: Vec2,
+    pub vel: Vec2,
+}
+
+/// Elastic collision between two equal-mass boxes of `half_size`. Returns the
+/// separated bodies and how long ago they first touched, or `None` if they
+/// aren't overlapping and closing.
+pub fn collide_boxes(a: Body, b: Body, half_size: Vec2) -> Option<(Body, Body, f32)> {
+    let delta = b.pos - a.pos;
+    let overlap = half_size * 2f32 - delta.abs();
+    if overlap.x <= 0f32 || overlap.y <= 0f32 {
+        return None;
+    }
+
+    let (normal, depth) = if overlap.x < overlap.y {
+        (Vec2::new(delta.x.signum(), 0f32), overlap.x)
+    }
+    else {
+        (Vec2::new(0f32, delta.y.signum()), overlap.y)
+    };
+    let closing_speed = (a.vel - b.vel).dot(normal);
+    if closing_speed <= 0f32 {
+        return None;
+    }
+
+    let a_normal = a.vel.dot(normal);
+    let b_normal = b.vel.dot(normal);
+    Some((
+        Body {
+            pos: a.pos - normal * depth/2f32,
+            vel: a.vel + (b_normal - a_normal) * normal,
+        },
+        Body {
+            pos: b.pos + normal * depth/2f32,
+            vel: b.vel + (a_normal - b_normal) * normal,
+        },
+        depth/closing_speed,
+    ))
+}
+
+/// Resolves every pair of `bodies` that `collide_boxes` would, the pair that has been touching the longest first.
+/// Pairs `interacts` turns down, by index, are left alone.
+pub fn collide_all(bodies: &mut [Body], half_size: Vec2, interacts: impl Fn(usize, usize) -> bool) {
+    let mut pairs = Vec::new();
+    for a in 0..bodies.len() {
+        for b in a + 1..bodies.len() {
+            if !interacts(a, b) {
+                continue;
+            }
+            if let Some((_, _, since)) = collide_boxes(bodies[a], bodies[b], half_size) {
+                pairs.push((a, b, since));
+            }
+        }
+    }
+
+    pairs.sort_by(|(_, _, a), (_, _, b)| b.total_cmp(a));
+    for (a, b, _) in pairs {
+        // An earlier pair may have already pushed these two apart.
+        if let Some((a_body, b_body, _)) = collide_boxes(bodies[a], bodies[b], half_size) {
+            bodies[a] = a_body;
+            bodies[b] = b_body;
+        }
+    }
+}
+
+/// `Vec2::from_angle` through libm, so the result doesn't depend on the platform's trig.
+pub fn from_angle(angle: f32) -> Vec2 {
+    Vec2::new(libm::cosf(angle), libm::sinf(angle))
+}
+
+pub fn reflect(vel: Vec2, normal: Vec2) -> Vec2 {
+    vel - 2f32 * vel.dot(normal) * normal
+}
+
+/// Reflects off a paddle face, steering the ball by up to `max_angle` depending on
+/// where it hit (`offset` is -1 at the bottom edge and 1 at the top edge).
+pub fn paddle_bounce(vel: Vec2, normal_x: f32, offset: f32, max_angle: f32, min_horizontal_ratio: f32) -> Vec2 {
+    let reflected = Vec2::new(-vel.x, vel.y);
+    let rotated = from_angle(max_angle * offset).rotate(reflected);
+    enforce_min_horizontal_speed(rotated, normal_x, min_horizontal_ratio)
+}
+
+/// Keeps at least `ratio` of the speed on the x axis, heading in `dir_x`.
+pub fn enforce_min_horizontal_speed(vel: Vec2, dir_x: f32, ratio: f32) -> Vec2 {
+    let speed = vel.length();
+    let min_x = speed * ratio;
+    if vel.x * dir_x >= min_x {
+        return vel;
+    }
+    let y = (speed * speed - min_x * min_x).sqrt();
+    Vec2::new(dir_x * min_x, if vel.y < 0f32 { -y } else { y })
+}
+
+/// Scales the normal part of a post-bounce velocity by `restitution` and the
+/// tangential part by `1 - friction`.
+pub fn apply_surface(vel: Vec2, normal: Vec2, restitution: f32, friction: f32) -> Vec2 {
+    let normal_vel = vel.dot(normal) * normal;
+    let tangent_vel = vel - normal_vel;
+    normal_vel * restitution + tangent_vel * (1f32 - friction)
+}
+
+/// Moves `pos` back onto the face of `target` given by `normal`, plus `epsilon`.
+pub fn resolve_penetration(pos: Vec2, normal: Vec2, half_size: Vec2, target: Aabb, epsilon: f32) -> Vec2 {
+    let extents = target.half_size + half_size + epsilon;
+    if normal.x != 0f32 {
+        Vec2::new(target.center.x + normal.x * extents.x, pos.y)
+    }
+    else {
+        Vec2::new(pos.x, target.center.y + normal.y * extents.y)
+    }
+}
+
+/// Accelerates `vel` perpendicular to itself by `spin * coefficient`, curving the path.
+pub fn magnus(vel: Vec2, spin: f32, coefficient: f32, dt: f32) -> Vec2 {
+    vel + vel.perp() * spin * coefficient * dt
+}
+
+pub fn decay_spin(spin: f32, decay: f32, dt: f32) -> f32 {
+    spin * libm::expf(-decay * dt)
+}
+
+/// Accelerates toward `dir * max_speed`, or slows to a stop by `friction` when `dir` is zero.
+pub fn paddle_velocity(vel: f32, dir: f32, accel: f32, max_speed: f32, friction: f32, dt: f32) -> f32 {
+    if dir != 0f32 {
+        (vel + dir * accel * dt).clamp(-max_speed, max_speed)
+    }
+    else {
+        vel.signum() * (vel.abs() - friction * dt).max(0f32)
+    }
+}
+
+/// Which way to push a sliding paddle toward `offset` away: on until pushing the other way at `accel` would only
+/// just stop it there, then back against its motion.
+pub fn steer_to(offset: f32, vel: f32, accel: f32) -> i32 {
+    let braking_distance = vel * vel / (2f32 * accel);
+    if vel * offset > 0f32 && braking_distance >= offset.abs() {
+        -vel.signum() as i32
+    }
+    else {
+        offset.signum() as i32
+    }
+}
+
+pub fn substeps(vel: Vec2, dt: f32, max_step: f32) -> u32 {
+    (vel.length() * dt / max_step).ceil().max(1f32) as u32
+}
+
+/// Folds `y` back into `-half_extent..=half_extent`, where a ball headed for it would end up after bouncing
+/// between walls at those heights.
+pub fn fold_between_walls(y: f32, half_extent: f32) -> f32 {
+    if half_extent <= 0f32 {
+        return 0f32;
+    }
+    let t = (y + half_extent).rem_euclid(4f32 * half_extent);
+    if t <= 2f32 * half_extent { t - half_extent } else { 3f32 * half_extent - t }
+}
+
+/// A ball as `step_ball` moves it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BallBody {
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub speed: f32,
+    pub spin: f32,
+}
+
+/// How `step_ball` moves a ball.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BallTuning {
+    pub half_size: Vec2,
+    pub acceleration: f32,
+    pub max_speed: f32,
+    /// Vertical acceleration, wind less gravity.
+    pub lift: f32,
+    pub magnus_coefficient: f32,
+    pub spin_decay: f32,
+    pub spin_per_hit: f32,
+    pub max_step: f32,
+    pub max_angle: f32,
+    pub min_horizontal_ratio: f32,
+    pub epsilon: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ObstacleKind {
+    /// Anything solid that isn't a paddle.
+    Wall,
+    /// Puts spin on the ball along `dir`, the way the paddle is moving.
+    Paddle { dir: f32 },
+    /// Passed through, but reports the ball entering it.
+    Trigger,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Obstacle {
+    pub aabb: Aabb,
+    pub kind: ObstacleKind,
+    pub restitution: f32,
+    pub friction: f32,
+}
+
+/// What happened to the ball during `step_ball`, by index into its obstacles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BallEvent {
+    Bounced(usize),
+    Entered(usize),
+}
+
+/// One fixed tick of the ball: speeds it up, then moves it in substeps short enough not to tunnel, curving it
+/// by its spin and `lift` and bouncing it off the earliest obstacle it sweeps into each substep.
+pub fn step_ball(ball: &mut BallBody, obstacles: &[Obstacle], tuning: &BallTuning, dt: f32, events: &mut Vec<BallEvent>) {
+    ball.speed = (ball.speed + tuning.acceleration * dt).min(tuning.max_speed);
+    ball.vel = ball.vel.normalize_or_zero() * ball.speed;
+
+    let steps = substeps(ball.vel, dt, tuning.max_step);
+    let step_dt = dt / steps as f32;
+    for _ in 0..steps {
+        ball.vel = magnus(ball.vel, ball.spin, tuning.magnus_coefficient, step_dt);
+        ball.vel.y += tuning.lift * step_dt;
+        ball.spin = decay_spin(ball.spin, tuning.spin_decay, step_dt);
+
+        let prev = ball.pos;
+        ball.pos += ball.vel * step_dt;
+
+        let mut earliest: Option<(Contact, usize)> = None;
+        for (i, obstacle) in obstacles.iter().enumerate() {
+            if obstacle.kind == ObstacleKind::Trigger {
+                if overlaps(ball.pos, tuning.half_size, obstacle.aabb) && !overlaps(prev, tuning.half_size, obstacle.aabb) {
+                    events.push(BallEvent::Entered(i));
+                }
+                continue;
+            }
+            let Some(contact) = sweep(prev, ball.pos, tuning.half_size, obstacle.aabb) else {
+                continue;
+            };
+            if earliest.is_none_or(|(first, _)| contact.toi < first.toi) {
+                earliest = Some((contact, i));
+            }
+        }
+
+        // Only the earliest contact is resolved; the bounce invalidates any later ones.
+        let Some((contact, i)) = earliest else {
+            continue;
+        };
+        let obstacle = &obstacles[i];
+        let normal = contact.normal;
+        let contact_pos = prev.lerp(ball.pos, contact.toi);
+
+        match obstacle.kind {
+            ObstacleKind::Paddle { dir } if normal.x != 0f32 => {
+                ball.spin = dir * normal.x * tuning.spin_per_hit;
+                let offset = (contact_pos.y - obstacle.aabb.center.y)/obstacle.aabb.half_size.y;
+                ball.vel = paddle_bounce(ball.vel, normal.x, offset, tuning.max_angle, tuning.min_horizontal_ratio);
+            },
+            _ => ball.vel = reflect(ball.vel, normal),
+        }
+        ball.vel = apply_surface(ball.vel, normal, obstacle.restitution, obstacle.friction);
+        ball.speed = ball.vel.length().min(tuning.max_speed);
+        ball.pos = resolve_penetration(ball.pos, normal, tuning.half_size, obstacle.aabb, tuning.epsilon);
+        events.push(BallEvent::Bounced(i));
+    }
+}
+
+/// How many `dt` steps `predict_intercept` looks ahead before giving up on a ball that won't arrive.
+pub const MAX_PREDICTION_STEPS: u32 = 1024;
+
+/// Where `body` will be in y once it reaches `target_x`, integrating its spin, the spin's decay and a constant
+/// vertical `accel` (wind less gravity) the same way the ball moves. Walls are left to `fold_between_walls`.
+/// `None` if the ball is heading away or curls back before arriving.
+pub fn predict_intercept(
+    body: Body,
+    spin: f32,
+    accel: f32,
+    target_x: f32,
+    coefficient: f32,
+    decay: f32,
+    dt: f32,
+) -> Option<f32> {
+    let Body { mut pos, mut vel } = body;
+    let mut spin = spin;
+    for _ in 0..MAX_PREDICTION_STEPS {
+        if (target_x - pos.x) * vel.x <= 0f32 {
+            return None;
+        }
+        vel = magnus(vel, spin, coefficient, dt);
+        vel.y += accel * dt;
+        spin = decay_spin(spin, decay, dt);
+
+        let next = pos + vel * dt;
+        if (target_x - next.x) * (target_x - pos.x) <= 0f32 {
+            let t = (target_x - pos.x) / (next.x - pos.x);
+            return Some(pos.y + (next.y - pos.y) * t);
+        }
+        pos = next;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    const EPSILON: f32 = 1e-4;
+    const BALL: Vec2 = Vec2::new(4f32, 4f32);
+    const PADDLE: Vec2 = Vec2::new(4f32, 32f32);
+
+    fn approx_eq(a: Vec2, b: Vec2) -> bool {
+        (a - b).length() < EPSILON
+    }
+
+    #[test]
+    fn aabb_expand_grows_half_size() {
+        let aabb = Aabb::new(Vec2::new(1f32, 2f32), Vec2::new(3f32, 4f32)).expand(BALL);
+        assert_eq!(aabb.min(), Vec2::new(-6f32, -6f32));
+        assert_eq!(aabb.max(), Vec2::new(8f32, 10f32));
+    }
+
+    #[test]
+    fn overlaps_accounts_for_half_size() {
+        let zone = Aabb::new(Vec2::ZERO, Vec2::new(16f32, 16f32));
+        assert!(overlaps(Vec2::new(19f32, 0f32), BALL, zone));
+        assert!(!overlaps(Vec2::new(20f32, 0f32), BALL, zone));
+        assert!(!overlaps(Vec2::new(0f32, -21f32), BALL, zone));
+    }
+
+    #[test]
+    fn sweep_hits_right_face() {
+        let paddle = Aabb::new(Vec2::ZERO, PADDLE);
+        let contact = sweep(Vec2::new(20f32, 0f32), Vec2::new(6f32, 0f32), BALL, paddle).unwrap();
+        assert_eq!(contact.normal, Vec2::X);
+        assert!((contact.toi - 12f32/14f32).abs() < EPSILON);
+    }
+
+    #[test]
+    fn sweep_hits_left_face() {
+        let paddle = Aabb::new(Vec2::ZERO, PADDLE);
+        let contact = sweep(Vec2::new(-20f32, 10f32), Vec2::new(-6f32, 10f32), BALL, paddle).unwrap();
+        assert_eq!(contact.normal, Vec2::NEG_X);
+    }
+
+    #[test]
+    fn sweep_hits_top_and_bottom_faces() {
+        let wall = Aabb::new(Vec2::ZERO, Vec2::new(64f32, 8f32));
+        let top = sweep(Vec2::new(0f32, 20f32), Vec2::new(0f32, 10f32), BALL, wall);
+        let bottom = sweep(Vec2::new(0f32, -20f32), Vec2::new(0f32, -10f32), BALL, wall);
+        assert_eq!(top.map(|c| c.normal), Some(Vec2::Y));
+        assert_eq!(bottom.map(|c| c.normal), Some(Vec2::NEG_Y));
+    }
+
+    #[test]
+    fn sweep_misses_when_outside_span() {
+        let paddle = Aabb::new(Vec2::ZERO, PADDLE);
+        assert_eq!(sweep(Vec2::new(20f32, 50f32), Vec2::new(6f32, 50f32), BALL, paddle), None);
+    }
+
+    #[test]
+    fn sweep_ignores_moving_away() {
+        let paddle = Aabb::new(Vec2::ZERO, PADDLE);
+        assert_eq!(sweep(Vec2::new(6f32, 0f32), Vec2::new(20f32, 0f32), BALL, paddle), None);
+    }
+
+    #[test]
+    fn sweep_catches_tunneling() {
+        let paddle = Aabb::new(Vec2::ZERO, PADDLE);
+        let contact = sweep(Vec2::new(20f32, 0f32), Vec2::new(-20f32, 0f32), BALL, paddle).unwrap();
+        assert_eq!(contact.normal, Vec2::X);
+        assert!((contact.toi - 0.3f32).abs() < EPSILON);
+    }
+
+    #[test]
+    fn from_angle_matches_unit_circle() {
+        assert!(approx_eq(from_angle(0f32), Vec2::X));
+        assert!(approx_eq(from_angle(PI/2f32), Vec2::Y));
+        assert!(approx_eq(from_angle(-PI/2f32), Vec2::NEG_Y));
+    }
+
+    #[test]
+    fn collide_boxes_exchanges_normal_velocity() {
+        let a = Body { pos: Vec2::new(-3f32, 0f32), vel: Vec2::new(100f32, 10f32) };
+        let b = Body { pos: Vec2::new(3f32, 1f32), vel: Vec2::new(-50f32, 0f32) };
+        let (a, b, since) = collide_boxes(a, b, BALL).unwrap();
+        assert!(approx_eq(a.vel, Vec2::new(-50f32, 10f32)));
+        assert!(approx_eq(b.vel, Vec2::new(100f32, 0f32)));
+        assert!(approx_eq(b.pos - a.pos, Vec2::new(8f32, 1f32)));
+        assert!((since - 2f32/150f32).abs() < EPSILON);
+    }
+
+    #[test]
+    fn collide_boxes_ignores_separating_or_apart() {
+        let a = Body { pos: Vec2::new(-3f32, 0f32), vel: Vec2::new(-100f32, 0f32) };
+        let b = Body { pos: Vec2::new(3f32, 0f32), vel: Vec2::new(100f32, 0f32) };
+        assert!(collide_boxes(a, b, BALL).is_none());
+
+        let far = Body { pos: Vec2::new(30f32, 0f32), vel: Vec2::new(-100f32, 0f32) };
+        assert!(collide_boxes(a, far, BALL).is_none());
+    }
+
+    #[test]
+    fn collide_all_resolves_the_oldest_contact_first() {
+        // The right pair has been touching longer, so it bounces first and the middle ball comes back at the left one
+        // faster than if the left pair had gone first.
+        let mut bodies = [
+            Body { pos: Vec2::new(0f32, 0f32), vel: Vec2::new(10f32, 0f32) },
+            Body { pos: Vec2::new(7f32, 0f32), vel: Vec2::new(-10f32, 0f32) },
+            Body { pos: Vec2::new(11f32, 0f32), vel: Vec2::new(-30f32, 0f32) },
+        ];
+        collide_all(&mut bodies, BALL, |_, _| true);
+        assert_eq!(bodies.map(|body| body.vel.x), [-30f32, 10f32, -10f32]);
+    }
+
+    #[test]
+    fn collide_all_skips_pairs_that_dont_interact() {
+        let mut bodies = [
+            Body { pos: Vec2::new(0f32, 0f32), vel: Vec2::new(10f32, 0f32) },
+            Body { pos: Vec2::new(4f32, 0f32), vel: Vec2::new(-10f32, 0f32) },
+        ];
+        let before = bodies;
+        collide_all(&mut bodies, BALL, |_, _| false);
+        assert_eq!(bodies, before);
+    }
+
+    #[test]
+    fn reflect_flips_normal_component() {
+        let vel = Vec2::new(3f32, -4f32);
+        assert_eq!(reflect(vel, Vec2::Y), Vec2::new(3f32, 4f32));
+        assert_eq!(reflect(vel, Vec2::NEG_X), Vec2::new(-3f32, -4f32));
+    }
+
+    #[test]
+    fn paddle_bounce_center_hit_is_straight() {
+        let vel = paddle_bounce(Vec2::new(-100f32, 0f32), 1f32, 0f32, PI/4f32, 0.5f32);
+        assert!(approx_eq(vel, Vec2::new(100f32, 0f32)));
+    }
+
+    #[test]
+    fn paddle_bounce_edge_hit_is_angled() {
+        let vel = paddle_bounce(Vec2::new(-100f32, 0f32), 1f32, 1f32, PI/4f32, 0.5f32);
+        let expected = from_angle(PI/4f32) * 100f32;
+        assert!(approx_eq(vel, expected));
+    }
+
+    #[test]
+    fn paddle_bounce_preserves_speed() {
+        let vel = Vec2::new(-90f32, 60f32);
+        let out = paddle_bounce(vel, 1f32, 0.7f32, PI/4f32, 0.5f32);
+        assert!((out.length() - vel.length()).abs() < EPSILON);
+        assert!(out.x > 0f32);
+    }
+
+    #[test]
+    fn min_horizontal_speed_is_enforced() {
+        let before = Vec2::new(1f32, 100f32);
+        let vel = enforce_min_horizontal_speed(before, 1f32, 0.5f32);
+        assert!((vel.x - before.length() * 0.5f32).abs() < EPSILON);
+        assert!((vel.length() - before.length()).abs() < EPSILON);
+        assert!(vel.y > 0f32);
+    }
+
+    #[test]
+    fn min_horizontal_speed_fixes_wrong_direction() {
+        let vel = enforce_min_horizontal_speed(Vec2::new(-10f32, -100f32), 1f32, 0.5f32);
+        assert!(vel.x > 0f32);
+        assert!(vel.y < 0f32);
+    }
+
+    #[test]
+    fn min_horizontal_speed_leaves_fast_balls_alone() {
+        let vel = Vec2::new(-80f32, 20f32);
+        assert_eq!(enforce_min_horizontal_speed(vel, -1f32, 0.5f32), vel);
+    }
+
+    #[test]
+    fn apply_surface_scales_components() {
+        let vel = apply_surface(Vec2::new(10f32, 20f32), Vec2::Y, 0.5f32, 0.1f32);
+        assert!(approx_eq(vel, Vec2::new(9f32, 10f32)));
+    }
+
+    #[test]
+    fn apply_surface_neutral_is_identity() {
+        let vel = Vec2::new(-30f32, 45f32);
+        assert!(approx_eq(apply_surface(vel, Vec2::NEG_Y, 1f32, 0f32), vel));
+    }
+
+    #[test]
+    fn resolve_penetration_moves_onto_face() {
+        let paddle = Aabb::new(Vec2::new(10f32, 0f32), PADDLE);
+        let pos = resolve_penetration(Vec2::new(15f32, 3f32), Vec2::X, BALL, paddle, 0.01f32);
+        assert!(approx_eq(pos, Vec2::new(18.01f32, 3f32)));
+
+        let pos = resolve_penetration(Vec2::new(12f32, -30f32), Vec2::NEG_Y, BALL, paddle, 0.01f32);
+        assert!(approx_eq(pos, Vec2::new(12f32, -36.01f32)));
+    }
+
+    #[test]
+    fn magnus_curves_toward_spin() {
+        let vel = Vec2::new(100f32, 0f32);
+        assert!(magnus(vel, 1f32, 1f32, 0.1f32).y > 0f32);
+        assert!(magnus(vel, -1f32, 1f32, 0.1f32).y < 0f32);
+        assert_eq!(magnus(vel, 0f32, 1f32, 0.1f32), vel);
+    }
+
+    const TUNING: BallTuning = BallTuning {
+        half_size: BALL,
+        acceleration: 0f32,
+        max_speed: 512f32,
+        lift: 0f32,
+        magnus_coefficient: 0.5f32,
+        spin_decay: 0.5f32,
+        spin_per_hit: 2f32,
+        max_step: 8f32,
+        max_angle: PI/4f32,
+        min_horizontal_ratio: 0.5f32,
+        epsilon: 0.01f32,
+    };
+
+    #[test]
+    fn step_ball_bounces_off_the_earliest_obstacle() {
+        let wall = Obstacle {
+            aabb: Aabb::new(Vec2::new(20f32, 0f32), Vec2::new(4f32, 64f32)),
+            kind: ObstacleKind::Wall,
+            restitution: 1f32,
+            friction: 0f32,
+        };
+        let behind = Obstacle { aabb: Aabb::new(Vec2::new(28f32, 0f32), Vec2::new(4f32, 64f32)), ..wall };
+        let mut ball = BallBody { pos: Vec2::ZERO, vel: Vec2::new(256f32, 0f32), speed: 256f32, spin: 0f32 };
+        let mut events = Vec::new();
+        step_ball(&mut ball, &[behind, wall], &TUNING, 0.1f32, &mut events);
+        assert_eq!(events, vec![BallEvent::Bounced(1)]);
+        assert!(ball.vel.x < 0f32);
+        assert!(ball.pos.x <= 12f32);
+    }
+
+    #[test]
+    fn step_ball_spins_off_a_moving_paddle() {
+        let paddle = Obstacle {
+            aabb: Aabb::new(Vec2::new(20f32, 0f32), PADDLE),
+            kind: ObstacleKind::Paddle { dir: 1f32 },
+            restitution: 1f32,
+            friction: 0f32,
+        };
+        let mut ball = BallBody { pos: Vec2::ZERO, vel: Vec2::new(256f32, 0f32), speed: 256f32, spin: 0f32 };
+        let mut events = Vec::new();
+        step_ball(&mut ball, &[paddle], &TUNING, 0.05f32, &mut events);
+        assert_eq!(events, vec![BallEvent::Bounced(0)]);
+        assert!(ball.spin != 0f32);
+    }
+
+    #[test]
+    fn step_ball_passes_through_triggers() {
+        let goal = Obstacle {
+            aabb: Aabb::new(Vec2::new(20f32, 0f32), Vec2::new(4f32, 64f32)),
+            kind: ObstacleKind::Trigger,
+            restitution: 1f32,
+            friction: 0f32,
+        };
+        let mut ball = BallBody { pos: Vec2::ZERO, vel: Vec2::new(256f32, 0f32), speed: 256f32, spin: 0f32 };
+        let mut events = Vec::new();
+        step_ball(&mut ball, &[goal], &TUNING, 0.1f32, &mut events);
+        assert_eq!(events, vec![BallEvent::Entered(0)]);
+        assert!(ball.vel.x > 0f32);
+    }
+
+    #[test]
+    fn straight_ball_intercepts_on_its_line() {
+        let body = Body { pos: Vec2::ZERO, vel: Vec2::new(100f32, 50f32) };
+        let y = predict_intercept(body, 0f32, 0f32, 200f32, 1f32, 0f32, 1f32 / 64f32).unwrap();
+        assert!((y - 100f32).abs() < EPSILON);
+    }
+
+    #[test]
+    fn spinning_ball_intercepts_off_its_line() {
+        let body = Body { pos: Vec2::ZERO, vel: Vec2::new(100f32, 0f32) };
+        let dt = 1f32 / 64f32;
+        let straight = predict_intercept(body, 0f32, 0f32, 200f32, 0.5f32, 0.5f32, dt).unwrap();
+        let topspin = predict_intercept(body, 2f32, 0f32, 200f32, 0.5f32, 0.5f32, dt).unwrap();
+        let backspin = predict_intercept(body, -2f32, 0f32, 200f32, 0.5f32, 0.5f32, dt).unwrap();
+        assert!(topspin > straight + 1f32);
+        assert!(backspin < straight - 1f32);
+    }
+
+    #[test]
+    fn fold_between_walls_reflects_off_each_wall() {
+        assert_eq!(fold_between_walls(50f32, 100f32), 50f32);
+        assert_eq!(fold_between_walls(130f32, 100f32), 70f32);
+        assert_eq!(fold_between_walls(-130f32, 100f32), -70f32);
+        assert_eq!(fold_between_walls(430f32, 100f32), 30f32);
+    }
+
+    #[test]
+    fn angled_shot_intercepts_after_one_bounce() {
+        let body = Body { pos: Vec2::ZERO, vel: Vec2::new(100f32, 100f32) };
+        let y = predict_intercept(body, 0f32, 0f32, 200f32, 1f32, 0f32, 1f32 / 64f32).unwrap();
+        assert!((fold_between_walls(y, 150f32) - 100f32).abs() < EPSILON);
+    }
+
+    #[test]
+    fn receding_ball_has_no_intercept() {
+        let body = Body { pos: Vec2::ZERO, vel: Vec2::new(-100f32, 0f32) };
+        assert_eq!(predict_intercept(body, 0f32, 0f32, 200f32, 1f32, 0f32, 1f32 / 64f32), None);
+    }
+
+    #[test]
+    fn spin_decays_toward_zero() {
+        let spin = decay_spin(2f32, 1f32, 0.5f32);
+        assert!(spin > 0f32 && spin < 2f32);
+        assert!(decay_spin(-2f32, 1f32, 0.5f32) > -2f32);
+    }
+
+    #[test]
+    fn paddle_velocity_accelerates_to_max() {
+        assert_eq!(paddle_velocity(0f32, 1f32, 100f32, 50f32, 10f32, 0.1f32), 10f32);
+        assert_eq!(paddle_velocity(45f32, 1f32, 100f32, 50f32, 10f32, 0.1f32), 50f32);
+        assert_eq!(paddle_velocity(-45f32, -1f32, 100f32, 50f32, 10f32, 0.1f32), -50f32);
+    }
+
+    #[test]
+    fn paddle_velocity_stops_without_reversing() {
+        assert_eq!(paddle_velocity(20f32, 0f32, 100f32, 50f32, 100f32, 0.1f32), 10f32);
+        assert_eq!(paddle_velocity(5f32, 0f32, 100f32, 50f32, 100f32, 0.1f32), 0f32);
+        assert_eq!(paddle_velocity(-5f32, 0f32, 100f32, 50f32, 100f32, 0.1f32), 0f32);
+    }
+
+    #[test]
+    fn steering_brakes_before_the_target() {
+        assert_eq!(steer_to(100f32, 0f32, 100f32), 1);
+        assert_eq!(steer_to(100f32, 100f32, 100f32), 1);
+        assert_eq!(steer_to(40f32, 100f32, 100f32), -1);
+        assert_eq!(steer_to(-40f32, 100f32, 100f32), -1);
+    }
+
+    #[test]
+    fn substeps_split_long_moves() {
+        assert_eq!(substeps(Vec2::new(100f32, 0f32), 0.01f32, 8f32), 1);
+        assert_eq!(substeps(Vec2::new(1000f32, 0f32), 0.1f32, 8f32), 13);
+        assert_eq!(substeps(Vec2::ZERO, 0.1f32, 8f32), 1);
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::f32::consts::PI;
+
+    const MAX_ANGLE: f32 = PI/4f32;
+    const MIN_HORIZONTAL_RATIO: f32 = 0.5f32;
+    const BALL: Vec2 = Vec2::new(4f32, 4f32);
+    const PADDLE: Vec2 = Vec2::new(4f32, 32f32);
+
+    fn vec2(range: f32) -> impl Strategy<Value = Vec2> {
+        (-range..range, -range..range).prop_map(|(x, y)| Vec2::new(x, y))
+    }
+
+    fn axis() -> impl Strategy<Value = Vec2> {
+        prop_oneof![Just(Vec2::X), Just(Vec2::NEG_X), Just(Vec2::Y), Just(Vec2::NEG_Y)]
+    }
+
+    fn side() -> impl Strategy<Value = f32> {
+        prop_oneof![Just(1f32), Just(-1f32)]
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() <= 1e-3 * a.abs().max(b.abs()).max(1f32)
+    }
+
+    proptest! {
+        #[test]
+        fn reflect_preserves_speed(vel in vec2(1000f32), normal in axis()) {
+            prop_assert!(close(reflect(vel, normal).length(), vel.length()));
+        }
+
+        #[test]
+        fn paddle_bounce_preserves_speed(vel in vec2(1000f32), normal_x in side(), offset in -1f32..=1f32) {
+            prop_assume!(vel.length() > 1f32);
+            let out = paddle_bounce(vel, normal_x, offset, MAX_ANGLE, MIN_HORIZONTAL_RATIO);
+            prop_assert!(close(out.length(), vel.length()));
+        }
+
+        #[test]
+        fn paddle_bounce_leaves_paddle_with_bounded_angle(
+            vel in vec2(1000f32),
+            normal_x in side(),
+            offset in -1f32..=1f32,
+        ) {
+            prop_assume!(vel.length() > 1f32);
+            let out = paddle_bounce(vel, normal_x, offset, MAX_ANGLE, MIN_HORIZONTAL_RATIO);
+            prop_assert!(out.x * normal_x > 0f32);
+            let max_vertical = (1f32 - MIN_HORIZONTAL_RATIO * MIN_HORIZONTAL_RATIO).sqrt();
+            prop_assert!(out.y.abs() <= out.length() * max_vertical + 1e-3);
+        }
+
+        #[test]
+        fn head_on_bounce_stays_within_max_angle(
+            speed in 1f32..1000f32,
+            normal_x in side(),
+            offset in -1f32..=1f32,
+        ) {
+            let out = paddle_bounce(Vec2::new(-normal_x * speed, 0f32), normal_x, offset, MAX_ANGLE, MIN_HORIZONTAL_RATIO);
+            let angle = libm::atan2f(out.y, out.x * normal_x);
+            prop_assert!(angle.abs() <= MAX_ANGLE + 1e-4);
+        }
+
+        #[test]
+        fn min_horizontal_speed_preserves_speed(vel in vec2(1000f32), dir_x in side()) {
+            let out = enforce_min_horizontal_speed(vel, dir_x, MIN_HORIZONTAL_RATIO);
+            prop_assert!(close(out.length(), vel.length()));
+            prop_assert!(out.x * dir_x >= out.length() * MIN_HORIZONTAL_RATIO - 1e-3);
+        }
+
+        #[test]
+        fn ball_never_ends_inside_paddle(
+            prev in vec2(64f32),
+            pos in vec2(64f32),
+            center in vec2(16f32),
+        ) {
+            let paddle = Aabb::new(center, PADDLE);
+            prop_assume!(!overlaps(prev, BALL, paddle));
+            if let Some(contact) = sweep(prev, pos, BALL, paddle) {
+                prop_assert!((0f32..=1f32).contains(&contact.toi));
+                let resolved = resolve_penetration(pos, contact.normal, BALL, paddle, 0.01f32);
+                prop_assert!(!overlaps(resolved, BALL, paddle));
+            }
+        }
+    }
+}
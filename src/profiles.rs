@@ -0,0 +1,94 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{settings::Bindings, storage};
+
+const PROFILES_FILE: &str = "profiles.ron";
+const DEFAULT_PROFILE: &str = "Player";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    #[serde(default)]
+    pub bindings: Bindings,
+}
+
+impl Profile {
+    pub fn new(name: &str) -> Self {
+        Profile {
+            name: name.to_string(),
+            bindings: Bindings::default(),
+        }
+    }
+
+    /// Per-profile data lives under a directory named after the profile, minus anything unsafe in a path.
+    pub fn data_file(&self, file: &str) -> String {
+        let dir: String = self.name
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+            .collect();
+        format!("profiles/{}/{}", dir, file)
+    }
+}
+
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profiles {
+    pub active: usize,
+    pub profiles: Vec<Profile>,
+}
+
+impl Default for Profiles {
+    fn default() -> Self {
+        Profiles {
+            active: 0,
+            profiles: vec![Profile::new(DEFAULT_PROFILE)],
+        }
+    }
+}
+
+impl Profiles {
+    pub fn load() -> Self {
+        let mut profiles: Profiles = storage::config_path(PROFILES_FILE)
+            .and_then(|path| storage::load_ron(&path))
+            .unwrap_or_default();
+        if profiles.profiles.is_empty() {
+            profiles = Profiles::default();
+        }
+        profiles.active = profiles.active.min(profiles.profiles.len() - 1);
+        profiles
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = storage::config_path(PROFILES_FILE).ok_or("no config directory")?;
+        storage::save_ron(&path, self)
+    }
+
+    pub fn active(&self) -> &Profile {
+        &self.profiles[self.active]
+    }
+
+    /// Switches to the profile called `name`, creating it if it doesn't exist yet. Returns whether it was created.
+    pub fn select(&mut self, name: &str) -> bool {
+        if let Some(i) = self.profiles.iter().position(|profile| profile.name == name) {
+            self.active = i;
+            return false;
+        }
+        self.profiles.push(Profile::new(name));
+        self.active = self.profiles.len() - 1;
+        true
+    }
+
+    pub fn cycle(&mut self) {
+        self.active = (self.active + 1) % self.profiles.len();
+    }
+}
+
+pub fn save_profiles(profiles: Res<Profiles>) {
+    if !profiles.is_changed() || profiles.is_added() {
+        return;
+    }
+    if let Err(err) = profiles.save() {
+        warn!("Failed to save profiles: {}", err);
+    }
+}
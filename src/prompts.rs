@@ -0,0 +1,96 @@
+use bevy::prelude::*;
+
+use crate::{profiles::Profiles, settings::Bindings, tutorial::TutorialStep};
+
+const STICK_ACTIVE: f32 = 0.5f32;
+
+/// Whatever the player touched last, so on-screen hints name the right buttons.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InputDevice {
+    #[default]
+    Keyboard,
+    Gamepad,
+}
+
+/// Text that tells the player which button to press, rewritten whenever the input device or bindings change.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prompt {
+    Serve,
+    Resume,
+    Tutorial(TutorialStep),
+}
+
+impl Prompt {
+    fn text(&self, device: InputDevice, bindings: &Bindings) -> String {
+        match (self, device) {
+            (Prompt::Serve, InputDevice::Keyboard) => {
+                format!("Press {} or {} to serve", key_name(bindings.up), key_name(bindings.down))
+            },
+            (Prompt::Serve, InputDevice::Gamepad) => "Press A or the D-pad to serve".into(),
+            (Prompt::Resume, InputDevice::Keyboard) => "Paused\nPress Esc or tap to resume\nF for photo mode".into(),
+            (Prompt::Resume, InputDevice::Gamepad) => "Paused\nPress Start to resume".into(),
+            (Prompt::Tutorial(TutorialStep::Move), InputDevice::Keyboard) => {
+                format!("Hold {} and {} to move your paddle", key_name(bindings.up), key_name(bindings.down))
+            },
+            (Prompt::Tutorial(TutorialStep::Move), InputDevice::Gamepad) => {
+                "Use the D-pad or left stick to move your paddle".into()
+            },
+            (Prompt::Tutorial(TutorialStep::Serve), _) => "Moving also serves the ball. Serve it!".into(),
+            (Prompt::Tutorial(TutorialStep::Return), _) => "Get in front of the ball to hit it back".into(),
+            (Prompt::Tutorial(TutorialStep::Angle), _) => {
+                "Hit the ball with the end of your paddle\nto send it off at an angle".into()
+            },
+            (Prompt::Tutorial(TutorialStep::Done), _) => "You're ready! Keep playing, or pause any time".into(),
+        }
+    }
+}
+
+fn key_name(key: KeyCode) -> String {
+    let name = format!("{:?}", key);
+    name.strip_prefix("Key")
+        .or_else(|| name.strip_prefix("Digit"))
+        .unwrap_or(&name)
+        .to_string()
+}
+
+pub fn track_input_device(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    touches: Res<Touches>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    mut device: ResMut<InputDevice>,
+) {
+    let stick_moved = gamepads.iter().any(|gamepad| {
+        let stick = GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY);
+        gamepad_axes.get(stick).is_some_and(|value| value.abs() > STICK_ACTIVE)
+    });
+    let next = if gamepad_buttons.get_just_pressed().next().is_some() || stick_moved {
+        InputDevice::Gamepad
+    }
+    else if keyboard_input.get_just_pressed().next().is_some()
+        || mouse_input.get_just_pressed().next().is_some()
+        || touches.any_just_pressed()
+    {
+        InputDevice::Keyboard
+    }
+    else {
+        return;
+    };
+    if *device != next {
+        *device = next;
+    }
+}
+
+pub fn update_prompts(
+    device: Res<InputDevice>,
+    profiles: Res<Profiles>,
+    mut prompts: Query<(Ref<Prompt>, &mut Text)>,
+) {
+    for (prompt, mut text) in prompts.iter_mut() {
+        if prompt.is_changed() || device.is_changed() || profiles.is_changed() {
+            text.sections[0].value = prompt.text(*device, &profiles.active().bindings);
+        }
+    }
+}
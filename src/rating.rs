@@ -0,0 +1,130 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chat::NetworkController, profiles::{Profile, Profiles}, replay::Replay, settings::Difficulty, spawn_toast, storage,
+    Arena, MatchOver, Side,
+};
+
+const RATING_FILE: &str = "rating.ron";
+const RATING_VERSION: u32 = 1;
+const START_RATING: f32 = 1000f32;
+/// How far one match can move the rating.
+const K_FACTOR: f32 = 32f32;
+/// Provisional ratings move faster, so a new player finds their level in a few matches.
+const PROVISIONAL_K_FACTOR: f32 = 64f32;
+const PROVISIONAL_MATCHES: u32 = 10;
+
+/// What each AI plays like on the rating scale.
+pub fn ai_rating(difficulty: Difficulty) -> f32 {
+    match difficulty {
+        Difficulty::Easy => 800f32,
+        Difficulty::Normal => 1100f32,
+        Difficulty::Hard => 1400f32,
+    }
+}
+
+/// A profile's Elo rating from its matches against the AI.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rating {
+    pub version: u32,
+    pub rating: f32,
+    pub matches: u32,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Rating { version: RATING_VERSION, rating: START_RATING, matches: 0 }
+    }
+}
+
+impl Rating {
+    pub fn load(profile: &Profile) -> Self {
+        storage::data_path(&profile.data_file(RATING_FILE))
+            .and_then(|path| storage::load_ron(&path))
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, profile: &Profile) -> Result<(), String> {
+        let path = storage::data_path(&profile.data_file(RATING_FILE)).ok_or("no data directory")?;
+        storage::save_ron(&path, self)
+    }
+
+    /// Moves the rating toward the result against `difficulty`, returning the change.
+    pub fn record(&mut self, difficulty: Difficulty, won: bool) -> f32 {
+        let expected = 1f32 / (1f32 + 10f32.powf((ai_rating(difficulty) - self.rating) / 400f32));
+        let k = if self.matches < PROVISIONAL_MATCHES { PROVISIONAL_K_FACTOR } else { K_FACTOR };
+        let change = k * (if won { 1f32 } else { 0f32 } - expected);
+        self.rating += change;
+        self.matches += 1;
+        change
+    }
+
+    /// The AI closest to the player's rating.
+    pub fn suggested(&self) -> Difficulty {
+        [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard]
+            .into_iter()
+            .min_by(|a, b| (ai_rating(*a) - self.rating).abs().total_cmp(&(ai_rating(*b) - self.rating).abs()))
+            .unwrap_or_default()
+    }
+
+    pub fn summary(&self) -> String {
+        let provisional = if self.matches < PROVISIONAL_MATCHES { "?" } else { "" };
+        format!("Rating: {:.0}{}  (suggested AI: {:?})", self.rating, provisional, self.suggested())
+    }
+}
+
+/// Rates every match against the AI, whatever else the match counts toward.
+pub fn rate_match(
+    mut cmd: Commands,
+    mut match_over: EventReader<MatchOver>,
+    replay: Res<Replay>,
+    arena: Res<Arena>,
+    profiles: Res<Profiles>,
+    mut rating: ResMut<Rating>,
+    controlled: Query<(), With<NetworkController>>,
+) {
+    for over in match_over.read() {
+        // Playing a replay back isn't a new match, and a paddle steered by chat isn't the AI.
+        if replay.is_playing() || !controlled.is_empty() {
+            continue;
+        }
+        let difficulty = replay.data().difficulty;
+        let before = rating.suggested();
+        let change = rating.record(difficulty, over.winner == Side::Left);
+        if let Err(err) = rating.save(profiles.active()) {
+            warn!("Failed to save rating: {}", err);
+        }
+        let mut message = format!("Rating {:.0} ({:+.0})", rating.rating, change);
+        if rating.suggested() != before {
+            message = format!("{}, try {:?} AI", message, rating.suggested());
+        }
+        spawn_toast(&mut cmd, &arena, message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upsets_move_the_rating_more() {
+        let mut rating = Rating { matches: PROVISIONAL_MATCHES, ..default() };
+        let easy_win = rating.clone().record(Difficulty::Easy, true);
+        let hard_win = rating.clone().record(Difficulty::Hard, true);
+        assert!(easy_win > 0f32 && hard_win > easy_win);
+
+        let hard_loss = rating.record(Difficulty::Hard, false);
+        assert!(hard_loss < 0f32 && hard_loss.abs() < hard_win);
+        assert_eq!(rating.matches, PROVISIONAL_MATCHES + 1);
+    }
+
+    #[test]
+    fn suggests_the_closest_ai() {
+        let rating = |rating| Rating { rating, ..default() };
+        assert_eq!(rating(600f32).suggested(), Difficulty::Easy);
+        assert_eq!(rating(1000f32).suggested(), Difficulty::Normal);
+        assert_eq!(rating(1300f32).suggested(), Difficulty::Hard);
+    }
+}
@@ -0,0 +1,91 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::storage;
+
+const RECORDS_FILE: &str = "records.ron";
+const RECORDS_VERSION: u32 = 1;
+const LEADERBOARD_SIZE: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub points_for: i32,
+    pub points_against: i32,
+    pub duration_secs: f32,
+}
+
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Records {
+    pub version: u32,
+    pub longest_rally: u32,
+    pub fastest_win_secs: Option<f32>,
+    pub leaderboard: Vec<LeaderboardEntry>,
+    /// Set when the file on disk came from a newer build, so we never overwrite fields we don't know about.
+    #[serde(skip)]
+    pub read_only: bool,
+}
+
+impl Default for Records {
+    fn default() -> Self {
+        Records {
+            version: RECORDS_VERSION,
+            longest_rally: 0,
+            fastest_win_secs: None,
+            leaderboard: Vec::new(),
+            read_only: false,
+        }
+    }
+}
+
+impl Records {
+    pub fn load() -> Self {
+        let Some(mut records) = storage::data_path(RECORDS_FILE)
+            .and_then(|path| storage::load_ron::<Records>(&path))
+        else {
+            return Records::default();
+        };
+
+        if records.version > RECORDS_VERSION {
+            warn!(
+                "Records file is version {}, newer than supported version {}; records won't be saved",
+                records.version, RECORDS_VERSION
+            );
+            records.read_only = true;
+        }
+        else {
+            // Older files only lack fields, which `serde(default)` already fills in.
+            records.version = RECORDS_VERSION;
+        }
+        records
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        if self.read_only {
+            return Ok(());
+        }
+        let path = storage::data_path(RECORDS_FILE).ok_or("no data directory")?;
+        storage::save_ron(&path, self)
+    }
+
+    pub fn record_rally(&mut self, hits: u32) -> bool {
+        if hits <= self.longest_rally {
+            return false;
+        }
+        self.longest_rally = hits;
+        true
+    }
+
+    pub fn record_win(&mut self, entry: LeaderboardEntry) {
+        if self.fastest_win_secs.is_none_or(|best| entry.duration_secs < best) {
+            self.fastest_win_secs = Some(entry.duration_secs);
+        }
+        self.leaderboard.push(entry);
+        self.leaderboard.sort_by(|a, b| {
+            (b.points_for - b.points_against)
+                .cmp(&(a.points_for - a.points_against))
+                .then(a.duration_secs.total_cmp(&b.duration_secs))
+        });
+        self.leaderboard.truncate(LEADERBOARD_SIZE);
+    }
+}
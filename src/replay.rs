@@ -0,0 +1,168 @@
+use std::path::{Path, PathBuf};
+
+use bevy::{app::AppExit, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{settings::Difficulty, storage, Persist};
+#[cfg(feature = "twitch")]
+use crate::twitch::ChatVotes;
+
+const REPLAY_VERSION: u32 = 2;
+
+/// The player's input for one fixed tick.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TickInput {
+    pub dir: i8,
+    #[serde(default)]
+    pub serve: bool,
+}
+
+/// A match's seed plus the player's input for every fixed tick, run-length encoded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayData {
+    pub version: u32,
+    pub seed: u64,
+    pub difficulty: Difficulty,
+    pub inputs: Vec<(TickInput, u32)>,
+}
+
+impl ReplayData {
+    pub fn new(seed: u64, difficulty: Difficulty) -> Self {
+        ReplayData {
+            version: REPLAY_VERSION,
+            seed,
+            difficulty,
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, input: TickInput) {
+        if let Some((last, count)) = self.inputs.last_mut() {
+            if *last == input {
+                *count += 1;
+                return;
+            }
+        }
+        self.inputs.push((input, 1));
+    }
+
+    pub fn load(path: &Path) -> Option<Self> {
+        let data: ReplayData = storage::load_ron(path)?;
+        if data.version != REPLAY_VERSION {
+            warn!("Replay {} is version {}, expected {}", path.display(), data.version, REPLAY_VERSION);
+            return None;
+        }
+        Some(data)
+    }
+}
+
+#[derive(Resource)]
+pub enum Replay {
+    Recording {
+        data: ReplayData,
+        path: Option<PathBuf>,
+        /// Set for a match that didn't start from its seed, which the recording couldn't reproduce.
+        discarded: bool,
+    },
+    Playing {
+        data: ReplayData,
+        run: usize,
+        tick: u32,
+    },
+}
+
+impl Replay {
+    pub fn record(seed: u64, difficulty: Difficulty) -> Self {
+        Replay::Recording {
+            data: ReplayData::new(seed, difficulty),
+            path: storage::data_path(&format!("replays/{}.ron", storage::timestamp())),
+            discarded: false,
+        }
+    }
+
+    pub fn play(data: ReplayData) -> Self {
+        Replay::Playing { data, run: 0, tick: 0 }
+    }
+
+    /// Changes the AI for the rest of the match. The recording can't be played back after this, so only
+    /// sessions that don't save replays should call it.
+    pub fn set_difficulty(&mut self, difficulty: Difficulty) {
+        if let Replay::Recording { data, .. } = self {
+            data.difficulty = difficulty;
+        }
+    }
+
+    /// Keeps the recording from being saved or shared.
+    pub fn discard(&mut self) {
+        if let Replay::Recording { discarded, .. } = self {
+            *discarded = true;
+        }
+    }
+
+    pub fn is_discarded(&self) -> bool {
+        matches!(self, Replay::Recording { discarded: true, .. })
+    }
+
+    pub fn is_playing(&self) -> bool {
+        matches!(self, Replay::Playing { .. })
+    }
+
+    pub fn data(&self) -> &ReplayData {
+        match self {
+            Replay::Recording { data, .. } | Replay::Playing { data, .. } => data,
+        }
+    }
+
+    /// Records `live` and returns it, or returns the recorded input for this tick when playing back.
+    pub fn next_input(&mut self, live: TickInput) -> TickInput {
+        match self {
+            Replay::Recording { data, .. } => {
+                data.push(live);
+                live
+            },
+            Replay::Playing { data, run, tick } => {
+                let Some(&(input, count)) = data.inputs.get(*run) else {
+                    return TickInput::default();
+                };
+                *tick += 1;
+                if *tick >= count {
+                    *run += 1;
+                    *tick = 0;
+                    if *run == data.inputs.len() {
+                        info!("Replay finished");
+                    }
+                }
+                input
+            },
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        match self {
+            Replay::Recording { data, path, discarded: false } => {
+                let path = path.as_ref().ok_or("no data directory")?;
+                storage::save_ron(path, data)
+            },
+            Replay::Recording { discarded: true, .. } | Replay::Playing { .. } => Ok(()),
+        }
+    }
+}
+
+pub fn save_replay_on_exit(
+    mut exits: EventReader<AppExit>,
+    replay: Res<Replay>,
+    persist: Res<Persist>,
+    #[cfg(feature = "twitch")] chat: Option<Res<ChatVotes>>,
+) {
+    if exits.read().count() == 0 || !persist.0 {
+        return;
+    }
+    // Chat input isn't recorded, so the replay couldn't reproduce the match.
+    #[cfg(feature = "twitch")]
+    if chat.is_some() {
+        return;
+    }
+    if let Err(err) = replay.save() {
+        warn!("Failed to save replay: {}", err);
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+
+use bevy::{
+    ecs::world::{EntityRef, EntityWorldMut},
+    prelude::*,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+/// Marks an entity the authority (the LAN host or the dedicated server) sends to its guests every tick,
+/// along with whichever of its components are registered with `replicate`.
+#[derive(Component, Default)]
+pub struct Replicated;
+
+/// Names a replicated entity the same on every end.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct NetId(u32);
+
+/// Moved ahead of the authority on this end, so replicated components leave it alone; only its position is reconciled.
+#[derive(Component)]
+pub struct Predicted;
+
+struct ComponentRule {
+    capture: fn(&EntityRef) -> Option<Value>,
+    apply: fn(&mut EntityWorldMut, Value),
+}
+
+struct ResourceRule {
+    capture: fn(&World) -> Option<Value>,
+    apply: fn(&mut World, Value),
+}
+
+/// What gets replicated. Every end registers the same components and resources in the same order,
+/// so each is sent as its index rather than its name.
+#[derive(Resource, Default)]
+pub struct ReplicationRules {
+    components: Vec<ComponentRule>,
+    resources: Vec<ResourceRule>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EntityState {
+    pub id: NetId,
+    pub pos: Vec2,
+    components: Vec<(usize, Value)>,
+}
+
+/// Every replicated entity and resource on the authority at one tick.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct WorldState {
+    pub entities: Vec<EntityState>,
+    resources: Vec<(usize, Value)>,
+}
+
+impl WorldState {
+    pub fn entity(&self, id: NetId) -> Option<&EntityState> {
+        self.entities.iter().find(|entity| entity.id == id)
+    }
+}
+
+impl ReplicationRules {
+    pub fn capture(&self, world: &World) -> WorldState {
+        let entities = world
+            .iter_entities()
+            .filter(|entity| entity.contains::<Replicated>())
+            .filter_map(|entity| {
+                Some(EntityState {
+                    id: *entity.get::<NetId>()?,
+                    pos: entity.get::<Transform>().map_or(Vec2::ZERO, |transform| transform.translation.truncate()),
+                    components: self
+                        .components
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(i, rule)| Some((i, (rule.capture)(&entity)?)))
+                        .collect(),
+                })
+            })
+            .collect();
+        let resources =
+            self.resources.iter().enumerate().filter_map(|(i, rule)| Some((i, (rule.capture)(world)?))).collect();
+        WorldState { entities, resources }
+    }
+}
+
+fn capture_component<C: Component + Serialize>(entity: &EntityRef) -> Option<Value> {
+    serde_json::to_value(entity.get::<C>()?).ok()
+}
+
+fn apply_component<C: Component + DeserializeOwned>(entity: &mut EntityWorldMut, value: Value) {
+    if let Ok(component) = serde_json::from_value::<C>(value) {
+        entity.insert(component);
+    }
+}
+
+fn capture_resource<R: Resource + Serialize>(world: &World) -> Option<Value> {
+    serde_json::to_value(world.get_resource::<R>()?).ok()
+}
+
+fn apply_resource<R: Resource + DeserializeOwned>(world: &mut World, value: Value) {
+    if let Ok(resource) = serde_json::from_value::<R>(value) {
+        world.insert_resource(resource);
+    }
+}
+
+pub trait ReplicationAppExt {
+    fn replicate<C: Component + Serialize + DeserializeOwned>(&mut self) -> &mut Self;
+    fn replicate_resource<R: Resource + Serialize + DeserializeOwned>(&mut self) -> &mut Self;
+}
+
+impl ReplicationAppExt for App {
+    fn replicate<C: Component + Serialize + DeserializeOwned>(&mut self) -> &mut Self {
+        self.init_resource::<ReplicationRules>();
+        self.world.resource_mut::<ReplicationRules>().components.push(ComponentRule {
+            capture: capture_component::<C>,
+            apply: apply_component::<C>,
+        });
+        self
+    }
+
+    fn replicate_resource<R: Resource + Serialize + DeserializeOwned>(&mut self) -> &mut Self {
+        self.init_resource::<ReplicationRules>();
+        self.world.resource_mut::<ReplicationRules>().resources.push(ResourceRule {
+            capture: capture_resource::<R>,
+            apply: apply_resource::<R>,
+        });
+        self
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct NetIds {
+    next: u32,
+}
+
+impl NetIds {
+    /// Numbers past every entity in `state`, for an end taking over as the authority.
+    pub fn resume_after(&mut self, state: &WorldState) {
+        let last = state.entities.iter().map(|entity| entity.id.0 + 1).max().unwrap_or(0);
+        self.next = self.next.max(last);
+    }
+}
+
+/// Numbers new replicated entities. Every end spawns the same entities at startup in the same order,
+/// so those get the same ids everywhere; anything spawned later only gets one on the authority.
+pub fn assign_net_ids(
+    mut cmd: Commands,
+    mut ids: ResMut<NetIds>,
+    entities: Query<Entity, (With<Replicated>, Without<NetId>)>,
+) {
+    let mut entities: Vec<Entity> = entities.iter().collect();
+    entities.sort();
+    for entity in entities {
+        cmd.entity(entity).insert(NetId(ids.next));
+        ids.next += 1;
+    }
+}
+
+/// The newest state from the authority, waiting to be applied.
+#[derive(Resource, Default)]
+pub struct ReceivedState(pub Option<WorldState>);
+
+/// Applies the newest state on a guest: spawns entities the authority has and this end doesn't, despawns the reverse,
+/// and overwrites every replicated component and resource. Positions are left to the guest's interpolation.
+pub fn apply_received_state(world: &mut World) {
+    let Some(state) = world.resource_mut::<ReceivedState>().0.take() else {
+        return;
+    };
+    world.resource_scope(|world, rules: Mut<ReplicationRules>| {
+        let mut local: HashMap<NetId, Entity> = world
+            .query_filtered::<(Entity, &NetId), With<Replicated>>()
+            .iter(world)
+            .map(|(entity, id)| (*id, entity))
+            .collect();
+        for entity_state in &state.entities {
+            let entity = match local.remove(&entity_state.id) {
+                Some(entity) => entity,
+                None => {
+                    let transform = Transform::from_translation(entity_state.pos.extend(0f32));
+                    world.spawn((Replicated, entity_state.id, TransformBundle::from_transform(transform))).id()
+                },
+            };
+            let mut entity = world.entity_mut(entity);
+            if entity.contains::<Predicted>() {
+                continue;
+            }
+            for (i, value) in &entity_state.components {
+                if let Some(rule) = rules.components.get(*i) {
+                    (rule.apply)(&mut entity, value.clone());
+                }
+            }
+        }
+        for entity in local.into_values() {
+            world.entity_mut(entity).despawn_recursive();
+        }
+        for (i, value) in state.resources {
+            if let Some(rule) = rules.resources.get(i) {
+                (rule.apply)(world, value);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[derive(Component, Debug, PartialEq, Serialize, Deserialize)]
+    struct Health(i32);
+
+    #[derive(Resource, Debug, PartialEq, Serialize, Deserialize)]
+    struct Round(u32);
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.init_resource::<NetIds>()
+            .init_resource::<ReceivedState>()
+            .replicate::<Health>()
+            .replicate_resource::<Round>()
+            .add_systems(Update, assign_net_ids);
+        app
+    }
+
+    #[test]
+    fn mirrors_the_authority() {
+        let mut authority = app();
+        authority.insert_resource(Round(3));
+        authority.world.spawn((Replicated, Health(10)));
+        authority.world.spawn((Replicated, Health(7)));
+        authority.world.spawn(Health(1));
+        authority.update();
+
+        let mut guest = app();
+        guest.insert_resource(Round(0));
+        guest.world.spawn((Replicated, Health(10)));
+        guest.world.spawn((Replicated, Health(10), Predicted));
+        guest.world.spawn((Replicated, Health(5)));
+        guest.update();
+
+        let state = authority.world.resource::<ReplicationRules>().capture(&authority.world);
+        assert_eq!(state.entities.len(), 2);
+        guest.world.resource_mut::<ReceivedState>().0 = Some(state);
+        guest.world.run_system_once(apply_received_state);
+
+        let mut healths: Vec<(NetId, i32)> =
+            guest.world.query::<(&NetId, &Health)>().iter(&guest.world).map(|(id, health)| (*id, health.0)).collect();
+        healths.sort_by_key(|(id, _)| id.0);
+        assert_eq!(healths, vec![(NetId(0), 10), (NetId(1), 10)]);
+        assert_eq!(guest.world.resource::<Round>(), &Round(3));
+    }
+}
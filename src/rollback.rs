@@ -0,0 +1,360 @@
+use std::{net::SocketAddr, time::Duration};
+
+use bevy::prelude::*;
+use bevy_ggrs::{
+    ggrs::{DesyncDetection, GgrsEvent, Message, NonBlockingSocket, PlayerType, SessionBuilder},
+    AddRollbackCommandExtension, GgrsConfig, LocalInputs, LocalPlayers, PlayerInputs, Session,
+};
+use bevy_matchbox::prelude::{MatchboxSocket, PeerId, SingleChannel, WebRtcChannel};
+
+use serde::Serialize;
+
+use crate::{
+    net::{Link, NetMessage},
+    profiles::Profiles,
+    settings::Settings,
+    restart_match, storage, Ball, GameState, Paddle, PlayerInput, ProfileName, Score, ServeRequested, Side,
+    FIXED_TIMESTEP_HZ,
+};
+
+const INPUT_UP: u8 = 1 << 0;
+const INPUT_DOWN: u8 = 1 << 1;
+const INPUT_SERVE: u8 = 1 << 2;
+/// Frames between the checksums the two ends compare.
+const DESYNC_CHECK_INTERVAL: u32 = 30;
+
+/// Where the other player is: straight across the LAN, or behind a matchbox relay.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PeerAddr {
+    Lan(SocketAddr),
+    Relay(PeerId),
+}
+
+pub type RollbackConfig = GgrsConfig<u8, PeerAddr>;
+
+/// The game state as of the last simulated tick, kept so a rollback can restore `State<GameState>`.
+#[derive(Resource, Clone, Default)]
+pub struct RollbackState(GameState);
+
+pub enum RollbackSocket {
+    /// Answers anything the guest sends from before the match with `reply`, so a guest that missed the start still learns of it.
+    Lan {
+        link: Link,
+        reply: Option<NetMessage>,
+    },
+    /// Works in the browser too, where there's no UDP.
+    Relay(WebRtcChannel),
+}
+
+impl NonBlockingSocket<PeerAddr> for RollbackSocket {
+    fn send_to(&mut self, msg: &Message, addr: &PeerAddr) {
+        match (self, addr) {
+            (RollbackSocket::Lan { link, .. }, PeerAddr::Lan(addr)) if link.peer == Some(*addr) => {
+                link.send(&NetMessage::Ggrs(msg.clone()));
+            },
+            (RollbackSocket::Relay(channel), PeerAddr::Relay(peer)) => {
+                channel.send(NetMessage::Ggrs(msg.clone()).encode().into_boxed_slice(), *peer);
+            },
+            _ => {},
+        }
+    }
+
+    fn receive_all_messages(&mut self) -> Vec<(PeerAddr, Message)> {
+        match self {
+            RollbackSocket::Lan { link, reply } => {
+                let mut messages = Vec::new();
+                for (from, message) in link.receive() {
+                    match message {
+                        Ok(NetMessage::Ggrs(msg)) => messages.push((PeerAddr::Lan(from), msg)),
+                        Ok(NetMessage::Hello { .. } | NetMessage::LobbyChoice(_)) => {
+                            if let Some(reply) = reply {
+                                link.send(reply);
+                            }
+                        },
+                        _ => {},
+                    }
+                }
+                messages
+            },
+            RollbackSocket::Relay(channel) => channel
+                .receive()
+                .into_iter()
+                .filter_map(|(peer, packet)| match NetMessage::decode(&packet) {
+                    Ok(NetMessage::Ggrs(msg)) => Some((PeerAddr::Relay(peer), msg)),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Round trip to the other player, as the session measures it.
+pub fn ping_ms(session: &Session<RollbackConfig>) -> Option<u32> {
+    let Session::P2P(session) = session else {
+        return None;
+    };
+    let remote = *session.remote_player_handles().first()?;
+    session.network_stats(remote).ok().map(|stats| stats.ping as u32)
+}
+
+pub fn in_session(session: Option<Res<Session<RollbackConfig>>>) -> bool {
+    session.is_some()
+}
+
+/// Starts a rollback session and restarts the match from the same seed on both ends.
+/// Player 0 plays the left paddle and player 1 the right, whichever of them is local.
+pub fn start_session(
+    cmd: &mut Commands,
+    players: Vec<PlayerType<PeerAddr>>,
+    socket: RollbackSocket,
+    input_delay: usize,
+    seed: u64,
+) -> Result<(), String> {
+    let mut builder = SessionBuilder::<RollbackConfig>::new()
+        .with_num_players(players.len())
+        .with_input_delay(input_delay)
+        .with_fps(FIXED_TIMESTEP_HZ as usize)
+        .map_err(|err| err.to_string())?
+        .with_desync_detection_mode(DesyncDetection::On { interval: DESYNC_CHECK_INTERVAL });
+    for (handle, player) in players.into_iter().enumerate() {
+        builder = builder.add_player(player, handle).map_err(|err| err.to_string())?;
+    }
+    let session = builder.start_p2p_session(socket).map_err(|err| err.to_string())?;
+
+    cmd.insert_resource(Session::P2P(session));
+    cmd.insert_resource(RollbackState::default());
+    restart_match(cmd, seed);
+    Ok(())
+}
+
+pub fn add_rollback_entities(
+    mut cmd: Commands,
+    entities: Query<Entity, Or<(With<Paddle>, With<Ball>)>>,
+) {
+    for entity in entities.iter() {
+        cmd.entity(entity).add_rollback();
+    }
+}
+
+pub fn read_local_inputs(
+    mut cmd: Commands,
+    mut input: ResMut<PlayerInput>,
+    local_players: Res<LocalPlayers>,
+) {
+    let mut bits = match input.dir {
+        1 => INPUT_UP,
+        -1 => INPUT_DOWN,
+        _ => 0,
+    };
+    if input.serve {
+        bits |= INPUT_SERVE;
+    }
+    input.serve = false;
+    let inputs = local_players.0.iter().map(|handle| (*handle, bits)).collect();
+    cmd.insert_resource(LocalInputs::<RollbackConfig>(inputs));
+}
+
+/// Runs the tick on a fixed step, the way `FixedMain` does, so both ends advance identically.
+pub fn begin_tick(mut time: ResMut<Time>) {
+    *time = Time::default();
+    time.advance_by(Duration::from_secs_f64(1f64 / FIXED_TIMESTEP_HZ));
+}
+
+pub fn apply_inputs(
+    inputs: Res<PlayerInputs<RollbackConfig>>,
+    mut serve: ResMut<ServeRequested>,
+    mut paddles: Query<(&mut Paddle, &Side)>,
+) {
+    serve.0 = false;
+    for (mut paddle, side) in paddles.iter_mut() {
+        let (bits, _) = inputs[side.index()];
+        paddle.dir = if bits & INPUT_UP != 0 { 1 } else if bits & INPUT_DOWN != 0 { -1 } else { 0 };
+        // Either player serves by moving, like offline.
+        serve.0 |= bits & INPUT_SERVE != 0 || paddle.dir != 0;
+    }
+}
+
+pub fn end_tick(
+    state: Res<State<GameState>>,
+    mut rollback_state: ResMut<RollbackState>,
+    virtual_time: Res<Time<Virtual>>,
+    mut time: ResMut<Time>,
+) {
+    rollback_state.0 = state.get().clone();
+    *time = virtual_time.as_generic();
+}
+
+fn fnv(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+fn checksum_floats(floats: &[f32]) -> u64 {
+    fnv(floats.iter().flat_map(|float| float.to_bits().to_le_bytes()))
+}
+
+pub fn checksum_transform(transform: &Transform) -> u64 {
+    checksum_floats(&transform.translation.to_array())
+}
+
+pub fn checksum_ball(ball: &Ball) -> u64 {
+    checksum_floats(&[ball.vel.x, ball.vel.y, ball.speed, ball.spin])
+}
+
+#[derive(Component)]
+pub struct DesyncWarning;
+
+pub fn spawn_desync_warning(mut cmd: Commands) {
+    cmd.spawn((
+        Text2dBundle {
+            text: Text::from_section("", TextStyle {
+                font_size: 20f32,
+                color: Color::rgb(1f32, 0.35f32, 0.35f32),
+                ..default()
+            })
+            .with_justify(JustifyText::Center),
+            transform: Transform::from_xyz(0f32, 96f32, 6f32),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        DesyncWarning,
+    ));
+}
+
+/// This end's state when the desync was noticed, which is a few frames after the frame that differed.
+#[derive(Serialize)]
+struct DesyncDump {
+    frame: i32,
+    local_checksum: u128,
+    remote_checksum: u128,
+    state: GameState,
+    score: Score,
+    paddles: Vec<(Vec3, Paddle)>,
+    balls: Vec<(Vec3, Ball)>,
+}
+
+impl DesyncDump {
+    fn write(&self) -> Result<String, String> {
+        let dir = storage::data_path("desyncs").ok_or("no data directory")?;
+        let file = format!("desync-{}.ron", storage::timestamp());
+        storage::save_ron(&dir.join(&file), self)?;
+        Ok(file)
+    }
+}
+
+/// Warns once the two ends' simulations stop matching, rather than letting the match quietly go two ways.
+pub fn detect_desync(
+    mut session: ResMut<Session<RollbackConfig>>,
+    settings: Res<Settings>,
+    state: Res<State<GameState>>,
+    score: Res<Score>,
+    paddles: Query<(&Transform, &Paddle)>,
+    balls: Query<(&Transform, &Ball)>,
+    mut warnings: Query<(&mut Text, &mut Visibility), With<DesyncWarning>>,
+) {
+    let Session::P2P(session) = &mut *session else {
+        return;
+    };
+    for event in session.events() {
+        let GgrsEvent::DesyncDetected { frame, local_checksum, remote_checksum, addr } = event else {
+            continue;
+        };
+        error!(
+            "Desync with {:?} at frame {}: local checksum {:x}, remote {:x}",
+            addr, frame, local_checksum, remote_checksum
+        );
+        let mut warning = "Desync detected: this match no longer matches your opponent's".to_string();
+        if settings.net.dump_desyncs {
+            let dump = DesyncDump {
+                frame,
+                local_checksum,
+                remote_checksum,
+                state: state.get().clone(),
+                score: score.clone(),
+                paddles: paddles.iter().map(|(transform, paddle)| (transform.translation, paddle.clone())).collect(),
+                balls: balls.iter().map(|(transform, ball)| (transform.translation, ball.clone())).collect(),
+            };
+            match dump.write() {
+                Ok(file) => warning += &format!("\nState saved as {}", file),
+                Err(err) => warn!("Failed to save the desync state: {}", err),
+            }
+        }
+        for (mut text, mut visibility) in warnings.iter_mut() {
+            text.sections[0].value = warning.clone();
+            *visibility = Visibility::Visible;
+        }
+    }
+}
+
+pub fn restore_game_state(world: &mut World) {
+    let state = world.resource::<RollbackState>().0.clone();
+    world.insert_resource(State::new(state));
+}
+
+/// Joins a room on the matchbox server, which pairs the first two players to arrive.
+#[derive(Resource)]
+pub struct RelayRoom(pub String);
+
+pub fn open_relay_room(mut cmd: Commands, room: Res<RelayRoom>, settings: Res<Settings>) {
+    join_relay_room(&mut cmd, &settings, &room.0);
+}
+
+/// Connects to `room` on the relay, where `wait_for_opponent` picks up whoever else joins it.
+pub fn join_relay_room(cmd: &mut Commands, settings: &Settings, room: &str) {
+    let url = format!("{}/{}?next=2", settings.net.relay_url.trim_end_matches('/'), room);
+    info!("Waiting for an opponent in room {}", room);
+    cmd.insert_resource(RelayRoom(room.to_string()));
+    cmd.insert_resource(MatchboxSocket::new_ggrs(url));
+}
+
+pub fn wait_for_opponent(
+    mut cmd: Commands,
+    room: Res<RelayRoom>,
+    settings: Res<Settings>,
+    profiles: Res<Profiles>,
+    mut socket: ResMut<MatchboxSocket<SingleChannel>>,
+    mut names: Query<(&mut Text, &Side), With<ProfileName>>,
+) {
+    // The channel is gone once the session has it.
+    if socket.get_channel(0).is_err() {
+        return;
+    }
+    socket.update_peers();
+    let players = socket.players();
+    if players.len() < 2 {
+        return;
+    }
+    // Both ends list the players in the same order, so they agree on who plays which side.
+    let local_is_left = players[0] == PlayerType::Local;
+    let players = players
+        .into_iter()
+        .map(|player| match player {
+            PlayerType::Local => PlayerType::Local,
+            PlayerType::Remote(peer) => PlayerType::Remote(PeerAddr::Relay(peer)),
+            PlayerType::Spectator(peer) => PlayerType::Spectator(PeerAddr::Relay(peer)),
+        })
+        .collect();
+    let Ok(channel) = socket.take_channel(0) else {
+        return;
+    };
+    match start_session(&mut cmd, players, RollbackSocket::Relay(channel), settings.net.input_delay, room_seed(&room.0)) {
+        Ok(()) => {
+            info!("Opponent found in room {}", room.0);
+            for (mut text, side) in names.iter_mut() {
+                text.sections[0].value =
+                    if (*side == Side::Left) == local_is_left { profiles.active().name.clone() } else { "Opponent".into() };
+            }
+        },
+        Err(err) => warn!("Failed to start relay session: {}", err),
+    }
+}
+
+pub fn label_waiting_opponent(mut names: Query<(&mut Text, &Side), With<ProfileName>>) {
+    for (mut name, _) in names.iter_mut().filter(|(_, side)| **side == Side::Right) {
+        name.sections[0].value = "Waiting...".into();
+    }
+}
+
+/// Both players only share the room code, so the match seed is derived from it (FNV-1a).
+fn room_seed(room: &str) -> u64 {
+    fnv(room.bytes())
+}
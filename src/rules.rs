@@ -0,0 +1,175 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::GameConfig, PaddleLength, Side, POINTS_TO_WIN};
+
+pub const MAX_POINTS_TO_WIN: i32 = 21;
+const FAST_BALL_SCALE: f32 = 1.5f32;
+const GIANT_BALL_SCALE: f32 = 4f32;
+const TINY_PADDLE_SCALE: f32 = 0.4f32;
+const TINY_BALL_SCALE: f32 = 0.5f32;
+const HUGE_BALL_SCALE: f32 = 2f32;
+pub const MIN_PADDLE_LENGTH: f32 = 0.5f32;
+pub const MAX_PADDLE_LENGTH: f32 = 1.5f32;
+const PADDLE_LENGTH_STEP: f32 = 0.25f32;
+/// Pixels per second squared.
+const GRAVITY: f32 = 192f32;
+/// Pixels per second squared, at the strongest gust.
+const WIND: f32 = 384f32;
+/// On ice, paddles build up speed slowly, go faster, and barely slow down on their own.
+const ICE_ACCELERATION_SCALE: f32 = 0.25f32;
+const ICE_FRICTION_SCALE: f32 = 0.03f32;
+const ICE_SPEED_SCALE: f32 = 1.5f32;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Mutators {
+    pub fast_ball: bool,
+    pub instant_paddles: bool,
+    /// Unlocked with a cheat code rather than picked in the lobby.
+    pub giant_ball: bool,
+    pub tiny_paddles: bool,
+    pub gravity: bool,
+    pub wind: bool,
+    /// Paddles slide, so they have to be steered ahead of the ball. Overrides instant paddles.
+    pub ice: bool,
+}
+
+impl Mutators {
+    pub fn apply(&self, config: &mut GameConfig) {
+        if self.fast_ball {
+            config.ball_start_speed *= FAST_BALL_SCALE;
+            config.ball_max_speed *= FAST_BALL_SCALE;
+        }
+        config.paddle_instant |= self.instant_paddles;
+        if self.giant_ball {
+            config.ball_half_size *= GIANT_BALL_SCALE;
+        }
+        if self.tiny_paddles {
+            config.paddle_half_size.y *= TINY_PADDLE_SCALE;
+        }
+        if self.gravity {
+            config.ball_gravity += GRAVITY;
+        }
+        if self.wind {
+            config.ball_wind += WIND;
+        }
+        if self.ice {
+            config.paddle_instant = false;
+            config.paddle_acceleration *= ICE_ACCELERATION_SCALE;
+            config.paddle_stop_friction *= ICE_FRICTION_SCALE;
+            config.paddle_speed *= ICE_SPEED_SCALE;
+        }
+    }
+
+    /// Every mutator alongside its name.
+    pub fn flags_mut(&mut self) -> [(&'static str, &mut bool); 7] {
+        [
+            ("Fast ball", &mut self.fast_ball),
+            ("Instant paddles", &mut self.instant_paddles),
+            ("Giant ball", &mut self.giant_ball),
+            ("Tiny paddles", &mut self.tiny_paddles),
+            ("Gravity", &mut self.gravity),
+            ("Wind", &mut self.wind),
+            ("Ice", &mut self.ice),
+        ]
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut mutators = self.clone();
+        let flags = mutators.flags_mut();
+        flags.into_iter().filter(|(_, on)| **on).map(|(name, _)| name).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BallSize {
+    Tiny,
+    #[default]
+    Normal,
+    Huge,
+}
+
+impl BallSize {
+    pub fn scale(self) -> f32 {
+        match self {
+            BallSize::Tiny => TINY_BALL_SCALE,
+            BallSize::Normal => 1f32,
+            BallSize::Huge => HUGE_BALL_SCALE,
+        }
+    }
+
+    /// The next size up, or down for a negative `step`, stopping at either end.
+    pub fn step(self, step: i32) -> Self {
+        const SIZES: [BallSize; 3] = [BallSize::Tiny, BallSize::Normal, BallSize::Huge];
+        let i = SIZES.iter().position(|size| *size == self).unwrap_or(1) as i32;
+        SIZES[(i + step).clamp(0, SIZES.len() as i32 - 1) as usize]
+    }
+}
+
+/// The rules the match is played by: what both players agreed to in the lobby, or what the player picked in
+/// the ruleset builder. Every other match uses the defaults.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MatchRules {
+    pub points_to_win: i32,
+    /// How far ahead the winner has to be, so a match can play on past match point.
+    pub win_by: i32,
+    /// Scales how fast the ball is served and how fast it can get.
+    pub ball_speed: f32,
+    /// Scales how quickly the ball speeds up over a rally.
+    pub ball_speed_up: f32,
+    /// Scales the paddles' length.
+    pub paddle_size: f32,
+    pub ball_size: BallSize,
+    /// Each paddle's own length, left then right, on top of `paddle_size`.
+    pub paddle_lengths: [f32; 2],
+    pub mutators: Mutators,
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        MatchRules {
+            points_to_win: POINTS_TO_WIN,
+            win_by: 1,
+            ball_speed: 1f32,
+            ball_speed_up: 1f32,
+            paddle_size: 1f32,
+            ball_size: BallSize::Normal,
+            paddle_lengths: [1f32, 1f32],
+            mutators: Mutators::default(),
+        }
+    }
+}
+
+impl MatchRules {
+    pub fn apply(&self, config: &mut GameConfig) {
+        config.ball_start_speed *= self.ball_speed;
+        config.ball_max_speed *= self.ball_speed;
+        config.ball_acceleration *= self.ball_speed_up;
+        config.paddle_half_size.y *= self.paddle_size;
+        config.ball_half_size *= self.ball_size.scale();
+        self.mutators.apply(config);
+    }
+
+    /// Whether a side with `points` has won against one with `other`.
+    pub fn won(&self, points: i32, other: i32) -> bool {
+        points >= self.points_to_win && points - other >= self.win_by
+    }
+}
+
+/// The next paddle length a player can pick, going back to the shortest after the longest.
+pub fn next_paddle_length(length: f32) -> f32 {
+    let next = length + PADDLE_LENGTH_STEP;
+    if next > MAX_PADDLE_LENGTH + f32::EPSILON { MIN_PADDLE_LENGTH } else { next }
+}
+
+/// Gives each paddle the length the rules picked for its side.
+pub fn apply_paddle_lengths(rules: Res<MatchRules>, mut paddles: Query<(&Side, &mut PaddleLength)>) {
+    for (side, mut length) in paddles.iter_mut() {
+        let picked = rules.paddle_lengths[side.index()].clamp(MIN_PADDLE_LENGTH, MAX_PADDLE_LENGTH);
+        if length.0 != picked {
+            length.0 = picked;
+        }
+    }
+}
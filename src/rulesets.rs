@@ -0,0 +1,301 @@
+use std::fmt::Write as _;
+
+use bevy::{prelude::*, window::ReceivedCharacter};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::GameConfig,
+    rules::{MatchRules, MAX_PADDLE_LENGTH, MAX_POINTS_TO_WIN, MIN_PADDLE_LENGTH},
+    spawn_toast, storage, Arena, Ball,
+};
+
+const RULESETS_FILE: &str = "rulesets.ron";
+const RULESETS_VERSION: u32 = 1;
+const FONT_SIZE: f32 = 16f32;
+const MAX_NAME_LEN: usize = 24;
+const MAX_WIN_BY: i32 = 3;
+const SCALE_STEP: f32 = 0.25f32;
+const MIN_SCALE: f32 = 0.5f32;
+const MAX_SCALE: f32 = 2f32;
+/// Rows above the mutator toggles: points to win, win by, ball speed, ball speed-up, paddle size, ball size, and
+/// each player's own paddle length.
+const SETTING_ROWS: usize = 8;
+
+/// A set of rules saved under a name, to be picked again in a later session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ruleset {
+    pub name: String,
+    pub rules: MatchRules,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rulesets {
+    pub version: u32,
+    pub saved: Vec<Ruleset>,
+}
+
+impl Default for Rulesets {
+    fn default() -> Self {
+        Rulesets { version: RULESETS_VERSION, saved: Vec::new() }
+    }
+}
+
+impl Rulesets {
+    pub fn load() -> Self {
+        storage::config_path(RULESETS_FILE)
+            .and_then(|path| storage::load_ron(&path))
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = storage::config_path(RULESETS_FILE).ok_or("no config directory")?;
+        storage::save_ron(&path, self)
+    }
+
+    /// Adds the ruleset, replacing any saved under the same name, and returns where it is.
+    pub fn put(&mut self, ruleset: Ruleset) -> usize {
+        match self.saved.iter().position(|saved| saved.name == ruleset.name) {
+            Some(i) => {
+                self.saved[i] = ruleset;
+                i
+            },
+            None => {
+                self.saved.push(ruleset);
+                self.saved.len() - 1
+            },
+        }
+    }
+}
+
+/// Steps the setting on `row` up or down, or flips the mutator on it.
+fn adjust(rules: &mut MatchRules, row: usize, step: i32) {
+    let scale = |value: &mut f32| *value = (*value + step as f32 * SCALE_STEP).clamp(MIN_SCALE, MAX_SCALE);
+    match row {
+        0 => rules.points_to_win = (rules.points_to_win + step).clamp(1, MAX_POINTS_TO_WIN),
+        1 => rules.win_by = (rules.win_by + step).clamp(1, MAX_WIN_BY),
+        2 => scale(&mut rules.ball_speed),
+        3 => scale(&mut rules.ball_speed_up),
+        4 => scale(&mut rules.paddle_size),
+        5 => rules.ball_size = rules.ball_size.step(step),
+        6 | 7 => {
+            let length = &mut rules.paddle_lengths[row - 6];
+            *length = (*length + step as f32 * SCALE_STEP).clamp(MIN_PADDLE_LENGTH, MAX_PADDLE_LENGTH);
+        },
+        _ => {
+            if let Some((_, on)) = rules.mutators.flags_mut().into_iter().nth(row - SETTING_ROWS) {
+                *on = !*on;
+            }
+        },
+    }
+}
+
+fn rows(rules: &MatchRules) -> Vec<String> {
+    let percent = |scale: f32| format!("{:.0}%", scale * 100f32);
+    let mut rows = vec![
+        format!("First to {}", rules.points_to_win),
+        format!("Win by {}", rules.win_by),
+        format!("Ball speed {}", percent(rules.ball_speed)),
+        format!("Ball speed-up {}", percent(rules.ball_speed_up)),
+        format!("Paddle size {}", percent(rules.paddle_size)),
+        format!("Ball size {:?}", rules.ball_size),
+        format!("Your paddle {}", percent(rules.paddle_lengths[0])),
+        format!("Their paddle {}", percent(rules.paddle_lengths[1])),
+    ];
+    let mut mutators = rules.mutators.clone();
+    rows.extend(mutators.flags_mut().into_iter().map(|(name, on)| format!("{}: {}", name, if *on { "on" } else { "off" })));
+    rows
+}
+
+/// The screen before an offline match where the rules are set up, saved under a name, or loaded from
+/// an earlier save. Nobody serves until it's closed.
+#[derive(Resource)]
+pub struct RulesetBuilder {
+    rules: MatchRules,
+    name: String,
+    selected: usize,
+    rulesets: Rulesets,
+    /// The saved ruleset loaded last, so the next Ctrl+L moves on from it.
+    loaded: Option<usize>,
+}
+
+impl RulesetBuilder {
+    fn summary(&self) -> String {
+        let mut summary = format!("RULES\nName: {}_\n", self.name);
+        for (i, row) in rows(&self.rules).iter().enumerate() {
+            let cursor = if i == self.selected { ">" } else { " " };
+            let _ = write!(summary, "\n{} {}", cursor, row);
+        }
+        if !self.rulesets.saved.is_empty() {
+            let names = self.rulesets.saved.iter().map(|saved| saved.name.as_str()).collect::<Vec<_>>();
+            let _ = write!(summary, "\n\nSaved: {}", names.join(", "));
+        }
+        summary.push_str("\n\nUp/Down select   Left/Right change   Ctrl+L load   Ctrl+S save   Enter play   type to name");
+        summary
+    }
+}
+
+#[derive(Component)]
+pub struct RulesetScreen;
+
+pub fn open_ruleset_builder(mut cmd: Commands) {
+    cmd.insert_resource(RulesetBuilder {
+        rules: MatchRules::default(),
+        name: String::new(),
+        selected: 0,
+        rulesets: Rulesets::load(),
+        loaded: None,
+    });
+    cmd.spawn((
+        Text2dBundle {
+            text: Text::from_section("", TextStyle {
+                font_size: FONT_SIZE,
+                ..default()
+            })
+            .with_justify(JustifyText::Center),
+            transform: Transform::from_xyz(0f32, 0f32, 5f32),
+            ..default()
+        },
+        RulesetScreen,
+    ));
+}
+
+pub fn edit_ruleset(
+    mut cmd: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    arena: Res<Arena>,
+    mut builder: ResMut<RulesetBuilder>,
+    mut config: ResMut<GameConfig>,
+    mut balls: Query<&mut Ball>,
+    screens: Query<Entity, With<RulesetScreen>>,
+) {
+    let pressed = |key| keyboard_input.just_pressed(key);
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    for character in characters.read().filter(|_| !ctrl) {
+        for c in character.char.chars().filter(|c| !c.is_control()) {
+            if builder.name.chars().count() < MAX_NAME_LEN {
+                builder.name.push(c);
+            }
+        }
+    }
+    if pressed(KeyCode::Backspace) {
+        builder.name.pop();
+    }
+    let count = rows(&builder.rules).len();
+    if pressed(KeyCode::ArrowDown) && builder.selected + 1 < count {
+        builder.selected += 1;
+    }
+    if pressed(KeyCode::ArrowUp) {
+        builder.selected = builder.selected.saturating_sub(1);
+    }
+    let selected = builder.selected;
+    if pressed(KeyCode::ArrowRight) {
+        adjust(&mut builder.rules, selected, 1);
+    }
+    if pressed(KeyCode::ArrowLeft) {
+        adjust(&mut builder.rules, selected, -1);
+    }
+    if ctrl && pressed(KeyCode::KeyL) && !builder.rulesets.saved.is_empty() {
+        let next = builder.loaded.map_or(0, |i| (i + 1) % builder.rulesets.saved.len());
+        let ruleset = builder.rulesets.saved[next].clone();
+        builder.rules = ruleset.rules;
+        builder.name = ruleset.name;
+        builder.loaded = Some(next);
+    }
+    if ctrl && pressed(KeyCode::KeyS) {
+        let name = builder.name.trim().to_string();
+        if name.is_empty() {
+            spawn_toast(&mut cmd, &arena, "Name the rules to save them".into());
+        }
+        else {
+            let ruleset = Ruleset { name: name.clone(), rules: builder.rules.clone() };
+            builder.loaded = Some(builder.rulesets.put(ruleset));
+            match builder.rulesets.save() {
+                Ok(()) => spawn_toast(&mut cmd, &arena, format!("Saved {}", name)),
+                Err(err) => warn!("Failed to save rulesets: {}", err),
+            }
+        }
+    }
+
+    if !pressed(KeyCode::Enter) {
+        return;
+    }
+    info!("Playing by {:?}", builder.rules);
+    builder.rules.apply(&mut config);
+    for mut ball in balls.iter_mut() {
+        ball.speed = config.ball_start_speed;
+    }
+    cmd.insert_resource(builder.rules.clone());
+    cmd.remove_resource::<RulesetBuilder>();
+    for screen in screens.iter() {
+        cmd.entity(screen).despawn_recursive();
+    }
+}
+
+pub fn update_ruleset_screen(builder: Res<RulesetBuilder>, mut screens: Query<&mut Text, With<RulesetScreen>>) {
+    if !builder.is_changed() {
+        return;
+    }
+    for mut text in screens.iter_mut() {
+        text.sections[0].value = builder.summary();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::BallSize;
+
+    #[test]
+    fn adjusting_stays_in_range() {
+        let mut rules = MatchRules::default();
+        for _ in 0..64 {
+            adjust(&mut rules, 0, 1);
+            adjust(&mut rules, 2, -1);
+        }
+        assert_eq!(rules.points_to_win, MAX_POINTS_TO_WIN);
+        assert_eq!(rules.ball_speed, MIN_SCALE);
+
+        for _ in 0..4 {
+            adjust(&mut rules, 5, 1);
+        }
+        assert_eq!(rules.ball_size, BallSize::Huge);
+        adjust(&mut rules, 5, -1);
+        assert_eq!(rules.ball_size, BallSize::Normal);
+
+        for _ in 0..4 {
+            adjust(&mut rules, 7, 1);
+        }
+        assert_eq!(rules.paddle_lengths, [1f32, MAX_PADDLE_LENGTH]);
+
+        adjust(&mut rules, SETTING_ROWS, 1);
+        assert!(rules.mutators.fast_ball);
+        adjust(&mut rules, SETTING_ROWS, -1);
+        assert!(!rules.mutators.fast_ball);
+        assert_eq!(rows(&rules).len(), SETTING_ROWS + rules.mutators.flags_mut().len());
+    }
+
+    #[test]
+    fn saving_under_a_taken_name_replaces_it() {
+        let mut rulesets = Rulesets::default();
+        let ruleset = |name: &str, points_to_win| Ruleset {
+            name: name.into(),
+            rules: MatchRules { points_to_win, ..default() },
+        };
+        assert_eq!(rulesets.put(ruleset("quick", 3)), 0);
+        assert_eq!(rulesets.put(ruleset("long", 15)), 1);
+        assert_eq!(rulesets.put(ruleset("quick", 5)), 0);
+        assert_eq!(rulesets.saved.len(), 2);
+        assert_eq!(rulesets.saved[0].rules.points_to_win, 5);
+    }
+
+    #[test]
+    fn winning_by_two_plays_past_match_point() {
+        let rules = MatchRules { points_to_win: 5, win_by: 2, ..default() };
+        assert!(!rules.won(5, 4));
+        assert!(rules.won(6, 4));
+        assert!(rules.won(5, 3));
+        assert!(!rules.won(4, 0));
+    }
+}
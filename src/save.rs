@@ -0,0 +1,204 @@
+use bevy::{app::AppExit, prelude::*, window::ApplicationLifetime};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    replay::Replay, rules::MatchRules, storage, Ball, GameRng, GameState, Interpolated, MatchClock, MatchOver,
+    Paddle, Persist, Rally, Score, ServeDir, Side,
+};
+
+const SAVE_FILE: &str = "save.ron";
+const SAVE_VERSION: u32 = 2;
+
+/// Where the match's RNG had got to, so serves and the AI's aim carry on as they would have.
+#[derive(Serialize, Deserialize)]
+struct RngState {
+    seed: u64,
+    /// ChaCha's 128-bit word position, high half first.
+    word_pos: [u64; 2],
+}
+
+impl RngState {
+    fn of(rng: &GameRng) -> Self {
+        let word_pos = rng.rng.get_word_pos();
+        RngState {
+            seed: rng.seed,
+            word_pos: [(word_pos >> 64) as u64, word_pos as u64],
+        }
+    }
+
+    fn restore(&self) -> GameRng {
+        let mut rng = GameRng::from_seed(self.seed);
+        rng.rng.set_word_pos(((self.word_pos[0] as u128) << 64) | self.word_pos[1] as u128);
+        rng
+    }
+}
+
+/// An in-progress match, written when the game quits and picked back up on the next launch.
+#[derive(Resource, Serialize, Deserialize)]
+pub struct SavedMatch {
+    version: u32,
+    pub state: GameState,
+    pub rules: MatchRules,
+    score: Score,
+    clock: MatchClock,
+    serve_dir: ServeDir,
+    rally: Rally,
+    rng: RngState,
+    /// Each ball alongside the side of the paddle that last hit it.
+    balls: Vec<(Vec2, Ball, Option<Side>)>,
+    players: Vec<(Vec2, Paddle)>,
+    enemies: Vec<(Vec2, Paddle)>,
+}
+
+/// Just enough of a save to tell which version wrote it, so an older one can be cleared without being understood.
+#[derive(Deserialize)]
+struct SaveHeader {
+    #[serde(default)]
+    version: u32,
+}
+
+impl SavedMatch {
+    /// Loads the save slot and clears it, so a match can only be resumed once.
+    pub fn take() -> Option<Self> {
+        let path = storage::data_path(SAVE_FILE)?;
+        let header: SaveHeader = storage::load_ron(&path)?;
+        if header.version != SAVE_VERSION {
+            warn!("Save slot is version {}, expected {}", header.version, SAVE_VERSION);
+            SavedMatch::clear();
+            return None;
+        }
+        let saved = storage::load_ron(&path);
+        SavedMatch::clear();
+        saved
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = storage::data_path(SAVE_FILE).ok_or("no data directory")?;
+        storage::save_ron(&path, self)
+    }
+
+    /// Empties the save slot, so a match that has since ended or restarted isn't picked back up.
+    pub fn clear() {
+        let Some(path) = storage::data_path(SAVE_FILE) else {
+            return;
+        };
+        if let Err(err) = storage::remove(&path) {
+            warn!("Failed to clear save slot: {}", err);
+        }
+    }
+}
+
+/// Saves on quit, and when a mobile app is suspended since the OS may kill it without warning. A match that's
+/// fresh or finished by then clears whatever an earlier suspend left in the slot.
+pub fn save_match_on_exit(
+    mut exits: EventReader<AppExit>,
+    mut lifetimes: EventReader<ApplicationLifetime>,
+    state: Res<State<GameState>>,
+    score: Res<Score>,
+    clock: Res<MatchClock>,
+    serve_dir: Res<ServeDir>,
+    rally: Res<Rally>,
+    rng: Res<GameRng>,
+    persist: Res<Persist>,
+    rules: Res<MatchRules>,
+    balls: Query<(&Interpolated, &Ball)>,
+    paddles: Query<(Entity, &Interpolated, &Paddle, &Side)>,
+) {
+    let suspended = lifetimes.read().any(|event| *event == ApplicationLifetime::Suspended);
+    if (exits.read().count() == 0 && !suspended) || !persist.0 {
+        return;
+    }
+    let fresh = *state.get() == GameState::Serving && score.player == 0 && score.enemy == 0;
+    let finished = rules.won(score.player, score.enemy) || rules.won(score.enemy, score.player);
+    if fresh || finished {
+        SavedMatch::clear();
+        return;
+    }
+
+    let saved = SavedMatch {
+        version: SAVE_VERSION,
+        state: state.get().clone(),
+        rules: rules.clone(),
+        score: score.clone(),
+        clock: clock.clone(),
+        serve_dir: serve_dir.clone(),
+        rally: rally.clone(),
+        rng: RngState::of(&rng),
+        balls: balls.iter()
+            .map(|(interp, ball)| {
+                let last_hit = ball.last_hit.and_then(|hit| paddles.get(hit).ok()).map(|(.., side)| *side);
+                (interp.current, ball.clone(), last_hit)
+            })
+            .collect(),
+        players: paddles.iter()
+            .filter(|(.., side)| **side == Side::Left)
+            .map(|(_, interp, paddle, _)| (interp.current, paddle.clone()))
+            .collect(),
+        enemies: paddles.iter()
+            .filter(|(.., side)| **side == Side::Right)
+            .map(|(_, interp, paddle, _)| (interp.current, paddle.clone()))
+            .collect(),
+    };
+    match saved.save() {
+        Ok(()) => info!("Saved match in progress"),
+        Err(err) => warn!("Failed to save match: {}", err),
+    }
+}
+
+/// A suspended app can carry on to the end of its match, which then mustn't be resumed on the next launch.
+pub fn clear_save_on_match_over(mut match_overs: EventReader<MatchOver>, persist: Res<Persist>) {
+    if match_overs.read().count() > 0 && persist.0 {
+        SavedMatch::clear();
+    }
+}
+
+/// Picks the saved match back up where it was left. The rules are applied in `main`, before the bodies are built.
+/// Replaying from the seed couldn't reproduce the part played before the save, so the replay is discarded.
+pub fn resume_match(
+    mut cmd: Commands,
+    saved: Option<Res<SavedMatch>>,
+    mut score: ResMut<Score>,
+    mut clock: ResMut<MatchClock>,
+    mut serve_dir: ResMut<ServeDir>,
+    mut rally: ResMut<Rally>,
+    mut rng: ResMut<GameRng>,
+    mut replay: ResMut<Replay>,
+    mut balls: Query<(&mut Interpolated, &mut Transform, &mut Ball), Without<Paddle>>,
+    mut paddles: Query<(Entity, &mut Interpolated, &mut Transform, &mut Paddle, &Side)>,
+) {
+    let Some(saved) = saved else {
+        return;
+    };
+    info!("Resuming saved match at {}-{}", saved.score.player, saved.score.enemy);
+
+    *score = saved.score.clone();
+    *clock = saved.clock.clone();
+    *serve_dir = saved.serve_dir.clone();
+    *rally = saved.rally.clone();
+    *rng = saved.rng.restore();
+    replay.discard();
+    let sides: Vec<(Entity, Side)> = paddles.iter().map(|(entity, .., side)| (entity, *side)).collect();
+    for ((mut interp, mut transform, mut ball), (pos, saved, last_hit)) in balls.iter_mut().zip(&saved.balls) {
+        *interp = Interpolated::at(*pos);
+        transform.translation = pos.extend(transform.translation.z);
+        *ball = Ball {
+            last_hit: last_hit.and_then(|hit| sides.iter().find(|(_, side)| *side == hit).map(|(entity, _)| *entity)),
+            ..saved.clone()
+        };
+    }
+    let mut players = saved.players.iter();
+    let mut enemies = saved.enemies.iter();
+    for (_, mut interp, mut transform, mut paddle, side) in paddles.iter_mut() {
+        let next = match side {
+            Side::Left => players.next(),
+            Side::Right => enemies.next(),
+        };
+        let Some((pos, saved)) = next else {
+            continue;
+        };
+        *interp = Interpolated::at(*pos);
+        transform.translation.y = pos.y;
+        *paddle = saved.clone();
+    }
+    cmd.remove_resource::<SavedMatch>();
+}
@@ -0,0 +1,205 @@
+use std::{io, net::SocketAddr};
+
+use bevy::prelude::*;
+
+use crate::{
+    chat::NetworkController,
+    net::{self, Link, MatchView, NetConditions, NetMessage},
+    settings::Settings,
+    tournament::Tournament,
+    Arena, Collider, Paddle, PaddleMotion, ProfileName, ServeRequested, Side,
+};
+
+const SERVER_NAME: &str = "Server";
+const PING_INTERVAL: f32 = 1f32;
+
+struct Client {
+    addr: SocketAddr,
+    name: String,
+    ping_ms: Option<u32>,
+    paddle_y: Option<f32>,
+    last_heard: f64,
+}
+
+/// Runs the match for two players who both connect with `--join`; the first to say hello plays the left paddle.
+#[derive(Resource)]
+pub struct DedicatedServer {
+    link: Link,
+    players: [Option<Client>; 2],
+    spectators: Vec<SocketAddr>,
+    ping_timer: Timer,
+    /// Closed while the server runs a tournament; everyone who says hello watches instead.
+    seats_open: bool,
+}
+
+impl DedicatedServer {
+    pub fn bind(port: u16, conditions: NetConditions) -> io::Result<Self> {
+        Ok(DedicatedServer {
+            link: Link::bind(SocketAddr::from(([0, 0, 0, 0], port)), None, conditions)?,
+            players: [None, None],
+            spectators: Vec::new(),
+            ping_timer: Timer::from_seconds(PING_INTERVAL, TimerMode::Repeating),
+            seats_open: true,
+        })
+    }
+
+    pub fn close_seats(&mut self) {
+        self.seats_open = false;
+    }
+
+    /// A chat line from the server to everyone connected.
+    pub fn announce(&self, text: &str) {
+        info!("{}", text);
+        self.broadcast(&NetMessage::Chat { name: SERVER_NAME.into(), text: text.into() }, None);
+    }
+
+    fn slot(&self, addr: SocketAddr) -> Option<usize> {
+        self.players.iter().position(|client| client.as_ref().is_some_and(|client| client.addr == addr))
+    }
+
+    /// A seat whose player went quiet, which they can take back by saying hello under the same name.
+    fn abandoned_slot(&self, name: &str, now: f64) -> Option<usize> {
+        self.players.iter().position(|client| {
+            client.as_ref().is_some_and(|client| client.name == name && now - client.last_heard > net::DISCONNECT_TIMEOUT)
+        })
+    }
+
+    fn broadcast(&self, message: &NetMessage, except: Option<SocketAddr>) {
+        let players = self.players.iter().flatten().map(|client| &client.addr);
+        for addr in players.chain(&self.spectators) {
+            if Some(*addr) != except {
+                self.link.send_to(message, *addr);
+            }
+        }
+    }
+}
+
+pub fn attach_client_controllers(
+    mut cmd: Commands,
+    paddles: Query<Entity, With<Paddle>>,
+    mut names: Query<&mut Text, With<ProfileName>>,
+) {
+    for paddle in paddles.iter() {
+        cmd.entity(paddle).insert(NetworkController::default());
+    }
+    for mut name in names.iter_mut() {
+        name.sections[0].value = "Waiting...".into();
+    }
+}
+
+pub fn receive_clients(
+    mut server: ResMut<DedicatedServer>,
+    time: Res<Time<Real>>,
+    mut serve: ResMut<ServeRequested>,
+    mut controllers: Query<(&mut NetworkController, &Side)>,
+) {
+    let welcome = |left| NetMessage::Welcome { name: SERVER_NAME.into(), left };
+    let now = time.elapsed_seconds_f64();
+    for (from, message) in server.link.receive() {
+        let slot = server.slot(from);
+        if let Some(client) = slot.and_then(|slot| server.players[slot].as_mut()) {
+            client.last_heard = now;
+        }
+        let message = match message {
+            Ok(message) => message,
+            Err(err) => {
+                warn!("Turned away {}: {}", from, err);
+                server.link.reject(from);
+                continue;
+            },
+        };
+        // With the seats closed, players watch like everyone else.
+        let message = match message {
+            NetMessage::Hello { name } if !server.seats_open => NetMessage::Spectate { name },
+            message => message,
+        };
+        match message {
+            NetMessage::Spectate { name } => {
+                if !server.spectators.contains(&from) {
+                    info!("{} is spectating from {}", name, from);
+                    server.spectators.push(from);
+                }
+                server.link.send_to(&welcome(false), from);
+            },
+            NetMessage::Hello { name } => {
+                if let Some(rejoined) = slot.is_none().then(|| server.abandoned_slot(&name, now)).flatten() {
+                    info!("{} reconnected from {}", name, from);
+                    if let Some(client) = server.players[rejoined].as_mut() {
+                        client.addr = from;
+                        client.last_heard = now;
+                    }
+                    server.link.send_to(&welcome(rejoined == 0), from);
+                    continue;
+                }
+                let Some(open) = slot.or_else(|| server.players.iter().position(Option::is_none)) else {
+                    continue;
+                };
+                if slot.is_none() {
+                    info!("{} joined from {} on the {} side", name, from, if open == 0 { "left" } else { "right" });
+                    server.players[open] = Some(Client { addr: from, name, ping_ms: None, paddle_y: None, last_heard: now });
+                }
+                server.link.send_to(&welcome(open == 0), from);
+            },
+            NetMessage::Ping { sent } => server.link.send_to(&NetMessage::Pong { sent }, from),
+            NetMessage::Pong { sent } => {
+                if let Some(client) = slot.and_then(|slot| server.players[slot].as_mut()) {
+                    client.ping_ms = Some(net::ping_ms(&time, sent));
+                }
+            },
+            NetMessage::Chat { name, text } if slot.is_some() || server.spectators.contains(&from) => {
+                server.broadcast(&NetMessage::Chat { name, text }, Some(from));
+            },
+            NetMessage::Input { dir, serve: client_serve, y } => {
+                let Some(slot) = slot else {
+                    continue;
+                };
+                if let Some(client) = server.players[slot].as_mut() {
+                    client.paddle_y = y.is_finite().then_some(y);
+                }
+                for (mut controller, side) in controllers.iter_mut() {
+                    if side.index() == slot {
+                        controller.dir = net::validate_dir(dir);
+                    }
+                }
+                serve.0 |= client_serve || dir != 0;
+            },
+            _ => {},
+        }
+    }
+}
+
+pub fn compensate_client_paddles(
+    server: Res<DedicatedServer>,
+    settings: Res<Settings>,
+    arena: Res<Arena>,
+    mut paddles: Query<(&mut Transform, &PaddleMotion, &Collider, &Side), With<NetworkController>>,
+) {
+    if !settings.net.lag_compensation {
+        return;
+    }
+    for (mut transform, motion, collider, side) in paddles.iter_mut() {
+        let client = server.players[side.index()].as_ref();
+        if let Some((y, client)) = client.and_then(|client| Some((client.paddle_y?, client))) {
+            let max_y = arena.half_size.y - collider.half_size.y;
+            net::compensate_lag(&mut transform, motion, max_y, y, client.ping_ms);
+        }
+    }
+}
+
+pub fn ping_clients(time: Res<Time<Real>>, mut server: ResMut<DedicatedServer>) {
+    if !server.ping_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let ping = NetMessage::Ping { sent: time.elapsed_seconds_f64() };
+    for client in server.players.iter().flatten() {
+        server.link.send_to(&ping, client.addr);
+    }
+}
+
+pub fn send_snapshot(server: Res<DedicatedServer>, tournament: Option<Res<Tournament>>, view: MatchView) {
+    let name = |client: &Option<Client>| client.as_ref().map_or_else(|| "Waiting...".into(), |client| client.name.clone());
+    let ping = |client: &Option<Client>| client.as_ref().and_then(|client| client.ping_ms);
+    let [left, right] = &server.players;
+    let names = tournament.map_or_else(|| [name(left), name(right)], |tournament| tournament.names());
+    server.broadcast(&view.snapshot(names, [ping(left), ping(right)]), None);
+}
@@ -0,0 +1,449 @@
+use std::time::Duration;
+
+use bevy::{
+    prelude::*,
+    utils::Instant,
+    app::AppExit,
+    window::{MonitorSelection, PresentMode, PrimaryWindow, WindowMode, WindowMoved, WindowPosition, WindowResized},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    spawn_toast, storage,
+    theme::{ColorPalette, HIGH_CONTRAST_TEXT_SCALE},
+    Arena,
+};
+
+const SETTINGS_FILE: &str = "settings.ron";
+const UI_SCALE_STEP: f32 = 0.25f32;
+const UI_SCALE_MIN: f32 = 0.5f32;
+const UI_SCALE_MAX: f32 = 3f32;
+const GAME_SPEED_STEP: f32 = 0.25f32;
+const GAME_SPEED_MIN: f32 = 0.5f32;
+const GAME_SPEED_MAX: f32 = 2f32;
+const JUICE_STEP: f32 = 0.25f32;
+const MAX_JUICE: f32 = 1.5f32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn aim_error(&self, normal: f32) -> f32 {
+        match self {
+            Difficulty::Easy => normal * 2f32,
+            Difficulty::Normal => normal,
+            Difficulty::Hard => normal / 3f32,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bindings {
+    pub up: KeyCode,
+    pub down: KeyCode,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Bindings {
+            up: KeyCode::KeyW,
+            down: KeyCode::KeyS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Presentation {
+    /// The court grows and shrinks with the window.
+    #[default]
+    Stretch,
+    /// The court keeps its classic square shape, scaled to fit, with bars filling the rest.
+    Letterbox,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VideoSettings {
+    pub fullscreen: bool,
+    pub presentation: Presentation,
+    /// `Fifo` is vsync, `Mailbox` is vsync without the added latency, `Immediate` tears but is fastest.
+    pub present_mode: PresentMode,
+    /// Sleeps between frames to stay under this many frames per second, to save battery.
+    pub frame_cap: Option<f64>,
+    /// Hide the OS cursor while a rally is in play.
+    pub hide_cursor: bool,
+    /// Extra scale for text and UI, on top of the OS scale factor.
+    pub ui_scale: f32,
+    /// Index of the monitor to open on when there's no remembered window position.
+    pub monitor: Option<usize>,
+    /// Where the window was and how big it was when the game last closed, in windowed mode.
+    pub window_position: Option<IVec2>,
+    pub window_size: Option<Vec2>,
+}
+
+impl Default for VideoSettings {
+    fn default() -> Self {
+        VideoSettings {
+            fullscreen: false,
+            presentation: Presentation::Stretch,
+            present_mode: PresentMode::AutoNoVsync,
+            frame_cap: None,
+            hide_cursor: true,
+            ui_scale: 1f32,
+            monitor: None,
+            window_position: None,
+            window_size: None,
+        }
+    }
+}
+
+impl VideoSettings {
+    pub fn window_mode(&self) -> WindowMode {
+        if self.fullscreen { WindowMode::BorderlessFullscreen } else { WindowMode::Windowed }
+    }
+
+    pub fn window_position(&self) -> WindowPosition {
+        match (self.window_position, self.monitor) {
+            (Some(position), _) => WindowPosition::At(position),
+            (None, Some(index)) => WindowPosition::Centered(MonitorSelection::Index(index)),
+            (None, None) => WindowPosition::Automatic,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetSettings {
+    /// Frames online inputs are held back before use; more means fewer rollbacks but a laggier feel.
+    pub input_delay: usize,
+    /// Matchbox signaling server that pairs up players who join the same room code.
+    pub relay_url: String,
+    /// Star out swear words in the in-match chat.
+    pub chat_filter: bool,
+    /// When hosting, trust where the guest saw its paddle, within what its ping allows, so saves register fairly.
+    pub lag_compensation: bool,
+    /// When a rollback match desyncs, write this end's state to the data directory to attach to a bug report.
+    pub dump_desyncs: bool,
+}
+
+impl Default for NetSettings {
+    fn default() -> Self {
+        NetSettings {
+            input_delay: 2,
+            relay_url: "ws://127.0.0.1:3536".into(),
+            chat_filter: true,
+            lag_compensation: true,
+            dump_desyncs: false,
+        }
+    }
+}
+
+impl NetSettings {
+    /// The relay's HTTP address, on the same host and port as its signaling socket.
+    pub fn relay_http_url(&self) -> String {
+        let url = self.relay_url.trim_end_matches('/');
+        if let Some(rest) = url.strip_prefix("wss://") {
+            format!("https://{}", rest)
+        }
+        else if let Some(rest) = url.strip_prefix("ws://") {
+            format!("http://{}", rest)
+        }
+        else {
+            url.to_string()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccessibilitySettings {
+    /// Turns off screen shake, slow motion, particles and animated backgrounds, all at once.
+    pub reduced_motion: bool,
+    /// The colors players pick from, and whether paddles are also told apart by pattern.
+    pub palette: ColorPalette,
+    /// The ball hums as it moves, higher toward the top and panned toward its side, and points are announced
+    /// with tones, so the game can be played by ear.
+    pub audio_cues: bool,
+    /// White on black with outlined paddles and ball, and larger text.
+    pub high_contrast: bool,
+}
+
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub difficulty: Difficulty,
+    /// How fast offline matches run, from 0.5 for a slower, more readable game to 2 for chaos.
+    pub game_speed: f32,
+    pub video: VideoSettings,
+    pub net: NetSettings,
+    pub accessibility: AccessibilitySettings,
+    /// How strongly shakes, particles, hit-stops and popups play, from 0 for none to 1.5 for extra.
+    pub juice: f32,
+    /// Write a JSON and CSV breakdown of every finished match to the data directory.
+    pub export_match_data: bool,
+    /// Send new longest rallies to `leaderboard_url` and show its global top 10.
+    pub online_leaderboard: bool,
+    pub leaderboard_url: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            difficulty: Difficulty::default(),
+            game_speed: 1f32,
+            video: VideoSettings::default(),
+            net: NetSettings::default(),
+            accessibility: AccessibilitySettings::default(),
+            juice: 1f32,
+            export_match_data: false,
+            online_leaderboard: false,
+            leaderboard_url: String::new(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        storage::config_path(SETTINGS_FILE)
+            .and_then(|path| storage::load_ron(&path))
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = storage::config_path(SETTINGS_FILE).ok_or("no config directory")?;
+        storage::save_ron(&path, self)
+    }
+
+    /// The user's UI scale, with high contrast's larger text on top.
+    pub fn text_scale(&self) -> f32 {
+        let contrast = if self.accessibility.high_contrast { HIGH_CONTRAST_TEXT_SCALE } else { 1f32 };
+        self.video.ui_scale * contrast
+    }
+
+    /// What every game-feel effect scales its size by: the juice setting, or nothing with reduced motion.
+    pub fn effect_scale(&self) -> f32 {
+        if self.accessibility.reduced_motion { 0f32 } else { self.juice.clamp(0f32, MAX_JUICE) }
+    }
+}
+
+pub fn save_settings(settings: Res<Settings>) {
+    if !settings.is_changed() || settings.is_added() {
+        return;
+    }
+    if let Err(err) = settings.save() {
+        warn!("Failed to save settings: {}", err);
+    }
+}
+
+pub fn toggle_fullscreen(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<Settings>,
+) {
+    let alt = keyboard_input.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
+    if keyboard_input.just_pressed(KeyCode::F11) || (alt && keyboard_input.just_pressed(KeyCode::Enter)) {
+        settings.video.fullscreen = !settings.video.fullscreen;
+    }
+}
+
+pub fn apply_video_settings(
+    settings: Res<Settings>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    for mut window in windows.iter_mut() {
+        let mode = settings.video.window_mode();
+        if window.mode != mode {
+            window.mode = mode;
+        }
+        if window.present_mode != settings.video.present_mode {
+            window.present_mode = settings.video.present_mode;
+        }
+    }
+}
+
+/// Ctrl with + and - steps the UI scale, and Ctrl+0 resets it.
+pub fn adjust_ui_scale(
+    mut cmd: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    arena: Res<Arena>,
+    mut settings: ResMut<Settings>,
+) {
+    if !keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    let scale = if keyboard_input.any_just_pressed([KeyCode::Equal, KeyCode::NumpadAdd]) {
+        settings.video.ui_scale + UI_SCALE_STEP
+    }
+    else if keyboard_input.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        settings.video.ui_scale - UI_SCALE_STEP
+    }
+    else if keyboard_input.any_just_pressed([KeyCode::Digit0, KeyCode::Numpad0]) {
+        1f32
+    }
+    else {
+        return;
+    };
+    settings.video.ui_scale = scale.clamp(UI_SCALE_MIN, UI_SCALE_MAX);
+    spawn_toast(&mut cmd, &arena, format!("UI scale {}%", (settings.video.ui_scale * 100f32).round()));
+}
+
+/// Ctrl+M switches reduced motion on and off.
+pub fn toggle_reduced_motion(
+    mut cmd: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    arena: Res<Arena>,
+    mut settings: ResMut<Settings>,
+) {
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if !ctrl || !keyboard_input.just_pressed(KeyCode::KeyM) {
+        return;
+    }
+    settings.accessibility.reduced_motion = !settings.accessibility.reduced_motion;
+    let state = if settings.accessibility.reduced_motion { "on" } else { "off" };
+    spawn_toast(&mut cmd, &arena, format!("Reduced motion {}", state));
+}
+
+/// Ctrl+J turns the juice up a step, going back to none after the most.
+pub fn adjust_juice(
+    mut cmd: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    arena: Res<Arena>,
+    mut settings: ResMut<Settings>,
+) {
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if !ctrl || !keyboard_input.just_pressed(KeyCode::KeyJ) {
+        return;
+    }
+    let next = settings.juice + JUICE_STEP;
+    settings.juice = if next > MAX_JUICE + f32::EPSILON { 0f32 } else { next };
+    spawn_toast(&mut cmd, &arena, format!("Juice {:.0}%", settings.juice * 100f32));
+}
+
+/// Run condition for every effect that shakes, slows or animates the screen.
+pub fn full_motion(settings: Res<Settings>) -> bool {
+    !settings.accessibility.reduced_motion
+}
+
+/// F7 slows the game down a step and F8 speeds it up.
+pub fn adjust_game_speed(
+    mut cmd: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    arena: Res<Arena>,
+    mut settings: ResMut<Settings>,
+) {
+    let speed = if keyboard_input.just_pressed(KeyCode::F7) {
+        settings.game_speed - GAME_SPEED_STEP
+    }
+    else if keyboard_input.just_pressed(KeyCode::F8) {
+        settings.game_speed + GAME_SPEED_STEP
+    }
+    else {
+        return;
+    };
+    settings.game_speed = speed.clamp(GAME_SPEED_MIN, GAME_SPEED_MAX);
+    spawn_toast(&mut cmd, &arena, format!("Game speed {}%", (settings.game_speed * 100f32).round()));
+}
+
+/// Gameplay, and the timers and effects that follow it, run on virtual time; toasts and chat stay on real time.
+pub fn apply_game_speed(settings: Res<Settings>, mut time: ResMut<Time<Virtual>>) {
+    if !settings.is_changed() {
+        return;
+    }
+    let speed = settings.game_speed.clamp(GAME_SPEED_MIN, GAME_SPEED_MAX);
+    if time.relative_speed() != speed {
+        time.set_relative_speed(speed);
+    }
+}
+
+/// Window scale factors are already applied by Bevy, so this only layers the user's scale on top.
+/// `UiScale` covers bevy_ui nodes; world-space text is scaled through its transform.
+pub fn apply_ui_scale(
+    settings: Res<Settings>,
+    mut ui_scale: ResMut<UiScale>,
+    mut texts: ParamSet<(
+        Query<&mut Transform, (With<Text>, Without<Node>)>,
+        Query<&mut Transform, (Added<Text>, Without<Node>)>,
+    )>,
+) {
+    let scale = settings.text_scale();
+    if settings.is_changed() {
+        if ui_scale.0 != scale {
+            ui_scale.0 = scale;
+        }
+        for mut transform in texts.p0().iter_mut() {
+            transform.scale = Vec3::new(scale, scale, 1f32);
+        }
+    }
+    else {
+        for mut transform in texts.p1().iter_mut() {
+            transform.scale = Vec3::new(scale, scale, 1f32);
+        }
+    }
+}
+
+/// Remembers the windowed geometry without saving, since the window sends a move event per pixel while dragged.
+pub fn track_window_geometry(
+    mut moved: EventReader<WindowMoved>,
+    mut resized: EventReader<WindowResized>,
+    mut settings: ResMut<Settings>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+) {
+    let moved = moved.read().last().map(|event| event.position);
+    let resized = resized.read().count() > 0;
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    if window.mode != WindowMode::Windowed {
+        return;
+    }
+    let video = &mut settings.bypass_change_detection().video;
+    if let Some(position) = moved {
+        video.window_position = Some(position);
+    }
+    if resized {
+        video.window_size = Some(Vec2::new(window.width(), window.height()));
+    }
+}
+
+/// Saves the window geometry on exit, on top of the file on disk so command-line overrides aren't persisted.
+pub fn save_window_geometry_on_exit(mut exit: EventReader<AppExit>, settings: Res<Settings>) {
+    if exit.read().count() == 0 {
+        return;
+    }
+    let mut saved = Settings::load();
+    if saved.video.window_position == settings.video.window_position
+        && saved.video.window_size == settings.video.window_size
+    {
+        return;
+    }
+    saved.video.window_position = settings.video.window_position;
+    saved.video.window_size = settings.video.window_size;
+    if let Err(err) = saved.save() {
+        warn!("Failed to save settings: {}", err);
+    }
+}
+
+pub fn limit_frame_rate(
+    settings: Res<Settings>,
+    mut last_frame: Local<Option<Instant>>,
+) {
+    // The browser already paces frames, and can't sleep the main thread anyway.
+    if cfg!(target_arch = "wasm32") {
+        return;
+    }
+    if let (Some(fps), Some(last_frame)) = (settings.video.frame_cap.filter(|fps| *fps > 0f64), *last_frame) {
+        let frame_time = Duration::from_secs_f64(1f64 / fps);
+        let elapsed = last_frame.elapsed();
+        if elapsed < frame_time {
+            std::thread::sleep(frame_time - elapsed);
+        }
+    }
+    *last_frame = Some(Instant::now());
+}
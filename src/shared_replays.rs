@@ -0,0 +1,267 @@
+use std::fmt::Write as _;
+
+use bevy::{
+    prelude::*,
+    tasks::{block_on, futures_lite::future, IoTaskPool, Task},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    profiles::Profiles,
+    replay::{Replay, ReplayData},
+    restart_match,
+    settings::Settings,
+    spawn_toast, Arena, Persist, Score,
+};
+
+const FONT_SIZE: f32 = 16f32;
+/// Shared replays listed at once; each is picked with its number key.
+const MAX_LISTED: usize = 9;
+const DIGITS: [KeyCode; MAX_LISTED] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+/// A replay someone shared, as the relay lists it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SharedReplay {
+    pub code: String,
+    pub name: String,
+    pub player_score: i32,
+    pub enemy_score: i32,
+}
+
+#[derive(Serialize)]
+struct Upload<'a> {
+    name: &'a str,
+    player_score: i32,
+    enemy_score: i32,
+    replay: &'a ReplayData,
+}
+
+enum Response {
+    Listed(Vec<SharedReplay>),
+    Shared(String),
+    Downloaded(ReplayData),
+}
+
+/// The relay's replay shelf, and the one request to it that may be in flight.
+#[derive(Resource, Default)]
+pub struct SharedReplays {
+    listed: Vec<SharedReplay>,
+    request: Option<Task<Result<Response, String>>>,
+}
+
+impl SharedReplays {
+    /// Like the online leaderboard, a newer request replaces one still in flight.
+    fn start(&mut self, request: impl FnOnce() -> Result<Response, String> + Send + 'static) {
+        self.request = Some(IoTaskPool::get().spawn(async move { request() }));
+    }
+
+    fn summary(&self) -> String {
+        let mut summary = String::from("SHARED REPLAYS\n");
+        if self.listed.is_empty() {
+            summary.push_str("\nNothing shared yet");
+        }
+        for (i, shared) in self.listed.iter().take(MAX_LISTED).enumerate() {
+            let _ = write!(
+                summary,
+                "\n{}. {} ({}-{})   {}",
+                i + 1,
+                shared.name,
+                shared.player_score,
+                shared.enemy_score,
+                shared.code
+            );
+        }
+        summary.push_str("\n\n1-9 watch   F5 share this match   F6 close");
+        summary
+    }
+}
+
+/// The relay serves replays over HTTP next to its signaling socket: `POST /replays` stores one and answers
+/// with its code, `GET /replays` lists the latest, and `GET /replays/<code>` fetches one.
+#[cfg(all(feature = "online", not(target_arch = "wasm32")))]
+mod http {
+    use super::{Response, SharedReplay, Upload};
+    use crate::replay::ReplayData;
+
+    #[derive(serde::Deserialize)]
+    struct Shared {
+        code: String,
+    }
+
+    pub fn list(url: &str) -> Result<Response, String> {
+        let listed: Vec<SharedReplay> = ureq::get(&format!("{}/replays", url))
+            .call()
+            .map_err(|err| err.to_string())?
+            .into_json()
+            .map_err(|err| err.to_string())?;
+        Ok(Response::Listed(listed))
+    }
+
+    pub fn share(url: &str, upload: &Upload) -> Result<Response, String> {
+        let shared: Shared = ureq::post(&format!("{}/replays", url))
+            .send_json(upload)
+            .map_err(|err| err.to_string())?
+            .into_json()
+            .map_err(|err| err.to_string())?;
+        Ok(Response::Shared(shared.code))
+    }
+
+    pub fn download(url: &str, code: &str) -> Result<Response, String> {
+        let replay: ReplayData = ureq::get(&format!("{}/replays/{}", url, code))
+            .call()
+            .map_err(|err| err.to_string())?
+            .into_json()
+            .map_err(|err| err.to_string())?;
+        Ok(Response::Downloaded(replay))
+    }
+}
+
+#[cfg(not(all(feature = "online", not(target_arch = "wasm32"))))]
+mod http {
+    use super::{Response, Upload};
+
+    pub fn list(_url: &str) -> Result<Response, String> {
+        Err("shared replays aren't available in this build".into())
+    }
+
+    pub fn share(_url: &str, _upload: &Upload) -> Result<Response, String> {
+        Err("shared replays aren't available in this build".into())
+    }
+
+    pub fn download(_url: &str, _code: &str) -> Result<Response, String> {
+        Err("shared replays aren't available in this build".into())
+    }
+}
+
+#[derive(Component)]
+pub struct SharedReplaysScreen;
+
+pub fn spawn_shared_replays_screen(mut cmd: Commands) {
+    cmd.spawn((
+        Text2dBundle {
+            text: Text::from_section("", TextStyle {
+                font_size: FONT_SIZE,
+                ..default()
+            })
+            .with_justify(JustifyText::Center),
+            transform: Transform::from_xyz(0f32, 0f32, 5f32),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        SharedReplaysScreen,
+    ));
+}
+
+/// The code passed to `--watch`.
+#[derive(Resource)]
+pub struct WatchCode(pub String);
+
+pub fn download_shared_replay(settings: Res<Settings>, code: Res<WatchCode>, mut shared: ResMut<SharedReplays>) {
+    let url = settings.net.relay_http_url();
+    let code = code.0.clone();
+    shared.start(move || http::download(&url, &code));
+}
+
+/// F5 shares the match so far, F6 opens the list of shared replays, and a number key there watches one.
+pub fn browse_shared_replays(
+    mut cmd: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    profiles: Res<Profiles>,
+    replay: Res<Replay>,
+    score: Res<Score>,
+    arena: Res<Arena>,
+    mut shared: ResMut<SharedReplays>,
+    mut screens: Query<(&mut Text, &mut Visibility), With<SharedReplaysScreen>>,
+) {
+    let url = settings.net.relay_http_url();
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        if replay.is_playing() {
+            spawn_toast(&mut cmd, &arena, "Only your own matches can be shared".into());
+        }
+        else if replay.is_discarded() {
+            spawn_toast(&mut cmd, &arena, "A resumed match can't be shared".into());
+        }
+        else {
+            let name = profiles.active().name.clone();
+            let (player_score, enemy_score) = (score.player, score.enemy);
+            let data = replay.data().clone();
+            shared.start(move || {
+                http::share(&url, &Upload { name: &name, player_score, enemy_score, replay: &data })
+            });
+            spawn_toast(&mut cmd, &arena, "Sharing replay...".into());
+        }
+        return;
+    }
+    for (mut text, mut visibility) in screens.iter_mut() {
+        if keyboard_input.just_pressed(KeyCode::F6) {
+            *visibility = if *visibility == Visibility::Hidden {
+                let url = url.clone();
+                shared.start(move || http::list(&url));
+                text.sections[0].value = "SHARED REPLAYS\n\nLoading...".into();
+                Visibility::Visible
+            }
+            else {
+                Visibility::Hidden
+            };
+            continue;
+        }
+        if *visibility == Visibility::Hidden {
+            continue;
+        }
+        let picked = DIGITS.iter().position(|key| keyboard_input.just_pressed(*key));
+        if let Some(code) = picked.and_then(|i| shared.listed.get(i)).map(|shared| shared.code.clone()) {
+            let url = url.clone();
+            shared.start(move || http::download(&url, &code));
+            *visibility = Visibility::Hidden;
+        }
+    }
+}
+
+pub fn poll_shared_replays(
+    mut cmd: Commands,
+    arena: Res<Arena>,
+    mut shared: ResMut<SharedReplays>,
+    mut screens: Query<&mut Text, With<SharedReplaysScreen>>,
+) {
+    let Some(request) = shared.bypass_change_detection().request.as_mut() else {
+        return;
+    };
+    let Some(result) = block_on(future::poll_once(request)) else {
+        return;
+    };
+    shared.request = None;
+    match result {
+        Ok(Response::Listed(listed)) => {
+            shared.listed = listed;
+            for mut text in screens.iter_mut() {
+                text.sections[0].value = shared.summary();
+            }
+        },
+        Ok(Response::Shared(code)) => {
+            info!("Shared replay as {}", code);
+            spawn_toast(&mut cmd, &arena, format!("Shared! Your friend can watch it with code {}", code));
+        },
+        Ok(Response::Downloaded(data)) => {
+            info!("Watching shared replay with seed {}", data.seed);
+            // Neither the replay's results nor the match it interrupts are the player's to keep.
+            cmd.insert_resource(Persist(false));
+            restart_match(&mut cmd, data.seed);
+            cmd.insert_resource(Replay::play(data));
+        },
+        Err(err) => {
+            warn!("Shared replay request failed: {}", err);
+            spawn_toast(&mut cmd, &arena, "Couldn't reach the relay".into());
+        },
+    }
+}
@@ -0,0 +1,177 @@
+use std::fmt::Write as _;
+
+use bevy::{prelude::*, sprite::Anchor};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    profiles::{Profile, Profiles},
+    replay::Replay,
+    settings::Difficulty,
+    spawn_toast, storage, Arena, MatchClock, MatchOver, Side,
+};
+
+const SPEEDRUN_FILE: &str = "speedrun.ron";
+const SPEEDRUN_VERSION: u32 = 1;
+const FONT_SIZE: f32 = 14f32;
+
+/// The opponents a run has to beat, in order.
+pub const GAUNTLET: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+/// A profile's best run, and its best time against each opponent, whichever run that came from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BestTimes {
+    pub version: u32,
+    pub total: Option<f32>,
+    pub splits: [Option<f32>; GAUNTLET.len()],
+}
+
+impl Default for BestTimes {
+    fn default() -> Self {
+        BestTimes {
+            version: SPEEDRUN_VERSION,
+            total: None,
+            splits: [None; GAUNTLET.len()],
+        }
+    }
+}
+
+impl BestTimes {
+    pub fn load(profile: &Profile) -> Self {
+        storage::data_path(&profile.data_file(SPEEDRUN_FILE))
+            .and_then(|path| storage::load_ron(&path))
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, profile: &Profile) -> Result<(), String> {
+        let path = storage::data_path(&profile.data_file(SPEEDRUN_FILE)).ok_or("no data directory")?;
+        storage::save_ron(&path, self)
+    }
+
+    /// Keeps whichever times beat the old ones, returning whether the run as a whole was a new best.
+    pub fn record(&mut self, splits: &[f32]) -> bool {
+        for (best, split) in self.splits.iter_mut().zip(splits) {
+            if best.map_or(true, |best| *split < best) {
+                *best = Some(*split);
+            }
+        }
+        let total = splits.iter().sum::<f32>();
+        let new_best = self.total.map_or(true, |best| total < best);
+        if new_best {
+            self.total = Some(total);
+        }
+        new_best
+    }
+}
+
+/// Beat every opponent in `GAUNTLET` as fast as possible. Lost matches are replayed against the same opponent,
+/// with the clock still running.
+#[derive(Resource)]
+pub struct Speedrun {
+    opponent: usize,
+    /// Time spent on the current opponent so far.
+    current: f32,
+    splits: Vec<f32>,
+    last_clock: f32,
+    best: BestTimes,
+}
+
+impl Speedrun {
+    pub fn new(best: BestTimes) -> Self {
+        Speedrun {
+            opponent: 0,
+            current: 0f32,
+            splits: Vec::new(),
+            last_clock: 0f32,
+            best,
+        }
+    }
+
+    fn finished(&self) -> bool {
+        self.opponent >= GAUNTLET.len()
+    }
+}
+
+#[derive(Component)]
+pub struct SpeedrunText;
+
+pub fn spawn_speedrun_text(mut cmd: Commands, arena: Res<Arena>) {
+    cmd.spawn((
+        Text2dBundle {
+            text: Text::from_section("", TextStyle {
+                font_size: FONT_SIZE,
+                ..default()
+            })
+            .with_justify(JustifyText::Right),
+            text_anchor: Anchor::BottomRight,
+            transform: Transform::from_xyz(arena.half_size.x - 8f32, -arena.half_size.y + 8f32, 3f32),
+            ..default()
+        },
+        SpeedrunText,
+    ));
+}
+
+/// Runs the timer off the match clock, so it stops whenever play does, and moves on to the next opponent after each win.
+pub fn track_speedrun(
+    mut cmd: Commands,
+    mut match_over: EventReader<MatchOver>,
+    clock: Res<MatchClock>,
+    arena: Res<Arena>,
+    profiles: Res<Profiles>,
+    mut replay: ResMut<Replay>,
+    mut run: ResMut<Speedrun>,
+) {
+    // The clock starts again from zero with every match.
+    let delta = if clock.elapsed >= run.last_clock { clock.elapsed - run.last_clock } else { clock.elapsed };
+    run.last_clock = clock.elapsed;
+    if run.finished() {
+        return;
+    }
+    run.current += delta;
+
+    for over in match_over.read() {
+        if over.winner != Side::Left || run.finished() {
+            continue;
+        }
+        let split = std::mem::take(&mut run.current);
+        run.splits.push(split);
+        run.opponent += 1;
+        if let Some(difficulty) = GAUNTLET.get(run.opponent) {
+            replay.set_difficulty(*difficulty);
+            continue;
+        }
+        let splits = run.splits.clone();
+        let new_best = run.best.record(&splits);
+        if let Err(err) = run.best.save(profiles.active()) {
+            warn!("Failed to save speedrun times: {}", err);
+        }
+        let message = if new_best { "New best time!" } else { "Gauntlet cleared!" };
+        spawn_toast(&mut cmd, &arena, format!("{} {}", message, format_time(splits.iter().sum())));
+    }
+}
+
+pub fn update_speedrun_text(run: Res<Speedrun>, mut texts: Query<&mut Text, With<SpeedrunText>>) {
+    if !run.is_changed() {
+        return;
+    }
+    let mut value = String::new();
+    for (i, difficulty) in GAUNTLET.iter().enumerate() {
+        let time = match i.cmp(&run.opponent) {
+            std::cmp::Ordering::Less => format_time(run.splits[i]),
+            std::cmp::Ordering::Equal => format_time(run.current),
+            std::cmp::Ordering::Greater => "-".into(),
+        };
+        let best = run.best.splits[i].map_or_else(|| "-".into(), format_time);
+        let _ = writeln!(value, "{:?}  {}  (best {})", difficulty, time, best);
+    }
+    let total = run.splits.iter().sum::<f32>() + run.current;
+    let best = run.best.total.map_or_else(|| "-".into(), format_time);
+    let _ = write!(value, "Total  {}  (best {})", format_time(total), best);
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = value.clone();
+    }
+}
+
+fn format_time(secs: f32) -> String {
+    format!("{}:{:04.1}", (secs / 60f32) as u32, secs % 60f32)
+}
@@ -0,0 +1,28 @@
+use bevy::{ecs::schedule::StateTransitionEvent, prelude::*};
+
+/// Despawns the entity, children and all, once its state is left. Spawn menus and effects that belong to
+/// one state with it rather than cleaning them up in an `OnExit` system.
+#[derive(Component, Debug, Clone)]
+pub struct StateScoped<S: States>(pub S);
+
+pub trait StateScopedAppExt {
+    fn enable_state_scoped_entities<S: States>(&mut self) -> &mut Self;
+}
+
+impl StateScopedAppExt for App {
+    fn enable_state_scoped_entities<S: States>(&mut self) -> &mut Self {
+        self.add_systems(StateTransition, despawn_state_scoped::<S>.after(apply_state_transition::<S>))
+    }
+}
+
+fn despawn_state_scoped<S: States>(
+    mut cmd: Commands,
+    mut transitions: EventReader<StateTransitionEvent<S>>,
+    entities: Query<(Entity, &StateScoped<S>)>,
+) {
+    for transition in transitions.read() {
+        for (entity, _) in entities.iter().filter(|(_, scoped)| scoped.0 == transition.before) {
+            cmd.entity(entity).despawn_recursive();
+        }
+    }
+}
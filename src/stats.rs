@@ -0,0 +1,71 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{profiles::Profile, storage};
+
+const STATS_FILE: &str = "stats.ron";
+const STATS_VERSION: u32 = 1;
+
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LifetimeStats {
+    pub version: u32,
+    pub matches_played: u32,
+    pub matches_won: u32,
+    pub points_scored: u32,
+    pub points_conceded: u32,
+    pub rallies: u32,
+    pub seconds_played: f64,
+}
+
+impl Default for LifetimeStats {
+    fn default() -> Self {
+        LifetimeStats {
+            version: STATS_VERSION,
+            matches_played: 0,
+            matches_won: 0,
+            points_scored: 0,
+            points_conceded: 0,
+            rallies: 0,
+            seconds_played: 0f64,
+        }
+    }
+}
+
+impl LifetimeStats {
+    pub fn load(profile: &Profile) -> Self {
+        let mut stats = storage::data_path(&profile.data_file(STATS_FILE))
+            .and_then(|path| storage::load_ron::<LifetimeStats>(&path))
+            .unwrap_or_default();
+        stats.version = STATS_VERSION;
+        stats
+    }
+
+    pub fn save(&self, profile: &Profile) -> Result<(), String> {
+        let path = storage::data_path(&profile.data_file(STATS_FILE)).ok_or("no data directory")?;
+        storage::save_ron(&path, self)
+    }
+
+    pub fn record_match(&mut self, points_for: u32, points_against: u32, won: bool, seconds: f32) {
+        self.matches_played += 1;
+        if won {
+            self.matches_won += 1;
+        }
+        self.points_scored += points_for;
+        self.points_conceded += points_against;
+        self.rallies += points_for + points_against;
+        self.seconds_played += seconds as f64;
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "Matches played: {}\nMatches won: {}\nPoints: {} - {}\nRallies: {}\nHours played: {:.1}",
+            self.matches_played,
+            self.matches_won,
+            self.points_scored,
+            self.points_conceded,
+            self.rallies,
+            self.seconds_played / 3600f64,
+        )
+    }
+}
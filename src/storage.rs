@@ -0,0 +1,133 @@
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+#[cfg(not(target_arch = "wasm32"))]
+mod backend {
+    use std::{
+        fs, io,
+        path::{Path, PathBuf},
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    use directories::ProjectDirs;
+
+    fn project_dirs() -> Option<ProjectDirs> {
+        ProjectDirs::from("", "ketexon", "KPong")
+    }
+
+    pub fn config_dir() -> Option<PathBuf> {
+        project_dirs().map(|dirs| dirs.config_dir().to_path_buf())
+    }
+
+    pub fn data_dir() -> Option<PathBuf> {
+        project_dirs().map(|dirs| dirs.data_dir().to_path_buf())
+    }
+
+    pub fn timestamp() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default()
+    }
+
+    pub fn read(path: &Path) -> Option<String> {
+        fs::read_to_string(path).ok()
+    }
+
+    pub fn write(path: &Path, contents: &str) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+        }
+        fs::write(path, contents).map_err(|err| err.to_string())
+    }
+
+    pub fn remove(path: &Path) -> Result<(), String> {
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The browser has no file system, so paths are just keys into local storage.
+#[cfg(target_arch = "wasm32")]
+mod backend {
+    use std::path::{Path, PathBuf};
+
+    fn local_storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok()?
+    }
+
+    fn key(path: &Path) -> String {
+        format!("kpong/{}", path.display())
+    }
+
+    pub fn config_dir() -> Option<PathBuf> {
+        Some(PathBuf::from("config"))
+    }
+
+    pub fn data_dir() -> Option<PathBuf> {
+        Some(PathBuf::from("data"))
+    }
+
+    pub fn timestamp() -> u64 {
+        (js_sys::Date::now() / 1000f64) as u64
+    }
+
+    pub fn read(path: &Path) -> Option<String> {
+        local_storage()?.get_item(&key(path)).ok()?
+    }
+
+    pub fn write(path: &Path, contents: &str) -> Result<(), String> {
+        local_storage()
+            .ok_or("local storage unavailable")?
+            .set_item(&key(path), contents)
+            .map_err(|err| format!("{:?}", err))
+    }
+
+    pub fn remove(path: &Path) -> Result<(), String> {
+        local_storage()
+            .ok_or("local storage unavailable")?
+            .remove_item(&key(path))
+            .map_err(|err| format!("{:?}", err))
+    }
+}
+
+pub fn config_path(file: &str) -> Option<PathBuf> {
+    backend::config_dir().map(|dir| dir.join(file))
+}
+
+pub fn data_path(file: &str) -> Option<PathBuf> {
+    backend::data_dir().map(|dir| dir.join(file))
+}
+
+/// Seconds since the Unix epoch, for naming files that shouldn't overwrite each other.
+pub fn timestamp() -> u64 {
+    backend::timestamp()
+}
+
+/// Reads a RON file, returning `None` if it is missing or can't be parsed.
+pub fn load_ron<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let contents = backend::read(path)?;
+    ron::from_str(&contents)
+        .map_err(|err| warn!("Ignoring invalid file {}: {}", path.display(), err))
+        .ok()
+}
+
+pub fn save_ron<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let contents = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+        .map_err(|err| err.to_string())?;
+    write(path, &contents)
+}
+
+/// Writes a text file, creating its directory first.
+pub fn write(path: &Path, contents: &str) -> Result<(), String> {
+    backend::write(path, contents).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+/// Deletes a file. One that's already gone counts as deleted.
+pub fn remove(path: &Path) -> Result<(), String> {
+    backend::remove(path)
+}
@@ -0,0 +1,257 @@
+use bevy::{prelude::*, sprite::Mesh2dHandle};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::GameConfig, settings::Settings, spawn_toast, Arena, Ball, Collider, Paddle, Side};
+
+/// How many colors each palette offers players to pick from.
+pub const SWATCHES: usize = 6;
+const STRIPE_HEIGHT: f32 = 2f32;
+/// Where the stripes cross a paddle, as shares of its half length.
+const STRIPE_OFFSETS: [f32; 3] = [-0.5f32, 0f32, 0.5f32];
+const STRIPE_COLOR: Color = Color::BLACK;
+const HIGH_CONTRAST_BACKGROUND: Color = Color::BLACK;
+const HIGH_CONTRAST_FOREGROUND: Color = Color::WHITE;
+const OUTLINE_COLOR: Color = Color::YELLOW;
+const OUTLINE_THICKNESS: f32 = 2f32;
+/// How much bigger text and the HUD are in high contrast, on top of the UI scale.
+pub const HIGH_CONTRAST_TEXT_SCALE: f32 = 1.5f32;
+
+/// The colors players pick from, with sets chosen to stay distinct under each kind of color blindness.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ColorPalette {
+    #[default]
+    Standard,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+}
+
+impl ColorPalette {
+    pub fn swatches(self) -> [(&'static str, Color); SWATCHES] {
+        match self {
+            ColorPalette::Standard => [
+                ("white", Color::WHITE),
+                ("red", Color::rgb(1f32, 0.35f32, 0.35f32)),
+                ("orange", Color::rgb(1f32, 0.6f32, 0.2f32)),
+                ("green", Color::rgb(0.4f32, 1f32, 0.4f32)),
+                ("blue", Color::rgb(0.4f32, 0.6f32, 1f32)),
+                ("purple", Color::rgb(0.8f32, 0.45f32, 1f32)),
+            ],
+            // Red and green run together, so these lean on blue against orange and yellow.
+            ColorPalette::Deuteranopia => [
+                ("white", Color::WHITE),
+                ("vermilion", Color::rgb_u8(213, 94, 0)),
+                ("yellow", Color::rgb_u8(240, 228, 66)),
+                ("sky blue", Color::rgb_u8(86, 180, 233)),
+                ("blue", Color::rgb_u8(0, 114, 178)),
+                ("pink", Color::rgb_u8(204, 121, 167)),
+            ],
+            // Reds look dark as well, so the warm colors are kept bright.
+            ColorPalette::Protanopia => [
+                ("white", Color::WHITE),
+                ("orange", Color::rgb_u8(230, 159, 0)),
+                ("yellow", Color::rgb_u8(240, 228, 66)),
+                ("sky blue", Color::rgb_u8(86, 180, 233)),
+                ("blue", Color::rgb_u8(0, 114, 178)),
+                ("grey", Color::rgb_u8(140, 140, 140)),
+            ],
+            // Blue and yellow run together, so these lean on red against teal.
+            ColorPalette::Tritanopia => [
+                ("white", Color::WHITE),
+                ("red", Color::rgb_u8(220, 50, 32)),
+                ("pink", Color::rgb_u8(255, 140, 170)),
+                ("teal", Color::rgb_u8(0, 160, 160)),
+                ("dark teal", Color::rgb_u8(0, 90, 90)),
+                ("grey", Color::rgb_u8(140, 140, 140)),
+            ],
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            ColorPalette::Standard => ColorPalette::Deuteranopia,
+            ColorPalette::Deuteranopia => ColorPalette::Protanopia,
+            ColorPalette::Protanopia => ColorPalette::Tritanopia,
+            ColorPalette::Tritanopia => ColorPalette::Standard,
+        }
+    }
+}
+
+/// A tint laid over the court in photo mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PhotoFilter {
+    #[default]
+    None,
+    Sepia,
+    Cool,
+    Night,
+}
+
+impl PhotoFilter {
+    pub fn tint(self) -> Option<Color> {
+        match self {
+            PhotoFilter::None => None,
+            PhotoFilter::Sepia => Some(Color::rgba(0.44f32, 0.26f32, 0.08f32, 0.35f32)),
+            PhotoFilter::Cool => Some(Color::rgba(0.1f32, 0.3f32, 0.6f32, 0.3f32)),
+            PhotoFilter::Night => Some(Color::rgba(0.02f32, 0.02f32, 0.15f32, 0.55f32)),
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            PhotoFilter::None => PhotoFilter::Sepia,
+            PhotoFilter::Sepia => PhotoFilter::Cool,
+            PhotoFilter::Cool => PhotoFilter::Night,
+            PhotoFilter::Night => PhotoFilter::None,
+        }
+    }
+}
+
+/// Which of the palette's swatches a paddle is drawn in. The lobby picks it for LAN matches.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq)]
+pub struct PaddleColor(pub usize);
+
+/// A stripe across the right paddle, so the two can be told apart without seeing color.
+#[derive(Component)]
+pub struct SidePattern(f32);
+
+/// A border behind a paddle or ball that only shows in high contrast.
+#[derive(Component)]
+pub struct Outline;
+
+pub fn decorate_bodies(
+    mut cmd: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    paddles: Query<(Entity, &Side), With<Paddle>>,
+    balls: Query<Entity, With<Ball>>,
+) {
+    let unit_mesh = Mesh2dHandle(meshes.add(Rectangle::new(1f32, 1f32)));
+    let stripe_mat = materials.add(STRIPE_COLOR);
+    let outline_mat = materials.add(OUTLINE_COLOR);
+    let outline = || {
+        (
+            ColorMesh2dBundle {
+                mesh: unit_mesh.clone(),
+                material: outline_mat.clone(),
+                transform: Transform::from_xyz(0f32, 0f32, -0.5f32),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            Outline,
+        )
+    };
+    for entity in balls.iter() {
+        cmd.entity(entity).with_children(|ball| {
+            ball.spawn(outline());
+        });
+    }
+    for (entity, side) in paddles.iter() {
+        cmd.entity(entity).insert(PaddleColor::default()).with_children(|paddle| {
+            paddle.spawn(outline());
+            if *side != Side::Right {
+                return;
+            }
+            for offset in STRIPE_OFFSETS {
+                paddle.spawn((
+                    ColorMesh2dBundle {
+                        mesh: unit_mesh.clone(),
+                        material: stripe_mat.clone(),
+                        transform: Transform::from_xyz(0f32, 0f32, 1f32),
+                        visibility: Visibility::Hidden,
+                        ..default()
+                    },
+                    SidePattern(offset),
+                ));
+            }
+        });
+    }
+}
+
+/// Ctrl+P steps through the color palettes.
+pub fn cycle_palette(
+    mut cmd: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    arena: Res<Arena>,
+    mut settings: ResMut<Settings>,
+) {
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if !ctrl || !keyboard_input.just_pressed(KeyCode::KeyP) {
+        return;
+    }
+    settings.accessibility.palette = settings.accessibility.palette.next();
+    spawn_toast(&mut cmd, &arena, format!("Colors: {:?}", settings.accessibility.palette));
+}
+
+/// Ctrl+H switches high contrast on and off.
+pub fn toggle_high_contrast(
+    mut cmd: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    arena: Res<Arena>,
+    mut settings: ResMut<Settings>,
+) {
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if !ctrl || !keyboard_input.just_pressed(KeyCode::KeyH) {
+        return;
+    }
+    settings.accessibility.high_contrast = !settings.accessibility.high_contrast;
+    let state = if settings.accessibility.high_contrast { "on" } else { "off" };
+    spawn_toast(&mut cmd, &arena, format!("High contrast {}", state));
+}
+
+/// Draws each paddle in its swatch from the current palette, or plain white on black in high contrast.
+/// The right paddle is striped under any palette picked for color blindness, and in high contrast.
+pub fn apply_theme(
+    settings: Res<Settings>,
+    mut applied: Local<Option<(ColorPalette, bool)>>,
+    mut default_clear: Local<Option<Color>>,
+    mut clear_color: ResMut<ClearColor>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut paddles: Query<(Ref<PaddleColor>, &mut Handle<ColorMaterial>)>,
+    mut patterns: Query<&mut Visibility, (With<SidePattern>, Without<Outline>)>,
+    mut outlines: Query<&mut Visibility, (With<Outline>, Without<SidePattern>)>,
+) {
+    let palette = settings.accessibility.palette;
+    let high_contrast = settings.accessibility.high_contrast;
+    let changed = *applied != Some((palette, high_contrast));
+    *applied = Some((palette, high_contrast));
+    let swatches = palette.swatches();
+    for (color, mut material) in paddles.iter_mut() {
+        if changed || color.is_changed() {
+            let color = if high_contrast { HIGH_CONTRAST_FOREGROUND } else { swatches[color.0 % SWATCHES].1 };
+            *material = materials.add(color);
+        }
+    }
+    if !changed {
+        return;
+    }
+    let default_clear = *default_clear.get_or_insert(clear_color.0);
+    clear_color.0 = if high_contrast { HIGH_CONTRAST_BACKGROUND } else { default_clear };
+    let shown = |on: bool| if on { Visibility::Inherited } else { Visibility::Hidden };
+    for mut pattern in patterns.iter_mut() {
+        *pattern = shown(palette != ColorPalette::Standard || high_contrast);
+    }
+    for mut outline in outlines.iter_mut() {
+        *outline = shown(high_contrast);
+    }
+}
+
+/// Keeps the stripes and outlines fitted to their paddle or ball as the config resizes it.
+pub fn fit_decorations(
+    config: Res<GameConfig>,
+    paddles: Query<&Collider, With<Paddle>>,
+    mut patterns: Query<(&Parent, &SidePattern, &mut Transform), Without<Outline>>,
+    mut outlines: Query<(&Parent, &mut Transform), With<Outline>>,
+) {
+    for (parent, pattern, mut transform) in patterns.iter_mut() {
+        let Ok(collider) = paddles.get(parent.get()) else {
+            continue;
+        };
+        transform.translation.y = pattern.0 * collider.half_size.y;
+        transform.scale = Vec3::new(collider.half_size.x * 2f32, STRIPE_HEIGHT, 1f32);
+    }
+    for (parent, mut transform) in outlines.iter_mut() {
+        let half_size = paddles.get(parent.get()).map_or(config.ball_half_size, |collider| collider.half_size);
+        transform.scale = ((half_size + OUTLINE_THICKNESS) * 2f32).extend(1f32);
+    }
+}
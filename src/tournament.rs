@@ -0,0 +1,261 @@
+use bevy::prelude::*;
+use rand::Rng;
+use serde::Serialize;
+
+use crate::{
+    chat::NetworkController, cli::Entrant, nearest_ball, server::DedicatedServer, storage, Ball, MatchOver, Score,
+    ServeRequested, Side, ENEMY_AIM_ERROR,
+};
+
+/// Between matches, so spectators can catch the result and the next pairing.
+const MATCH_BREAK: f32 = 10f32;
+/// Between one tournament ending and the next one starting.
+const TOURNAMENT_BREAK: f32 = 60f32;
+
+impl Entrant {
+    fn name(&self, seed: usize) -> String {
+        match self {
+            Entrant::Ai(difficulty) => format!("{:?} AI #{}", difficulty, seed + 1),
+            Entrant::Bot => "Bot".into(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct MatchResult {
+    round: u32,
+    left: String,
+    right: String,
+    left_score: i32,
+    right_score: i32,
+    winner: String,
+}
+
+/// A single-elimination bracket the dedicated server plays out between AIs and a bot, over and over.
+/// Everyone who connects watches; the bracket and results are written to `tournament.json` as they come in.
+#[derive(Resource, Serialize)]
+pub struct Tournament {
+    names: Vec<String>,
+    #[serde(skip)]
+    entrants: Vec<Entrant>,
+    edition: u32,
+    round: u32,
+    /// Entrants yet to play this round, in bracket order.
+    remaining: Vec<usize>,
+    /// This round's winners so far, on to the next one.
+    advancing: Vec<usize>,
+    /// Who's playing now, left then right.
+    current: Option<[usize; 2]>,
+    results: Vec<MatchResult>,
+    champion: Option<String>,
+    #[serde(skip)]
+    break_timer: Timer,
+    /// Each side's aim error this round, so an easier AI misses more.
+    #[serde(skip)]
+    aim: [f32; 2],
+}
+
+impl Tournament {
+    pub fn new(entrants: Vec<Entrant>) -> Self {
+        let names = entrants.iter().enumerate().map(|(seed, entrant)| entrant.name(seed)).collect();
+        let mut tournament = Tournament {
+            names,
+            entrants,
+            edition: 0,
+            round: 0,
+            remaining: Vec::new(),
+            advancing: Vec::new(),
+            current: None,
+            results: Vec::new(),
+            champion: None,
+            break_timer: Timer::from_seconds(MATCH_BREAK, TimerMode::Once),
+            aim: [0f32; 2],
+        };
+        tournament.start_edition();
+        tournament
+    }
+
+    fn start_edition(&mut self) {
+        self.edition += 1;
+        self.round = 1;
+        self.remaining = (0..self.entrants.len()).collect();
+        self.advancing.clear();
+        self.current = None;
+        self.results.clear();
+        self.champion = None;
+    }
+
+    /// The next pairing, or `None` once the tournament has a champion. An odd one out gets a bye.
+    fn next_pair(&mut self) -> Option<[usize; 2]> {
+        loop {
+            match self.remaining.len() {
+                0 if self.advancing.len() <= 1 => {
+                    self.champion = self.advancing.first().map(|&winner| self.names[winner].clone());
+                    return None;
+                },
+                0 => {
+                    self.round += 1;
+                    self.remaining = std::mem::take(&mut self.advancing);
+                },
+                1 => self.advancing.push(self.remaining.remove(0)),
+                _ => {
+                    let (a, b) = (self.remaining.remove(0), self.remaining.remove(0));
+                    return Some(if self.entrants[b] == Entrant::Bot { [b, a] } else { [a, b] });
+                },
+            }
+        }
+    }
+
+    fn finish_match(&mut self, left_won: bool, score: &Score) -> Option<MatchResult> {
+        let [left, right] = self.current.take()?;
+        let winner = if left_won { left } else { right };
+        self.advancing.push(winner);
+        let result = MatchResult {
+            round: self.round,
+            left: self.names[left].clone(),
+            right: self.names[right].clone(),
+            left_score: score.player,
+            right_score: score.enemy,
+            winner: self.names[winner].clone(),
+        };
+        self.results.push(result.clone());
+        Some(result)
+    }
+
+    /// The players' names left to right, for snapshots.
+    pub fn names(&self) -> [String; 2] {
+        match self.current {
+            Some([left, right]) => [self.names[left].clone(), self.names[right].clone()],
+            None => ["Next match soon".into(), "".into()],
+        }
+    }
+
+    fn publish(&self) {
+        let Some(path) = storage::data_path("tournament.json") else {
+            return;
+        };
+        let json = serde_json::to_string_pretty(self).expect("tournaments always serialize");
+        if let Err(err) = storage::write(&path, &json) {
+            warn!("Failed to publish the tournament: {}", err);
+        }
+    }
+}
+
+pub fn open_tournament(tournament: Res<Tournament>, mut server: ResMut<DedicatedServer>) {
+    server.close_seats();
+    info!("Running a tournament between {}", tournament.names.join(", "));
+    tournament.publish();
+}
+
+/// Starts the next match once the break is over, and a fresh tournament once the last one has a champion.
+pub fn schedule_matches(time: Res<Time<Real>>, mut tournament: ResMut<Tournament>, server: Res<DedicatedServer>) {
+    if tournament.current.is_some() || !tournament.break_timer.tick(time.delta()).finished() {
+        return;
+    }
+    if tournament.champion.is_some() {
+        tournament.start_edition();
+        server.announce(&format!("Tournament #{} is starting", tournament.edition));
+    }
+    match tournament.next_pair() {
+        Some(pair) => {
+            tournament.current = Some(pair);
+            let [left, right] = tournament.names();
+            server.announce(&format!("Round {}: {} vs {}", tournament.round, left, right));
+        },
+        None => {
+            let champion = tournament.champion.clone().unwrap_or_default();
+            server.announce(&format!("{} wins tournament #{}!", champion, tournament.edition));
+            tournament.break_timer = Timer::from_seconds(TOURNAMENT_BREAK, TimerMode::Once);
+        },
+    }
+    tournament.publish();
+}
+
+pub fn record_results(
+    mut match_over: EventReader<MatchOver>,
+    score: Res<Score>,
+    mut tournament: ResMut<Tournament>,
+    server: Res<DedicatedServer>,
+) {
+    for over in match_over.read() {
+        let Some(result) = tournament.finish_match(over.winner == Side::Left, &score) else {
+            continue;
+        };
+        server.announce(&format!(
+            "{} beat {} {}-{}",
+            result.winner,
+            if result.winner == result.left { &result.right } else { &result.left },
+            result.left_score.max(result.right_score),
+            result.left_score.min(result.right_score),
+        ));
+        tournament.break_timer = Timer::from_seconds(MATCH_BREAK, TimerMode::Once);
+        tournament.publish();
+    }
+}
+
+pub fn aim_entrants(mut tournament: ResMut<Tournament>) {
+    let Some(current) = tournament.current else {
+        return;
+    };
+    let mut rng = rand::thread_rng();
+    let aim = current.map(|entrant| match tournament.entrants[entrant] {
+        Entrant::Ai(difficulty) => {
+            let error = difficulty.aim_error(ENEMY_AIM_ERROR);
+            rng.gen_range(-error..=error)
+        },
+        Entrant::Bot => 0f32,
+    });
+    tournament.aim = aim;
+}
+
+/// Steers the AI entrants' paddles, and holds everyone still between matches so nobody serves.
+pub fn drive_entrants(
+    tournament: Res<Tournament>,
+    mut serve: ResMut<ServeRequested>,
+    mut paddles: Query<(&Transform, &mut NetworkController, &Side)>,
+    balls: Query<(&Transform, &Ball)>,
+) {
+    if tournament.current.is_none() {
+        serve.0 = false;
+    }
+    for (transform, mut controller, side) in paddles.iter_mut() {
+        let side = side.index();
+        let ball_y = nearest_ball(&balls, transform.translation).map_or(0f32, |(ball, _)| ball.translation.y);
+        controller.dir = match tournament.current.map(|current| tournament.entrants[current[side]]) {
+            None => 0,
+            Some(Entrant::Bot) => controller.dir,
+            Some(Entrant::Ai(_)) => (ball_y + tournament.aim[side] - transform.translation.y).signum() as i32,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Difficulty;
+
+    fn play_out(tournament: &mut Tournament) -> Vec<[usize; 2]> {
+        let mut pairs = Vec::new();
+        while let Some(pair) = tournament.next_pair() {
+            pairs.push(pair);
+            tournament.current = Some(pair);
+            // The lower seed always wins.
+            tournament.finish_match(pair[0] < pair[1], &Score { player: 11, enemy: 3 });
+        }
+        pairs
+    }
+
+    #[test]
+    fn plays_out_a_bracket() {
+        let easy = Entrant::Ai(Difficulty::Easy);
+        let mut tournament = Tournament::new(vec![easy, easy, easy, easy]);
+        assert_eq!(play_out(&mut tournament), vec![[0, 1], [2, 3], [0, 2]]);
+        assert_eq!(tournament.champion.as_deref(), Some("Easy AI #1"));
+
+        // The odd one out goes through, and the bot always plays on the left.
+        let mut tournament = Tournament::new(vec![easy, easy, Entrant::Bot]);
+        assert_eq!(play_out(&mut tournament), vec![[0, 1], [2, 0]]);
+        assert_eq!(tournament.results.len(), 2);
+        assert_eq!(tournament.champion.as_deref(), Some("Easy AI #1"));
+    }
+}
@@ -0,0 +1,111 @@
+use bevy::prelude::*;
+
+use crate::{prompts::Prompt, Arena, Ball, BallHitPaddle, Collider, GameState, LocalPaddle, Paddle};
+
+const FONT_SIZE: f32 = 16f32;
+/// Seconds of moving that count as having learned it.
+const MOVE_SECONDS: f32 = 0.75f32;
+/// How far from the middle of the paddle, as a share of its half height, a hit has to be to count as angled.
+const ANGLED_OFFSET: f32 = 0.5f32;
+/// Feeds come in slower than a real serve, so there's time to line up.
+const FEED_SPEED_SCALE: f32 = 0.6f32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TutorialStep {
+    Move,
+    Serve,
+    Return,
+    Angle,
+    Done,
+}
+
+/// A guided first match: each step's prompt stays up until the player does what it asks, and every serve
+/// is fed straight at their paddle.
+#[derive(Resource)]
+pub struct Tutorial {
+    step: TutorialStep,
+    moved: f32,
+}
+
+impl Default for Tutorial {
+    fn default() -> Self {
+        Tutorial { step: TutorialStep::Move, moved: 0f32 }
+    }
+}
+
+/// Serving waits until the player has learned to move, since moving is also how they serve.
+pub fn serve_allowed(tutorial: Option<Res<Tutorial>>) -> bool {
+    tutorial.map_or(true, |tutorial| tutorial.step != TutorialStep::Move)
+}
+
+#[derive(Component)]
+pub struct TutorialPrompt;
+
+pub fn spawn_tutorial_prompt(mut cmd: Commands, arena: Res<Arena>) {
+    cmd.spawn((
+        Text2dBundle {
+            text: Text::from_section("", TextStyle {
+                font_size: FONT_SIZE,
+                ..default()
+            })
+            .with_justify(JustifyText::Center),
+            transform: Transform::from_xyz(0f32, arena.half_size.y / 2f32, 4f32),
+            ..default()
+        },
+        TutorialPrompt,
+        Prompt::Tutorial(TutorialStep::Move),
+    ));
+}
+
+pub fn advance_tutorial(
+    time: Res<Time>,
+    state: Res<State<GameState>>,
+    mut tutorial: ResMut<Tutorial>,
+    mut paddle_hits: EventReader<BallHitPaddle>,
+    local_paddles: Query<(&Paddle, &Transform, &Collider), With<LocalPaddle>>,
+    balls: Query<&Transform, With<Ball>>,
+    mut prompts: Query<&mut Prompt, With<TutorialPrompt>>,
+) {
+    let offsets: Vec<f32> = paddle_hits.read()
+        .filter_map(|hit| {
+            let (_, paddle_trans, collider) = local_paddles.get(hit.paddle).ok()?;
+            let ball_trans = balls.get(hit.ball).ok()?;
+            Some(((ball_trans.translation.y - paddle_trans.translation.y) / collider.half_size.y).abs())
+        })
+        .collect();
+    let next = match tutorial.step {
+        TutorialStep::Move => {
+            if local_paddles.iter().any(|(paddle, _, _)| paddle.dir != 0) {
+                tutorial.moved += time.delta_seconds();
+            }
+            (tutorial.moved >= MOVE_SECONDS).then_some(TutorialStep::Serve)
+        },
+        TutorialStep::Serve => (*state.get() == GameState::Started).then_some(TutorialStep::Return),
+        TutorialStep::Return => (!offsets.is_empty()).then_some(TutorialStep::Angle),
+        TutorialStep::Angle => offsets.iter().any(|offset| *offset >= ANGLED_OFFSET).then_some(TutorialStep::Done),
+        TutorialStep::Done => None,
+    };
+    let Some(next) = next else {
+        return;
+    };
+    info!("Tutorial step {:?} done", tutorial.step);
+    tutorial.step = next;
+    for mut prompt in prompts.iter_mut() {
+        *prompt = Prompt::Tutorial(next);
+    }
+}
+
+/// Aims each serve at the player's paddle, slower than usual.
+pub fn feed_ball(
+    local_paddles: Query<&Transform, With<LocalPaddle>>,
+    mut balls: Query<(&mut Ball, &Transform)>,
+) {
+    let Some(paddle_trans) = local_paddles.iter().next() else {
+        return;
+    };
+    for (mut ball, ball_trans) in balls.iter_mut() {
+        let dir = (paddle_trans.translation - ball_trans.translation).truncate().normalize_or_zero();
+        ball.speed *= FEED_SPEED_SCALE;
+        ball.vel = dir * ball.speed;
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::TcpStream,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use bevy::prelude::*;
+
+use crate::{chat::NetworkController, Paddle, ProfileName, Side};
+
+const TWITCH_IRC: &str = "irc.chat.twitch.tv:6667";
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const VOTE_WINDOW: f32 = 1f32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vote {
+    Up,
+    Down,
+    Stop,
+}
+
+/// Votes from a Twitch channel's chat, counted over a short window and applied to the enemy paddle.
+#[derive(Resource)]
+pub struct ChatVotes {
+    votes: Mutex<Receiver<Vote>>,
+    tally: [u32; 3],
+    timer: Timer,
+}
+
+impl ChatVotes {
+    /// Joins `channel` anonymously on a background thread, reconnecting if the connection drops.
+    pub fn connect(channel: &str) -> Self {
+        let (sender, votes) = mpsc::channel();
+        let channel = channel.trim_start_matches('#').to_lowercase();
+        std::thread::spawn(move || loop {
+            match read_chat(&channel, &sender) {
+                // The game has shut down once nothing is listening.
+                Ok(()) => return,
+                Err(err) => warn!("Lost Twitch chat for #{}: {}", channel, err),
+            }
+            std::thread::sleep(RECONNECT_DELAY);
+        });
+        ChatVotes {
+            votes: Mutex::new(votes),
+            tally: [0; 3],
+            timer: Timer::from_seconds(VOTE_WINDOW, TimerMode::Repeating),
+        }
+    }
+
+    /// The most popular vote, or `None` if nobody voted or there was a tie for first.
+    fn winner(&self) -> Option<Vote> {
+        let [up, down, stop] = self.tally;
+        if up > down && up > stop {
+            Some(Vote::Up)
+        }
+        else if down > up && down > stop {
+            Some(Vote::Down)
+        }
+        else if stop > up && stop > down {
+            Some(Vote::Stop)
+        }
+        else {
+            None
+        }
+    }
+}
+
+fn read_chat(channel: &str, sender: &Sender<Vote>) -> io::Result<()> {
+    let mut stream = TcpStream::connect(TWITCH_IRC)?;
+    // Twitch lets anyone read chat under a justinfan nick, no account needed.
+    write!(stream, "NICK justinfan{}\r\nJOIN #{}\r\n", rand::random::<u32>() % 100000, channel)?;
+    info!("Joined Twitch chat for #{}", channel);
+
+    let reader = BufReader::new(stream.try_clone()?);
+    for line in reader.lines() {
+        let line = line?;
+        if let Some(server) = line.strip_prefix("PING ") {
+            write!(stream, "PONG {}\r\n", server)?;
+        }
+        else if let Some(vote) = parse_vote(&line) {
+            if sender.send(vote).is_err() {
+                return Ok(());
+            }
+        }
+    }
+    Err(io::ErrorKind::UnexpectedEof.into())
+}
+
+/// Reads a vote out of a raw IRC line like `:name!name@name.tmi.twitch.tv PRIVMSG #channel :up`.
+fn parse_vote(line: &str) -> Option<Vote> {
+    let (_, rest) = line.split_once(" PRIVMSG ")?;
+    let (_, message) = rest.split_once(" :")?;
+    match message.trim().trim_start_matches('!').to_lowercase().as_str() {
+        "up" | "u" => Some(Vote::Up),
+        "down" | "d" => Some(Vote::Down),
+        "stop" | "s" => Some(Vote::Stop),
+        _ => None,
+    }
+}
+
+pub fn attach_network_controller(
+    mut cmd: Commands,
+    paddles: Query<(Entity, &Side), With<Paddle>>,
+    mut names: Query<(&mut Text, &Side), With<ProfileName>>,
+) {
+    for (paddle, _) in paddles.iter().filter(|(_, side)| **side == Side::Right) {
+        cmd.entity(paddle).insert(NetworkController::default());
+    }
+    for (mut name, _) in names.iter_mut().filter(|(_, side)| **side == Side::Right) {
+        name.sections[0].value = "Chat".into();
+    }
+}
+
+pub fn tally_chat_votes(
+    time: Res<Time>,
+    mut chat: ResMut<ChatVotes>,
+    mut controllers: Query<&mut NetworkController>,
+) {
+    let chat = &mut *chat;
+    for vote in chat.votes.get_mut().unwrap().try_iter() {
+        chat.tally[vote as usize] += 1;
+    }
+    if !chat.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    if let Some(vote) = chat.winner() {
+        for mut controller in controllers.iter_mut() {
+            controller.dir = match vote {
+                Vote::Up => 1,
+                Vote::Down => -1,
+                Vote::Stop => 0,
+            };
+        }
+    }
+    chat.tally = [0; 3];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_chat_votes() {
+        let line = |message: &str| format!(":ana!ana@ana.tmi.twitch.tv PRIVMSG #kpong :{}", message);
+        assert_eq!(parse_vote(&line("up")), Some(Vote::Up));
+        assert_eq!(parse_vote(&line("!DOWN ")), Some(Vote::Down));
+        assert_eq!(parse_vote(&line("s")), Some(Vote::Stop));
+        assert_eq!(parse_vote(&line("go up please")), None);
+        assert_eq!(parse_vote("PING :tmi.twitch.tv"), None);
+    }
+}
@@ -0,0 +1,106 @@
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::{config::GameConfig, settings::Settings, Arena, GameRng, MatchClock};
+
+/// A gust comes once per period: a warning, then the wind, then calm until the next one.
+const GUST_PERIOD: f32 = 10f32;
+const WARNING_SECONDS: f32 = 1.5f32;
+const GUST_SECONDS: f32 = 3f32;
+/// The weakest gust, as a share of the config's wind.
+const MIN_GUST: f32 = 0.5f32;
+const BLINK_HZ: f32 = 4f32;
+const FONT_SIZE: f32 = 20f32;
+const INDICATOR_COLOR: Color = Color::rgb(0.6f32, 0.9f32, 1f32);
+
+/// The wind at one moment of the match. `push` is up when positive, as a share of the config's wind.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gust {
+    pub push: f32,
+    /// Set while a gust is on its way, to the direction it will blow.
+    pub coming: Option<f32>,
+}
+
+/// Gusts follow from the seed and the match clock alone, so replays and both ends of a rollback match agree.
+pub fn gust_at(seed: u64, elapsed: f32) -> Gust {
+    let period = (elapsed / GUST_PERIOD).floor();
+    let phase = elapsed - period * GUST_PERIOD;
+    let mut rng = ChaCha8Rng::seed_from_u64(seed ^ (period as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    let dir = if rng.gen_bool(0.5) { 1f32 } else { -1f32 };
+    let strength = rng.gen_range(MIN_GUST..=1f32);
+    let blowing = (WARNING_SECONDS..WARNING_SECONDS + GUST_SECONDS).contains(&phase);
+    Gust {
+        push: if blowing { dir * strength } else { 0f32 },
+        coming: (phase < WARNING_SECONDS).then_some(dir),
+    }
+}
+
+#[derive(Component)]
+pub struct WindIndicator;
+
+pub fn spawn_wind_indicator(mut cmd: Commands, arena: Res<Arena>) {
+    cmd.spawn((
+        Text2dBundle {
+            text: Text::from_section("", TextStyle {
+                font_size: FONT_SIZE,
+                color: INDICATOR_COLOR,
+                ..default()
+            }),
+            transform: Transform::from_xyz(0f32, arena.half_size.y - 48f32, 3f32),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        WindIndicator,
+    ));
+}
+
+/// Blinks the way the next gust will blow, then holds steady while it does. Blinking is left out with
+/// reduced motion.
+pub fn update_wind_indicator(
+    config: Res<GameConfig>,
+    settings: Res<Settings>,
+    rng: Res<GameRng>,
+    clock: Res<MatchClock>,
+    mut indicators: Query<(&mut Text, &mut Visibility), With<WindIndicator>>,
+) {
+    let gust = gust_at(rng.seed, clock.elapsed);
+    let blink_off = !settings.accessibility.reduced_motion && (clock.elapsed * BLINK_HZ).fract() < 0.5f32;
+    let shown = match (gust.coming, gust.push) {
+        _ if config.ball_wind == 0f32 => None,
+        (Some(_), _) if blink_off => None,
+        (Some(dir), _) => Some(dir),
+        (None, push) if push != 0f32 => Some(push.signum()),
+        _ => None,
+    };
+    for (mut text, mut visibility) in indicators.iter_mut() {
+        let Some(dir) = shown else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        let value = if dir > 0f32 { "^ WIND ^" } else { "v WIND v" };
+        if text.sections[0].value != value {
+            text.sections[0].value = value.into();
+        }
+        *visibility = Visibility::Visible;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gusts_are_warned_then_blow_then_calm() {
+        for seed in 0..32 {
+            let warning = gust_at(seed, 0.5f32);
+            let blowing = gust_at(seed, WARNING_SECONDS + 0.5f32);
+            let calm = gust_at(seed, WARNING_SECONDS + GUST_SECONDS + 0.5f32);
+            assert_eq!(warning.push, 0f32);
+            assert_eq!(warning.coming, Some(blowing.push.signum()));
+            assert!((MIN_GUST..=1f32).contains(&blowing.push.abs()) && blowing.coming.is_none());
+            assert_eq!(calm, Gust { push: 0f32, coming: None });
+            assert_eq!(blowing, gust_at(seed, WARNING_SECONDS + 0.5f32));
+        }
+    }
+}